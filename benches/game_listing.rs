@@ -0,0 +1,164 @@
+//! Latency of the game listing, loading the shipping, references and reviews of a page
+//! with one query per announce (as before) or with one query for the whole page.
+//!
+//! Needs a throwaway database, migrated and seeded by the benchmark :
+//! `BENCH_DB_URL="host=localhost user=postgres dbname=bench" cargo bench --bench game_listing`
+
+use std::collections::HashMap;
+use std::time::{Duration, Instant};
+
+use boardgame_finder::db::{
+    create_db_pool, get_db_client, refresh_listable_game_view, select_games_from_db,
+    select_references_by_ids_from_db, select_reviews_by_ids_from_db,
+    select_shipping_by_ids_from_db, upsert_announce_into_db,
+};
+use boardgame_finder::frontlib::server::State;
+use boardgame_finder::frontlib::{Filters, Pagination, Sort};
+use boardgame_finder::game::{Game, Reference, Reviewer};
+use boardgame_finder::migrations::{migrate_db, MigrationMode};
+use tokio_postgres::Client;
+
+const PAGE_SIZE: usize = 200;
+const ITERATIONS: usize = 20;
+/// seeded announces ids start there, far from okkazeo ones
+const FIRST_ID: u32 = 2_000_000_000;
+
+fn seed_game(id: u32) -> Game {
+    let mut game = Game::default();
+    game.okkazeo_announce.id = id;
+    game.okkazeo_announce.name = format!("Bench game {}", id);
+    game.okkazeo_announce.price = 20.0;
+    game.okkazeo_announce.extension = "Jeu".to_string();
+    game.okkazeo_announce.city = Some("Paris (75000)".to_string());
+    // in the future, so that they come first in the listing sorted by update date
+    game.okkazeo_announce.last_modification_date = chrono::Utc::now() + chrono::Duration::days(1);
+    game.okkazeo_announce.seller.id = FIRST_ID;
+    game.okkazeo_announce.seller.name = "bench".to_string();
+    for (shipper, price) in [("colissimo", 6.0), ("mondial_relay", 4.5)] {
+        game.okkazeo_announce
+            .shipping
+            .insert(shipper.to_string(), price);
+    }
+    for (name, price) in [
+        ("philibert", 35.0),
+        ("ludocortex", 33.0),
+        ("agorajeux", 34.0),
+    ] {
+        game.references.insert(
+            name.to_string(),
+            Reference {
+                name: name.to_string(),
+                price,
+                url: format!("https://{}.example/{}", name, id),
+            },
+        );
+    }
+    game.review.reviews.insert(
+        "bgg".to_string(),
+        Reviewer {
+            name: "bgg".to_string(),
+            url: format!("https://boardgamegeek.com/boardgame/{}", id),
+            note: 7.5,
+            number: 1000,
+        },
+    );
+    game.get_deal_advantage();
+    game
+}
+
+async fn n_plus_one(db_client: &Client, ids: &[i32]) {
+    let mut shippings = HashMap::new();
+    let mut references = HashMap::new();
+    let mut reviews = HashMap::new();
+    for id in ids {
+        let id = [*id];
+        shippings.extend(
+            select_shipping_by_ids_from_db(db_client, &id)
+                .await
+                .unwrap(),
+        );
+        references.extend(
+            select_references_by_ids_from_db(db_client, &id)
+                .await
+                .unwrap(),
+        );
+        reviews.extend(select_reviews_by_ids_from_db(db_client, &id).await.unwrap());
+    }
+    assert_eq!(references.len(), ids.len());
+}
+
+async fn batched(db_client: &Client, ids: &[i32]) {
+    let (_, references, _) = tokio::try_join!(
+        select_shipping_by_ids_from_db(db_client, ids),
+        select_references_by_ids_from_db(db_client, ids),
+        select_reviews_by_ids_from_db(db_client, ids),
+    )
+    .unwrap();
+    assert_eq!(references.len(), ids.len());
+}
+
+fn report(name: &str, mut durations: Vec<Duration>) {
+    durations.sort();
+    let mean = durations.iter().sum::<Duration>() / durations.len() as u32;
+    println!(
+        "{:<28} median {:>8.2?}  mean {:>8.2?}  min {:>8.2?}  max {:>8.2?}",
+        name,
+        durations[durations.len() / 2],
+        mean,
+        durations[0],
+        durations[durations.len() - 1]
+    );
+}
+
+#[tokio::main]
+async fn main() {
+    let Ok(db_url) = std::env::var("BENCH_DB_URL") else {
+        println!("BENCH_DB_URL is not defined, skipping the game listing benchmark");
+        return;
+    };
+    std::env::set_var("DB_URL", db_url);
+    let pool = create_db_pool().unwrap();
+    let mut db_client = get_db_client(&pool).await.unwrap();
+    migrate_db(&db_client, MigrationMode::Apply).await.unwrap();
+
+    for id in FIRST_ID..FIRST_ID + PAGE_SIZE as u32 {
+        upsert_announce_into_db(&mut db_client, &seed_game(id))
+            .await
+            .unwrap();
+    }
+    refresh_listable_game_view(&db_client).await.unwrap();
+    let ids: Vec<i32> = (FIRST_ID..FIRST_ID + PAGE_SIZE as u32)
+        .map(|id| id as i32)
+        .collect();
+    let state = State {
+        pagination: Pagination {
+            per_page: PAGE_SIZE,
+            page: 0,
+        },
+        filters: Filters {
+            vendor: Some("bench".to_string()),
+            ..Default::default()
+        },
+        sort: Sort::default(),
+    };
+
+    println!("{} announces, {} iterations", PAGE_SIZE, ITERATIONS);
+    let mut durations = (Vec::new(), Vec::new(), Vec::new());
+    for _ in 0..ITERATIONS {
+        let start = Instant::now();
+        n_plus_one(&db_client, &ids).await;
+        durations.0.push(start.elapsed());
+
+        let start = Instant::now();
+        batched(&db_client, &ids).await;
+        durations.1.push(start.elapsed());
+
+        let start = Instant::now();
+        let games = select_games_from_db(&db_client, &state).await.unwrap();
+        durations.2.push(start.elapsed());
+        assert_eq!(games.games.len(), PAGE_SIZE);
+    }
+    report("details, one query per game", durations.0);
+    report("details, batched", durations.1);
+    report("full page listing", durations.2);
+}
@@ -0,0 +1,12 @@
+CREATE TABLE IF NOT EXISTS "price_history" (
+  "ph_id" SERIAL PRIMARY KEY,
+  "ph_oa_id" integer REFERENCES okkazeo_announce("oa_id") ON DELETE CASCADE,
+  "ph_price" real,
+  "ph_date" timestamptz
+);
+CREATE INDEX IF NOT EXISTS idx_ph_oa_id ON price_history (ph_oa_id);
+
+-- seed the history with the current price of every announce
+INSERT INTO price_history (ph_oa_id, ph_price, ph_date)
+SELECT oa_id, oa_price, oa_last_modification_date FROM okkazeo_announce oa
+WHERE NOT EXISTS (SELECT 1 FROM price_history ph WHERE ph.ph_oa_id = oa.oa_id);
@@ -0,0 +1,15 @@
+ALTER TABLE seller ADD COLUMN IF NOT EXISTS "seller_rating" real;
+ALTER TABLE seller ADD COLUMN IF NOT EXISTS "seller_nb_ratings" integer;
+ALTER TABLE okkazeo_announce ADD COLUMN IF NOT EXISTS "oa_creation_date" timestamptz DEFAULT now();
+
+CREATE TABLE IF NOT EXISTS "announce_archive" (
+  "ar_oa_id" integer PRIMARY KEY,
+  "ar_seller" integer REFERENCES seller("seller_id"),
+  "ar_name" text,
+  "ar_price" real,
+  "ar_deal_percentage" integer,
+  "ar_creation_date" timestamptz,
+  "ar_removal_date" timestamptz
+);
+CREATE INDEX IF NOT EXISTS idx_ar_seller ON announce_archive (ar_seller);
+CREATE INDEX IF NOT EXISTS idx_oa_seller ON okkazeo_announce (oa_seller);
@@ -0,0 +1,17 @@
+CREATE TABLE IF NOT EXISTS "saved_search" (
+  "ss_id" SERIAL PRIMARY KEY,
+  "ss_name" text UNIQUE NOT NULL,
+  "ss_filters" text NOT NULL,
+  "ss_sort" text NOT NULL,
+  "ss_creation_date" timestamptz DEFAULT now(),
+  "ss_last_visit_date" timestamptz DEFAULT now()
+);
+
+CREATE TABLE IF NOT EXISTS "saved_search_match" (
+  "ssm_ss_id" integer REFERENCES saved_search("ss_id") ON DELETE CASCADE,
+  "ssm_oa_id" integer REFERENCES okkazeo_announce("oa_id") ON DELETE CASCADE,
+  "ssm_price" real,
+  "ssm_match_date" timestamptz DEFAULT now(),
+  PRIMARY KEY ("ssm_ss_id", "ssm_oa_id")
+);
+CREATE INDEX IF NOT EXISTS idx_ssm_match_date ON saved_search_match (ssm_match_date);
@@ -0,0 +1,7 @@
+CREATE TABLE IF NOT EXISTS "notification_sent" (
+  "ns_channel" text NOT NULL,
+  "ns_oa_id" integer REFERENCES okkazeo_announce("oa_id") ON DELETE CASCADE,
+  "ns_price" real,
+  "ns_date" timestamptz DEFAULT now(),
+  PRIMARY KEY ("ns_channel", "ns_oa_id")
+);
@@ -0,0 +1,18 @@
+CREATE TABLE IF NOT EXISTS "wishlist" (
+  "wl_id" SERIAL PRIMARY KEY,
+  "wl_name" text NOT NULL,
+  "wl_barcode" bigint,
+  "wl_bgg_id" integer,
+  "wl_max_price" real,
+  "wl_language" text,
+  "wl_edition" text,
+  "wl_creation_date" timestamptz DEFAULT now()
+);
+
+CREATE TABLE IF NOT EXISTS "wishlist_match" (
+  "wlm_wl_id" integer REFERENCES wishlist("wl_id") ON DELETE CASCADE,
+  "wlm_oa_id" integer REFERENCES okkazeo_announce("oa_id") ON DELETE CASCADE,
+  "wlm_price" real,
+  "wlm_match_date" timestamptz DEFAULT now(),
+  PRIMARY KEY ("wlm_wl_id", "wlm_oa_id")
+);
@@ -0,0 +1,3 @@
+ALTER TABLE wishlist ADD COLUMN IF NOT EXISTS "wl_priority" integer;
+ALTER TABLE wishlist ADD COLUMN IF NOT EXISTS "wl_want_to_buy" boolean DEFAULT false;
+CREATE UNIQUE INDEX IF NOT EXISTS idx_wl_bgg_id ON wishlist (wl_bgg_id);
@@ -0,0 +1,19 @@
+-- unique keys needed by the announce upsert (INSERT ... ON CONFLICT), keeping the
+-- most recent row of the duplicates left by interrupted inserts
+
+DELETE FROM deal a USING deal b
+WHERE a.deal_oa_id = b.deal_oa_id AND a.deal_id < b.deal_id;
+CREATE UNIQUE INDEX IF NOT EXISTS idx_deal_oa_id_unique ON deal (deal_oa_id);
+
+DELETE FROM shipping a USING shipping b
+WHERE a.ship_oa_id = b.ship_oa_id AND a.ship_shipper = b.ship_shipper AND a.ship_id < b.ship_id;
+CREATE UNIQUE INDEX IF NOT EXISTS idx_ship_oa_id_shipper ON shipping (ship_oa_id, ship_shipper);
+
+DELETE FROM reference a USING reference b
+WHERE a.ref_oa_id = b.ref_oa_id AND a.ref_name = b.ref_name AND a.ref_id < b.ref_id;
+CREATE UNIQUE INDEX IF NOT EXISTS idx_reference_oa_id_name ON reference (ref_oa_id, ref_name);
+
+DELETE FROM reviewer a USING reviewer b
+WHERE a.reviewer_oa_id = b.reviewer_oa_id AND a.reviewer_name = b.reviewer_name
+  AND a.reviewer_id < b.reviewer_id;
+CREATE UNIQUE INDEX IF NOT EXISTS idx_reviewer_oa_id_name ON reviewer (reviewer_oa_id, reviewer_name);
@@ -0,0 +1,14 @@
+-- full-text and trigram search on the announce names
+
+CREATE EXTENSION IF NOT EXISTS pg_trgm;
+
+-- unaccent is only stable, its dictionary could change, so it cannot be used in an
+-- index or a generated column without this wrapper
+CREATE OR REPLACE FUNCTION immutable_unaccent(text) RETURNS text
+  LANGUAGE sql IMMUTABLE PARALLEL SAFE STRICT
+  AS $$ SELECT public.unaccent('public.unaccent'::regdictionary, $1) $$;
+
+ALTER TABLE okkazeo_announce ADD COLUMN IF NOT EXISTS "oa_name_search" tsvector
+  GENERATED ALWAYS AS (to_tsvector('french', immutable_unaccent(oa_name))) STORED;
+CREATE INDEX IF NOT EXISTS idx_oa_name_search ON okkazeo_announce USING gin (oa_name_search);
+CREATE INDEX IF NOT EXISTS idx_oa_name_trgm ON okkazeo_announce USING gin (immutable_unaccent(oa_name) gin_trgm_ops);
@@ -0,0 +1,56 @@
+-- one row per listed announce, with its seller, deal and precomputed review average,
+-- cheapest reference and delivery, so that the listing needs neither join nor grouping.
+-- Refreshed by the backend after each feed cycle
+
+CREATE MATERIALIZED VIEW IF NOT EXISTS listable_game AS
+SELECT
+  oa.oa_id,
+  oa.oa_name,
+  oa.oa_name_search,
+  oa.oa_last_modification_date,
+  oa.oa_price,
+  oa.oa_url,
+  oa.oa_extension,
+  oa.oa_image,
+  oa.oa_city,
+  s.seller_id,
+  s.seller_name,
+  s.seller_url,
+  s.seller_is_pro,
+  s.seller_nb_announces,
+  s.seller_rating,
+  s.seller_nb_ratings,
+  d.deal_price,
+  d.deal_percentage,
+  -- announces without any review count as 0
+  COALESCE(r.average_note, 0) AS lg_average_note,
+  ref.ref_name AS lg_cheapest_ref_name,
+  ref.ref_price AS lg_cheapest_ref_price,
+  EXISTS (
+    SELECT 1 FROM shipping ship
+    WHERE ship.ship_oa_id = oa.oa_id AND ship.ship_shipper != 'hand_delivery'
+  ) AS lg_delivery
+FROM okkazeo_announce oa
+JOIN deal d ON d.deal_oa_id = oa.oa_id
+JOIN seller s ON s.seller_id = oa.oa_seller
+LEFT JOIN LATERAL (
+  SELECT SUM(CASE WHEN reviewer_number > 0 THEN reviewer_note * reviewer_number ELSE 0 END)
+    / SUM(CASE WHEN reviewer_number > 0 THEN reviewer_number ELSE 1 END) AS average_note
+  FROM reviewer
+  WHERE reviewer_oa_id = oa.oa_id
+) r ON true
+LEFT JOIN LATERAL (
+  SELECT ref_name, ref_price
+  FROM reference
+  WHERE ref_oa_id = oa.oa_id
+  ORDER BY ref_price
+  LIMIT 1
+) ref ON true;
+
+-- needed to refresh the view concurrently
+CREATE UNIQUE INDEX IF NOT EXISTS idx_lg_oa_id ON listable_game (oa_id);
+CREATE INDEX IF NOT EXISTS idx_lg_name_search ON listable_game USING gin (oa_name_search);
+CREATE INDEX IF NOT EXISTS idx_lg_name_trgm ON listable_game USING gin (immutable_unaccent(oa_name) gin_trgm_ops);
+CREATE INDEX IF NOT EXISTS idx_lg_last_modification_date ON listable_game (oa_last_modification_date);
+CREATE INDEX IF NOT EXISTS idx_lg_deal_price ON listable_game (deal_price);
+CREATE INDEX IF NOT EXISTS idx_lg_deal_percentage ON listable_game (deal_percentage);
@@ -0,0 +1,28 @@
+-- durable queue of the scraping work on announces, claimed by the workers of the backend,
+-- the crawler and the gamechecker with FOR UPDATE SKIP LOCKED. No foreign key on the
+-- announce, the enrich jobs concerning announces not stored yet
+
+CREATE TABLE IF NOT EXISTS "job" (
+  "job_id" bigserial PRIMARY KEY,
+  "job_kind" text NOT NULL,
+  "job_oa_id" integer NOT NULL,
+  "job_payload" jsonb NOT NULL DEFAULT '{}',
+  -- highest first
+  "job_priority" smallint NOT NULL DEFAULT 0,
+  -- pending, running (until job_locked_until, then claimable again) or dead
+  "job_status" text NOT NULL DEFAULT 'pending',
+  "job_attempts" integer NOT NULL DEFAULT 0,
+  "job_run_at" timestamptz NOT NULL DEFAULT now(),
+  "job_locked_until" timestamptz,
+  "job_last_error" text,
+  "job_creation_date" timestamptz NOT NULL DEFAULT now()
+);
+
+CREATE INDEX IF NOT EXISTS "job_claim_idx"
+  ON "job" ("job_kind", "job_priority" DESC, "job_run_at")
+  WHERE "job_status" <> 'dead';
+
+-- an announce has at most one live job of each kind, the dead ones being kept aside
+CREATE UNIQUE INDEX IF NOT EXISTS "job_live_unique_idx"
+  ON "job" ("job_kind", "job_oa_id")
+  WHERE "job_status" <> 'dead';
@@ -0,0 +1,4 @@
+-- when the shop prices of an announce were last queried, for the backend to refresh the
+-- stale ones. The announces stored before are considered fresh as of this migration
+ALTER TABLE okkazeo_announce ADD COLUMN IF NOT EXISTS "oa_references_date" timestamptz NOT NULL DEFAULT now();
+CREATE INDEX IF NOT EXISTS idx_oa_references_date ON okkazeo_announce (oa_references_date);
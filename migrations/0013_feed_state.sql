@@ -0,0 +1,8 @@
+-- most recent entry of each feed handled by the backend, to detect the entries missed
+-- while it was down or the feed moved faster than it is polled
+CREATE TABLE IF NOT EXISTS "feed_state" (
+  "fs_feed" text PRIMARY KEY,
+  "fs_last_id" integer NOT NULL,
+  "fs_last_date" timestamptz NOT NULL,
+  "fs_update_date" timestamptz NOT NULL DEFAULT now()
+);
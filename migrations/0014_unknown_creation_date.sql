@@ -0,0 +1,8 @@
+-- the announces stored before 0003 were stamped with the time that migration ran, which
+-- skews the days-to-sell statistics. Their creation date is unknown, the column default
+-- only applies to the announces inserted since. 0003 ran in the same transaction as its
+-- history row, so the stamp is its applied_on
+UPDATE okkazeo_announce SET oa_creation_date = NULL
+WHERE oa_creation_date = (SELECT applied_on FROM schema_migrations WHERE version = 3);
+UPDATE announce_archive SET ar_creation_date = NULL
+WHERE ar_creation_date = (SELECT applied_on FROM schema_migrations WHERE version = 3);
@@ -0,0 +1,14 @@
+-- when a job was dead-lettered, its announce not being queued again for a while, so that
+-- a job failing for good is not recreated on every run. Only the last dead job of an
+-- announce is kept
+ALTER TABLE job ADD COLUMN IF NOT EXISTS "job_dead_date" timestamptz;
+UPDATE job SET job_dead_date = now() WHERE job_status = 'dead';
+
+DELETE FROM job dead WHERE dead.job_status = 'dead' AND EXISTS (
+  SELECT 1 FROM job newer WHERE newer.job_kind = dead.job_kind
+  AND newer.job_oa_id = dead.job_oa_id AND newer.job_id > dead.job_id
+);
+
+CREATE INDEX IF NOT EXISTS "job_dead_idx"
+  ON "job" ("job_kind", "job_oa_id")
+  WHERE "job_status" = 'dead';
@@ -1,83 +1,300 @@
-use boardgame_finder::game::get_game_infos;
+use boardgame_finder::game::{get_game_infos, Game};
+use boardgame_finder::httpclient::env_or;
+use boardgame_finder::jobs::{
+    enqueue_job, process_jobs, refresh_priority, schedule_references_refresh, JobConfig, JobKind,
+    JobOutput, NewJob, RefreshConfig,
+};
 use boardgame_finder::metrics;
-use boardgame_finder::website::okkazeo::get_atom_feed;
+use boardgame_finder::notifier::{Dispatcher, Notification};
+use boardgame_finder::storage::{open_storage, Storage};
+use boardgame_finder::website::okkazeo::{get_atom_entry_price, get_atom_feed_since, FeedState};
 use lazy_static::lazy_static;
-use prometheus::{register_int_counter, IntCounter};
+use prometheus::{register_int_counter, register_int_counter_vec, IntCounter, IntCounterVec};
 use std::error;
+use std::sync::Arc;
 use std::time::{Duration, Instant};
 use tokio::task::JoinSet;
 use tokio::time;
 use tokio_postgres::Client;
 
 use boardgame_finder::db::{
-    connect_db, insert_announce_into_db, select_game_with_id_from_db, update_game_from_db,
+    get_db_client, record_saved_search_matches, record_wishlist_matches, DbPool,
 };
 
-async fn parse_game_feed(db_client: &Client) -> Result<(), Box<dyn error::Error + Send + Sync>> {
+/// the jobs consumed by the backend, the availability checks being left to the gamechecker
+const BACKEND_JOBS: &[JobKind] = &[
+    JobKind::EnrichAnnounce,
+    JobKind::RefreshReferences,
+    JobKind::FetchReviews,
+];
+/// number of announces stored by the jobs after which the listing is refreshed and the
+/// matches notified, without waiting for the queue to be empty
+const MATCH_BATCH: usize = 32;
+/// name of the okkazeo feed in the feed_state table
+const FEED: &str = "okkazeo";
+
+/// Store the updated announces of the feed, with the older pages published since the last
+/// run when the feed moved further than its first page. With Postgres, the new ones are
+/// queued to be enriched by the job workers, and saved searches and the wishlist are
+/// matched (and notified). Without it, the new ones are enriched right away
+async fn parse_game_feed(
+    storage: &dyn Storage,
+    db_client: Option<&Client>,
+    dispatcher: Option<&Dispatcher>,
+    max_catch_up_pages: u32,
+) -> Result<(), Box<dyn error::Error + Send + Sync>> {
     log::debug!("parsing game feed");
-    let feed = get_atom_feed().await?;
+    let last_seen = storage
+        .select_feed_state(FEED)
+        .await
+        .inspect_err(|e| log::error!("error db, cannot select feed state : {}", e))
+        .unwrap_or_default();
+    let feed = get_atom_feed_since(last_seen.as_ref(), max_catch_up_pages)
+        .await
+        .inspect_err(|e| e.record("okkazeo"))?;
     GET_ATOM_FEED.inc();
+    if feed.catch_up_pages > 0 {
+        let result = if !feed.complete {
+            "failed"
+        } else if feed.truncated {
+            "skipped"
+        } else {
+            "caught_up"
+        };
+        FEED_GAPS.with_label_values(&[result]).inc();
+        FEED_CATCH_UP_PAGES.inc_by(feed.catch_up_pages as u64);
+    }
+    // the last seen entry only moves once the ones before are handled, a catch-up that
+    // failed being tried again at the next run. A gap larger than the pages allowed is
+    // left to the crawler, the entry moving past it
+    let latest = FeedState::latest(&feed.entries)
+        .filter(|_| feed.complete)
+        .filter(|latest| {
+            last_seen
+                .as_ref()
+                .is_none_or(|last_seen| latest.last_date > last_seen.last_date)
+        });
 
     let mut tasks = JoinSet::new();
+    // new or repriced announces, to be evaluated against saved searches and the wishlist
+    let mut changed_games = Vec::new();
     log::debug!("checking {} games from feed", feed.entries.len());
     'outer: for entry in feed.entries {
         log::trace!("entry : {:?}", entry);
 
-        let price = entry
-            .summary
-            .as_ref()
-            .unwrap()
-            .content
-            .split('>')
-            .collect::<Vec<&str>>()
-            .last()
-            .unwrap()
-            .split('€')
-            .collect::<Vec<&str>>()
-            .first()
-            .unwrap()
-            .parse::<f32>()?;
+        let price = match get_atom_entry_price(&entry) {
+            Ok(price) => price,
+            Err(e) => {
+                e.record("okkazeo");
+                log::error!("skipping feed entry {} : {}", entry.id, e);
+                continue;
+            }
+        };
 
         // if same id, then it is an update
         let id = entry.id.parse::<u32>()?;
 
-        let fetched_game = select_game_with_id_from_db(db_client, id).await;
+        let fetched_game = match storage.select_game(id).await {
+            Ok(game) => game,
+            Err(e) => {
+                log::error!("error db, cannot select game {} : {}", id, e);
+                None
+            }
+        };
         if fetched_game.is_some() {
             let mut fetched_game = fetched_game.clone().unwrap();
             log::debug!("updating game {}", fetched_game.okkazeo_announce.name);
+            let repriced = fetched_game.okkazeo_announce.price != price;
             fetched_game.okkazeo_announce.last_modification_date =
                 entry.updated.unwrap_or_default();
             fetched_game.okkazeo_announce.price = price;
             fetched_game.get_deal_advantage();
 
-            if let Err(e) = update_game_from_db(db_client, &fetched_game).await {
+            if let Err(e) = storage.upsert_game(&fetched_game).await {
                 log::error!(
                     "error db, cannot update game {} : {}",
                     fetched_game.okkazeo_announce.name,
                     e
                 );
+            } else if repriced {
+                // the deal is recomputed with the stored shop prices until they are refreshed
+                if let Some(db_client) = db_client {
+                    let job = NewJob::new(JobKind::RefreshReferences, id)
+                        .priority(refresh_priority(Some(fetched_game.deal.deal_percentage)));
+                    if let Err(e) = enqueue_job(db_client, &job).await {
+                        log::error!("error db, cannot queue refresh of {} : {}", id, e);
+                    }
+                }
+                changed_games.push(fetched_game);
             }
             continue 'outer;
         }
 
-        tasks.spawn(async move { get_game_infos(Some(&entry), entry.id.parse::<u32>()?).await });
+        if let Some(db_client) = db_client {
+            let job = NewJob::new(JobKind::EnrichAnnounce, id).updated(entry.updated);
+            if let Err(e) = enqueue_job(db_client, &job).await {
+                log::error!("error db, cannot queue announce {} : {}", id, e);
+            }
+            continue 'outer;
+        }
+        tasks.spawn(async move { get_game_infos(Some(&entry), id).await });
     }
     while let Some(res) = tasks.join_next().await {
-        let game = res??;
+        let game = match res? {
+            Ok(game) => game,
+            Err(e) => {
+                e.record("okkazeo");
+                log::error!("cannot get game infos : {}", e);
+                continue;
+            }
+        };
         log::debug!("got result for game {}", game.okkazeo_announce.name);
 
-        if let Err(e) = insert_announce_into_db(db_client, &game).await {
+        if let Err(e) = storage.upsert_game(&game).await {
             log::error!(
                 "error db, cannot insert game {} : {}",
                 game.okkazeo_announce.name,
                 e
             );
+        } else {
+            changed_games.push(*game);
+        }
+    }
+
+    if let Some(latest) = latest {
+        if let Err(e) = storage.update_feed_state(FEED, &latest).await {
+            log::error!("error db, cannot update feed state : {}", e);
         }
     }
 
+    match_and_notify(storage, db_client, dispatcher, &changed_games).await;
+
     Ok(())
 }
 
+/// Refresh the listing, then match the new or repriced announces against the saved
+/// searches and the wishlist when a Postgres client is given, notifying the matches
+async fn match_and_notify(
+    storage: &dyn Storage,
+    db_client: Option<&Client>,
+    dispatcher: Option<&Dispatcher>,
+    changed_games: &[Game],
+) {
+    // the saved searches are matched against the view, so it is refreshed first
+    if let Err(e) = storage.refresh_listing().await {
+        log::error!("error db, cannot refresh listable games : {}", e);
+    }
+    let (Some(db_client), Some(dispatcher)) = (db_client, dispatcher) else {
+        return;
+    };
+
+    let changed_ids: Vec<i32> = changed_games
+        .iter()
+        .map(|game| game.okkazeo_announce.id as i32)
+        .collect();
+    match record_saved_search_matches(db_client, &changed_ids).await {
+        Ok(matches) => {
+            for (search, ids) in matches {
+                SAVED_SEARCH_MATCHES.inc_by(ids.len() as u64);
+                log::debug!("{} matches recorded for search {}", ids.len(), search.name);
+                let reason = format!("Recherche \"{}\"", search.name);
+                notify_matches(dispatcher, changed_games, &ids, &reason);
+            }
+        }
+        Err(e) => log::error!("error db, cannot record saved search matches : {}", e),
+    }
+
+    match record_wishlist_matches(db_client, changed_games).await {
+        Ok(matches) => {
+            for (item, ids) in matches {
+                WISHLIST_MATCHES.inc_by(ids.len() as u64);
+                log::debug!("{} matches recorded for wishlist {}", ids.len(), item.name);
+                let reason = format!("Liste de souhaits \"{}\"", item.name);
+                notify_matches(dispatcher, changed_games, &ids, &reason);
+            }
+        }
+        Err(e) => log::error!("error db, cannot record wishlist matches : {}", e),
+    }
+}
+
+/// Consume the enrichment jobs, the announces they store being matched and notified
+/// once the queue is empty, or every MATCH_BATCH announces
+async fn work_jobs(storage: Arc<dyn Storage>, pool: DbPool, dispatcher: Arc<Dispatcher>) {
+    let config = JobConfig::from_env();
+    log::info!("consuming jobs {} at a time", config.concurrency);
+
+    let mut changed_games = Vec::new();
+    loop {
+        let outputs = match process_jobs(&pool, BACKEND_JOBS, &config).await {
+            Ok(outputs) => outputs,
+            Err(e) => {
+                log::error!("cannot process jobs : {}", e);
+                Vec::new()
+            }
+        };
+        let idle = outputs.is_empty();
+        changed_games.extend(outputs.into_iter().filter_map(|output| match output {
+            JobOutput::Stored(game) => Some(*game),
+            _ => None,
+        }));
+
+        if !changed_games.is_empty() && (idle || changed_games.len() >= MATCH_BATCH) {
+            match get_db_client(&pool).await {
+                Ok(db_client) => {
+                    match_and_notify(
+                        storage.as_ref(),
+                        Some(&db_client),
+                        Some(&dispatcher),
+                        &changed_games,
+                    )
+                    .await;
+                    changed_games.clear();
+                }
+                Err(e) => log::error!("error db, cannot match stored announces : {}", e),
+            }
+        }
+        if idle {
+            time::sleep(config.poll_interval).await;
+        }
+    }
+}
+
+fn notify_matches(dispatcher: &Dispatcher, games: &[Game], ids: &[i32], reason: &str) {
+    for game in games
+        .iter()
+        .filter(|game| ids.contains(&(game.okkazeo_announce.id as i32)))
+    {
+        dispatcher.notify(&Notification::from_game(game, reason));
+    }
+}
+
+/// Queue the refresh of the shop prices of the announces as they get stale
+async fn schedule_refresh(pool: DbPool) {
+    let config = RefreshConfig::from_env();
+    if config.batch == 0 {
+        log::info!("shop prices refresh disabled");
+        return;
+    }
+    log::info!(
+        "refreshing shop prices older than {} hours",
+        config.max_age.as_secs() / 3600
+    );
+
+    loop {
+        match get_db_client(&pool).await {
+            Ok(db_client) => match schedule_references_refresh(&db_client, &config).await {
+                Ok(queued) => {
+                    log::debug!("{} shop prices refreshes queued", queued);
+                    REFERENCES_REFRESH_QUEUED.inc_by(queued as u64);
+                }
+                Err(e) => log::error!("error db, cannot queue shop prices refreshes : {}", e),
+            },
+            Err(e) => log::error!("error db, cannot queue shop prices refreshes : {}", e),
+        }
+        time::sleep(config.interval).await;
+    }
+}
+
 #[tokio::main]
 async fn main() {
     env_logger::init();
@@ -85,19 +302,48 @@ async fn main() {
     let backend_metrics_bind_addr =
         std::env::var("BACKEND_METRICS_ADDR").unwrap_or("127.0.0.1:3003".to_string());
 
-    let client = connect_db().await.expect("cannot connect to DB");
+    let (storage, pool) = open_storage().await.expect("cannot open storage");
+    // notifications are only sent with Postgres, which records them
+    let dispatcher = pool
+        .clone()
+        .map(|pool| Arc::new(Dispatcher::from_env(Arc::new(pool))));
 
     log::info!("starting program");
     let interval = Duration::from_secs(60 * 5);
+    let max_catch_up_pages = env_or("FEED_CATCH_UP_MAX_PAGES", 20);
     log::info!("parsing game feed every {} seconds", interval.as_secs());
 
     tokio::spawn(async { metrics::run_metrics(backend_metrics_bind_addr).await });
+    if let (Some(pool), Some(dispatcher)) = (pool.clone(), dispatcher.clone()) {
+        tokio::spawn(work_jobs(storage.clone(), pool.clone(), dispatcher));
+        tokio::spawn(schedule_refresh(pool));
+    }
 
     loop {
         let start = Instant::now();
         log::debug!("scraping time : {:?}", start);
-        if let Err(e) = parse_game_feed(&client).await {
-            log::error!("{}", e);
+        // a new connection is taken for each run, so that a lost one is replaced
+        let client = match &pool {
+            Some(pool) => get_db_client(pool).await.map(Some),
+            None => Ok(None),
+        };
+        match client {
+            Ok(client) => {
+                let client = client.as_deref().map(|client| &**client);
+                if let Err(e) = parse_game_feed(
+                    storage.as_ref(),
+                    client,
+                    dispatcher.as_deref(),
+                    max_catch_up_pages,
+                )
+                .await
+                {
+                    log::error!("{}", e);
+                }
+            }
+            // without a client the new announces could not be queued, the feed is
+            // parsed again at the next run
+            Err(e) => log::error!("error db, cannot parse game feed : {}", e),
         }
         let duration = start.elapsed();
 
@@ -110,4 +356,30 @@ async fn main() {
 lazy_static! {
     static ref GET_ATOM_FEED: IntCounter =
         register_int_counter!("get_atom_feed", "Number of time we get the atom feed").unwrap();
+    static ref FEED_GAPS: IntCounterVec = register_int_counter_vec!(
+        "feed_gaps",
+        "Number of time the atom feed did not reach the last seen entry, by catch-up result (caught_up, skipped, failed)",
+        &["result"]
+    )
+    .unwrap();
+    static ref FEED_CATCH_UP_PAGES: IntCounter = register_int_counter!(
+        "feed_catch_up_pages",
+        "Number of older atom feed pages fetched to fill the gaps"
+    )
+    .unwrap();
+    static ref SAVED_SEARCH_MATCHES: IntCounter = register_int_counter!(
+        "saved_search_matches",
+        "Number of announces matched by a saved search"
+    )
+    .unwrap();
+    static ref REFERENCES_REFRESH_QUEUED: IntCounter = register_int_counter!(
+        "references_refresh_queued",
+        "Number of announces queued for their shop prices to be refreshed"
+    )
+    .unwrap();
+    static ref WISHLIST_MATCHES: IntCounter = register_int_counter!(
+        "wishlist_matches",
+        "Number of announces matched by a wishlist item"
+    )
+    .unwrap();
 }
@@ -0,0 +1,40 @@
+use boardgame_finder::db::{
+    create_db_pool, get_db_client, insert_into_wishlist_table, record_wishlist_item_matches,
+};
+use boardgame_finder::migrations::{migrate_db, MigrationMode};
+use boardgame_finder::website::bgg::{get_bgg_collection, parse_bgg_collection};
+
+const USAGE: &str = "usage : bggimport <collection.xml> | bggimport --user <bgg username>";
+
+/// Import the wishlist and "want to buy" games of a bgg collection into the wishlist,
+/// from an exported xml file or directly from the bgg xml api
+#[tokio::main]
+async fn main() -> Result<(), anyhow::Error> {
+    env_logger::init();
+
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    let items = match args.as_slice() {
+        [flag, username] if flag == "--user" => get_bgg_collection(username).await?,
+        [path] if !path.starts_with("--") => parse_bgg_collection(&std::fs::read_to_string(path)?)?,
+        _ => return Err(anyhow::anyhow!(USAGE)),
+    };
+    log::info!("importing {} games from bgg collection", items.len());
+
+    let pool = create_db_pool()?;
+    let db_client = get_db_client(&pool).await?;
+    migrate_db(&db_client, MigrationMode::from_env()).await?;
+    let mut nb_matches = 0;
+    for item in &items {
+        let item_id = insert_into_wishlist_table(&db_client, item).await?;
+        let matching_ids = record_wishlist_item_matches(&db_client, item_id, item).await?;
+        log::debug!("{} : {} announces", item.name, matching_ids.len());
+        nb_matches += matching_ids.len();
+    }
+
+    log::info!(
+        "{} games imported, {} announces already matching",
+        items.len(),
+        nb_matches
+    );
+    Ok(())
+}
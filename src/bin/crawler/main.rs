@@ -1,14 +1,18 @@
-use boardgame_finder::game::get_game_infos;
+use boardgame_finder::jobs::{enqueue_job, process_jobs, JobConfig, JobKind, NewJob};
 use boardgame_finder::metrics;
+use boardgame_finder::migrations::{migrate_db, MigrationMode};
 
 use lazy_static::lazy_static;
 use prometheus::{register_int_counter, IntCounter};
 
-use boardgame_finder::db::{
-    connect_db, insert_announce_into_db, select_game_with_id_from_db, update_game_from_db,
-};
+use boardgame_finder::db::{create_db_pool, get_db_client, DbPool};
 use boardgame_finder::website::okkazeo::get_games_from_page;
 
+/// priority of the announces of the crawled pages, below the ones of the feed and their
+/// follow-up jobs. The crawler only runs the jobs up to it, the ones of the feed being
+/// left to the backend which notifies the announces it stores
+const CRAWLER_PRIORITY: i16 = 5;
+
 #[tokio::main]
 async fn main() {
     env_logger::init();
@@ -19,13 +23,24 @@ async fn main() {
     log::info!("starting program");
     tokio::spawn(async { metrics::run_metrics(backend_metrics_bind_addr).await });
 
-    let db_client = connect_db().await.unwrap();
+    let pool = create_db_pool().expect("cannot create DB pool");
+    migrate_db(
+        &get_db_client(&pool).await.expect("cannot connect to DB"),
+        MigrationMode::from_env(),
+    )
+    .await
+    .expect("cannot migrate DB");
 
+    let config = JobConfig {
+        max_priority: Some(CRAWLER_PRIORITY),
+        ..JobConfig::from_env()
+    };
     let mut page = 1;
     loop {
         CRAWLER_PAGE_CRAWLED.inc();
         match get_games_from_page(page).await {
             Err(e) => {
+                e.record("okkazeo");
                 log::error!(
                     "error getting game from page {} :{}, exiting crawler",
                     page,
@@ -35,30 +50,27 @@ async fn main() {
             }
             Ok(v) => {
                 log::info!("fetching {} games for page {}", v.len(), page);
-                for id in v {
-                    CRAWLER_GAME_CRAWLED.inc();
-                    let fetched_game = select_game_with_id_from_db(&db_client, id).await;
-                    match get_game_infos(None, id).await {
-                        Err(e) => log::error!("{}", e),
-                        Ok(g) => {
-                            if fetched_game.is_none() {
-                                if let Err(e) = insert_announce_into_db(&db_client, &g).await {
-                                    log::error!(
-                                        "error db, cannot insert game {} : {}",
-                                        g.okkazeo_announce.name,
-                                        e
-                                    );
+                let queued = match get_db_client(&pool).await {
+                    Ok(db_client) => {
+                        let mut queued = 0;
+                        for id in v {
+                            CRAWLER_GAME_CRAWLED.inc();
+                            let job =
+                                NewJob::new(JobKind::EnrichAnnounce, id).priority(CRAWLER_PRIORITY);
+                            match enqueue_job(&db_client, &job).await {
+                                Ok(true) => queued += 1,
+                                Ok(false) => {}
+                                Err(e) => {
+                                    log::error!("error db, cannot queue announce {} : {}", id, e)
                                 }
-                            } else if let Err(e) = update_game_from_db(&db_client, &g).await {
-                                log::error!(
-                                    "error db, cannot update game {} : {}",
-                                    g.okkazeo_announce.name,
-                                    e
-                                );
                             }
                         }
+                        queued
                     }
-                }
+                    Err(_) => 0,
+                };
+                log::debug!("{} games queued for page {}", queued, page);
+                drain_jobs(&pool, &config).await;
             }
         }
         page += 1;
@@ -66,6 +78,20 @@ async fn main() {
     log::info!("exiting crawler");
 }
 
+/// Enrich the queued announces until none is due, the page being crawled before the next
+async fn drain_jobs(pool: &DbPool, config: &JobConfig) {
+    loop {
+        match process_jobs(pool, &[JobKind::EnrichAnnounce], config).await {
+            Ok(outputs) if outputs.is_empty() => break,
+            Ok(_) => {}
+            Err(e) => {
+                log::error!("cannot process jobs : {}", e);
+                break;
+            }
+        }
+    }
+}
+
 lazy_static! {
     static ref CRAWLER_PAGE_CRAWLED: IntCounter =
         register_int_counter!("crawler_page_crawled", "Number of page crawled").unwrap();
@@ -16,7 +16,7 @@ async fn main() {
     set.spawn(async move { server::run_server(frontend_bind_addr).await });
     set.spawn(async move { metrics::run_metrics(frontend_metrics_bind_addr).await });
 
-    while let Some(_) = set.join_next().await {
+    while set.join_next().await.is_some() {
         log::info!("Main task over");
     }
 }
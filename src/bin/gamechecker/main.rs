@@ -1,13 +1,14 @@
+use boardgame_finder::jobs::{enqueue_job, process_jobs, JobConfig, JobKind, JobOutput, NewJob};
 use boardgame_finder::metrics;
+use boardgame_finder::migrations::{migrate_db, MigrationMode};
 use chrono::Duration;
 use lazy_static::lazy_static;
 use prometheus::{register_int_counter, IntCounter};
 use tokio::task::JoinSet;
 
 use boardgame_finder::db::{
-    connect_db, delete_from_all_table_with_id, select_intervalled_ids_from_oa_table_from_db,
+    create_db_pool, get_db_client, select_intervalled_ids_from_oa_table_from_db, DbPool,
 };
-use boardgame_finder::website::okkazeo::game_still_available;
 
 #[tokio::main]
 async fn main() {
@@ -16,25 +17,40 @@ async fn main() {
     let backend_metrics_bind_addr =
         std::env::var("BACKEND_METRICS_ADDR").unwrap_or("127.0.0.1:3003".to_string());
 
+    let pool = create_db_pool().expect("cannot create DB pool");
+    migrate_db(
+        &get_db_client(&pool).await.expect("cannot connect to DB"),
+        MigrationMode::from_env(),
+    )
+    .await
+    .expect("cannot migrate DB");
+
     log::info!("starting program");
     let mut set = JoinSet::new();
     set.spawn(async { metrics::run_metrics(backend_metrics_bind_addr).await });
-    set.spawn(async move { task(Duration::zero(), Duration::days(3)).await });
-    set.spawn(async move { task(Duration::days(3), Duration::days(10)).await });
-    set.spawn(async move { task(Duration::days(10), Duration::weeks(52 * 100)).await });
+    let task_pool = pool.clone();
+    set.spawn(async move { task(task_pool, Duration::zero(), Duration::days(3), 2).await });
+    let task_pool = pool.clone();
+    set.spawn(async move { task(task_pool, Duration::days(3), Duration::days(10), 1).await });
+    let task_pool = pool.clone();
+    set.spawn(
+        async move { task(task_pool, Duration::days(10), Duration::weeks(52 * 100), 0).await },
+    );
+    set.spawn(async move { work_jobs(pool).await });
 
     while let Some(Err(res)) = set.join_next().await {
         log::error!("error joining set : {}", res);
     }
 }
 
-async fn task(start_date_offset: Duration, duration: Duration) {
+/// Queue the availability check of the announces modified in a window of time, every
+/// hour at most. The checks of the most recent announces come first
+async fn task(pool: DbPool, start_date_offset: Duration, duration: Duration, priority: i16) {
     log::info!(
         "starting game checker task with start_date {:?} and duration {:?}",
         start_date_offset,
         duration
     );
-    let db_client = connect_db().await.unwrap();
     let min_loop_duration = Duration::hours(1);
 
     loop {
@@ -46,30 +62,33 @@ async fn task(start_date_offset: Duration, duration: Duration) {
             start_date,
             end_date
         );
-        let ids =
-            match select_intervalled_ids_from_oa_table_from_db(&db_client, end_date, start_date)
+        let queued = match get_db_client(&pool).await {
+            Ok(db_client) => {
+                let ids = match select_intervalled_ids_from_oa_table_from_db(
+                    &db_client, end_date, start_date,
+                )
                 .await
-            {
-                Ok(v) => v,
-                Err(e) => {
-                    log::error!("{}", e);
-                    vec![]
-                }
-            };
-
-        log::debug!("gamechecking {} games", ids.len());
-        for id in ids {
-            log::debug!("checking game with id {})", id,);
-            GAMECHECKER_CHECKED_GAME.inc();
-            if !game_still_available(id as u32).await {
-                // effectively removing ids that need to be removed
-                log::debug!("removing games with id {}", id);
-                if let Err(e) = delete_from_all_table_with_id(&db_client, id).await {
-                    GAMECHECKER_REMOVED_GAME.inc();
-                    log::error!("error deleting from db : {}", e);
+                {
+                    Ok(v) => v,
+                    Err(e) => {
+                        log::error!("{}", e);
+                        vec![]
+                    }
+                };
+                let mut queued = 0;
+                for id in ids {
+                    let job = NewJob::new(JobKind::CheckAvailability, id as u32).priority(priority);
+                    match enqueue_job(&db_client, &job).await {
+                        Ok(true) => queued += 1,
+                        Ok(false) => {}
+                        Err(e) => log::error!("error db, cannot queue check of {} : {}", id, e),
+                    }
                 }
+                queued
             }
-        }
+            Err(_) => 0,
+        };
+        log::debug!("gamechecking {} games", queued);
 
         let loop_duration = chrono::Utc::now() - start_loop_time;
         if loop_duration < min_loop_duration {
@@ -82,6 +101,34 @@ async fn task(start_date_offset: Duration, duration: Duration) {
     }
 }
 
+/// Consume the availability checks queued by the tasks
+async fn work_jobs(pool: DbPool) {
+    let config = JobConfig::from_env();
+    loop {
+        let outputs = match process_jobs(&pool, &[JobKind::CheckAvailability], &config).await {
+            Ok(outputs) => outputs,
+            Err(e) => {
+                log::error!("cannot process jobs : {}", e);
+                Vec::new()
+            }
+        };
+        if outputs.is_empty() {
+            tokio::time::sleep(config.poll_interval).await;
+            continue;
+        }
+        for output in outputs {
+            match output {
+                JobOutput::Failed => {}
+                JobOutput::Removed(_) => {
+                    GAMECHECKER_CHECKED_GAME.inc();
+                    GAMECHECKER_REMOVED_GAME.inc();
+                }
+                _ => GAMECHECKER_CHECKED_GAME.inc(),
+            }
+        }
+    }
+}
+
 lazy_static! {
     static ref GAMECHECKER_CHECKED_GAME: IntCounter = register_int_counter!(
         "gamechecker_checked_game",
@@ -1,75 +1,204 @@
 use std::collections::HashMap;
+use std::time::Duration;
 
 use chrono::{DateTime, Utc};
-use tokio_postgres::{Client, Error, NoTls, Row};
+use deadpool_postgres::{Manager, ManagerConfig, Pool, PoolError, RecyclingMethod, Runtime};
+use tokio_postgres::types::ToSql;
+use tokio_postgres::{Client, Error, NoTls, Row, Transaction};
 
+use crate::filter_query::{escape_like, FilterQuery};
 use crate::frontlib::server::State;
+use crate::website::okkazeo::FeedState;
+use crate::wishlist::WishlistItem;
 use crate::{
-    frontlib::Filters,
-    game::{Deal, Game, Games, OkkazeoAnnounce, Reference, Review, Reviewer, Seller},
+    frontlib::{Filters, SavedSearch, Sort},
+    game::{
+        ArchivedAnnounce, Deal, Game, Games, OkkazeoAnnounce, PricePoint, Reference, Review,
+        Reviewer, Seller, SellerStats,
+    },
 };
 
 use lazy_static::lazy_static;
-use prometheus::{register_int_counter_vec, IntCounterVec};
+use prometheus::{register_int_counter_vec, register_int_gauge_vec, IntCounterVec, IntGaugeVec};
+
+/// max time to open a connection, to check it or to wait for an idle one
+const POOL_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Pool of postgres connections shared by the tasks of a binary
+pub type DbPool = deadpool_postgres::Pool;
+
+/// Create the connection pool from DB_URL, sized by DB_POOL_SIZE (default 8) and with a
+/// DB_STATEMENT_TIMEOUT in seconds (default 30). Connections are opened lazily, checked
+/// before being handed out and replaced when the server closed them
+pub fn create_db_pool() -> Result<DbPool, anyhow::Error> {
+    let db_url = std::env::var("DB_URL").map_err(|_| anyhow::anyhow!("DB_URL is not defined"))?;
+    let pool_size = match std::env::var("DB_POOL_SIZE") {
+        Ok(v) => v.parse::<usize>()?,
+        Err(_) => 8,
+    };
+    let statement_timeout = match std::env::var("DB_STATEMENT_TIMEOUT") {
+        Ok(v) => Duration::from_secs(v.parse::<u64>()?),
+        Err(_) => Duration::from_secs(30),
+    };
+
+    let mut pg_config = db_url.parse::<tokio_postgres::Config>()?;
+    pg_config.options(format!(
+        "-c statement_timeout={}",
+        statement_timeout.as_millis()
+    ));
+    let manager = Manager::from_config(
+        pg_config,
+        NoTls,
+        ManagerConfig {
+            recycling_method: RecyclingMethod::Verified,
+        },
+    );
 
-pub async fn connect_db() -> Result<Client, Error> {
-    let db_url = std::env::var("DB_URL").expect("DB_URL is not defined");
+    log::info!("creating DB pool of {} connections", pool_size);
+    let pool = Pool::builder(manager)
+        .max_size(pool_size)
+        .runtime(Runtime::Tokio1)
+        .create_timeout(Some(POOL_TIMEOUT))
+        .wait_timeout(Some(POOL_TIMEOUT))
+        .recycle_timeout(Some(POOL_TIMEOUT))
+        .build()?;
 
-    log::info!("connecting to DB");
-    let (client, connection) = tokio_postgres::connect(&db_url, NoTls).await?;
-    log::info!("connected to DB");
+    Ok(pool)
+}
 
-    tokio::spawn(async move {
-        if let Err(e) = connection.await {
-            log::error!("connection error : {}", e);
-        }
-    });
+/// Get a connection from the pool, opening a new one if none is idle
+pub async fn get_db_client(pool: &DbPool) -> Result<deadpool_postgres::Client, PoolError> {
+    let res = pool.get().await;
+    let status = pool.status();
+    DB_POOL_CONNECTIONS
+        .with_label_values(&["size"])
+        .set(status.size as i64);
+    DB_POOL_CONNECTIONS
+        .with_label_values(&["available"])
+        .set(status.available as i64);
+    if let Err(e) = &res {
+        log::error!("cannot get a DB connection from the pool : {}", e);
+    }
 
-    Ok(client)
+    res
 }
 
-pub async fn delete_from_all_table_with_id(db_client: &Client, id: i32) -> Result<(), Error> {
-    db_client
+/// Archive an announce and remove it with its deals, shipping, references and reviews,
+/// all in one transaction. The seller's number of announces is the one scraped with its
+/// next announce
+pub async fn delete_from_all_table_with_id(db_client: &mut Client, id: i32) -> Result<(), Error> {
+    let transaction = db_client.transaction().await?;
+    insert_into_announce_archive_table(&transaction, id).await?;
+
+    transaction
         .execute("DELETE FROM deal WHERE deal_oa_id = $1", &[&id])
         .await?;
     DB_IO.with_label_values(&["delete", "deal"]).inc();
 
-    db_client
+    transaction
         .execute("DELETE FROM shipping WHERE ship_oa_id = $1", &[&id])
         .await?;
     DB_IO.with_label_values(&["delete", "shipping"]).inc();
 
-    db_client
+    transaction
         .execute("DELETE FROM reference WHERE ref_oa_id = $1", &[&id])
         .await?;
     DB_IO.with_label_values(&["delete", "reference"]).inc();
 
-    db_client
+    transaction
         .execute("DELETE FROM reviewer WHERE reviewer_oa_id = $1", &[&id])
         .await?;
     DB_IO.with_label_values(&["delete", "reviewer"]).inc();
 
-    db_client
-        .execute("UPDATE seller SET seller_nb_announces = seller_nb_announces - 1 WHERE seller_id = (SELECT oa_seller FROM okkazeo_announce WHERE oa_id = $1);", &[&id])
-        .await?;
-    DB_IO.with_label_values(&["update", "seller"]).inc();
-
-    db_client
+    transaction
         .execute("DELETE FROM okkazeo_announce WHERE oa_id = $1", &[&id])
         .await?;
     DB_IO
         .with_label_values(&["delete", "okkazeo_announce"])
         .inc();
 
+    transaction.commit().await
+}
+
+/// Keep a trace of an announce that is about to be removed, so that seller statistics
+/// (average discount, time to sell) can still be computed afterwards
+pub async fn insert_into_announce_archive_table(
+    transaction: &Transaction<'_>,
+    id: i32,
+) -> Result<(), Error> {
+    transaction
+        .execute(
+            "INSERT INTO announce_archive (ar_oa_id, ar_seller, ar_name, ar_price, ar_deal_percentage, ar_creation_date, ar_removal_date)
+                SELECT oa.oa_id, oa.oa_seller, oa.oa_name, oa.oa_price, COALESCE(d.deal_percentage, 0), oa.oa_creation_date, now()
+                FROM okkazeo_announce oa
+                LEFT JOIN deal d on d.deal_oa_id = oa.oa_id
+                WHERE oa.oa_id = $1
+                ON CONFLICT (ar_oa_id) DO NOTHING",
+            &[&id],
+        )
+        .await?;
+    DB_IO
+        .with_label_values(&["insert", "announce_archive"])
+        .inc();
+
     Ok(())
 }
 
-pub async fn insert_into_okkazeo_announce_table(
-    db_client: &Client,
-    game: &Box<Game>,
+async fn upsert_into_seller_table(
+    transaction: &Transaction<'_>,
+    seller: &Seller,
 ) -> Result<(), Error> {
-    let okkazeo_insert_req = format!(
-        r#"INSERT INTO okkazeo_announce ({}, {}, {}, {}, {}, {}, {}, {}, {}, {}) VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10)"#,
+    // an unknown rating (the profile could not be fetched) keeps the stored one
+    let rating = seller.known_rating();
+    let seller_upsert_req = format!(
+        r#"INSERT INTO seller ({}, {}, {}, {}, {}, {}, {}) VALUES ($1, $2, $3, $4, $5, $6, $7)
+            ON CONFLICT (seller_id) DO UPDATE SET
+            seller_nb_announces = EXCLUDED.seller_nb_announces,
+            seller_rating = COALESCE(EXCLUDED.seller_rating, seller.seller_rating),
+            seller_nb_ratings = COALESCE(EXCLUDED.seller_nb_ratings, seller.seller_nb_ratings)"#,
+        "seller_id",
+        "seller_name",
+        "seller_url",
+        "seller_nb_announces",
+        "seller_is_pro",
+        "seller_rating",
+        "seller_nb_ratings",
+    );
+    transaction
+        .execute(
+            &seller_upsert_req,
+            &[
+                &(seller.id as i32),
+                &seller.name,
+                &seller.url,
+                &(seller.nb_announces as i32),
+                &seller.is_pro,
+                &rating.map(|(rating, _)| rating),
+                &rating.map(|(_, nb_ratings)| nb_ratings as i32),
+            ],
+        )
+        .await?;
+    DB_IO.with_label_values(&["upsert", "seller"]).inc();
+
+    Ok(())
+}
+
+async fn upsert_into_okkazeo_announce_table(
+    transaction: &Transaction<'_>,
+    game: &Game,
+) -> Result<(), Error> {
+    let okkazeo_upsert_req = format!(
+        r#"INSERT INTO okkazeo_announce ({}, {}, {}, {}, {}, {}, {}, {}, {}, {}) VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10)
+            ON CONFLICT (oa_id) DO UPDATE SET
+            oa_last_modification_date = EXCLUDED.oa_last_modification_date,
+            oa_name = EXCLUDED.oa_name,
+            oa_image = EXCLUDED.oa_image,
+            oa_price = EXCLUDED.oa_price,
+            oa_url = EXCLUDED.oa_url,
+            oa_extension = EXCLUDED.oa_extension,
+            oa_seller = EXCLUDED.oa_seller,
+            oa_barcode = EXCLUDED.oa_barcode,
+            oa_city = EXCLUDED.oa_city"#,
         "oa_id",
         "oa_last_modification_date",
         "oa_name",
@@ -81,9 +210,9 @@ pub async fn insert_into_okkazeo_announce_table(
         "oa_barcode",
         "oa_city",
     );
-    let _ = db_client
-        .query(
-            &okkazeo_insert_req,
+    transaction
+        .execute(
+            &okkazeo_upsert_req,
             &[
                 &(game.okkazeo_announce.id as i32),
                 &game.okkazeo_announce.last_modification_date,
@@ -103,252 +232,204 @@ pub async fn insert_into_okkazeo_announce_table(
         )
         .await?;
     DB_IO
-        .with_label_values(&["insert", "okkazeo_announce"])
+        .with_label_values(&["upsert", "okkazeo_announce"])
         .inc();
 
     Ok(())
 }
 
-pub async fn insert_into_shipping_table(
-    db_client: &Client,
+async fn upsert_into_deal_table(
+    transaction: &Transaction<'_>,
     id: i32,
-    shipping: &HashMap<String, f32>,
+    deal: &Deal,
 ) -> Result<(), Error> {
-    let seller_insert_req = format!(
-        r#"INSERT INTO shipping ({}, {}, {}) VALUES ($1, $2, $3)"#,
-        "ship_oa_id", "ship_shipper", "ship_price",
+    let deal_upsert_req = format!(
+        r#"INSERT INTO deal ({}, {}, {}) VALUES ($1, $2, $3)
+            ON CONFLICT (deal_oa_id) DO UPDATE SET
+            deal_price = EXCLUDED.deal_price,
+            deal_percentage = EXCLUDED.deal_percentage"#,
+        "deal_oa_id", "deal_price", "deal_percentage",
     );
-
-    for (key, value) in shipping.iter() {
-        let _ = db_client
-            .query(&seller_insert_req, &[&id, &key, &value])
-            .await?;
-    }
-    DB_IO.with_label_values(&["insert", "shipping"]).inc();
+    transaction
+        .execute(
+            &deal_upsert_req,
+            &[&id, &deal.deal_price, &deal.deal_percentage],
+        )
+        .await?;
+    DB_IO.with_label_values(&["upsert", "deal"]).inc();
 
     Ok(())
 }
 
-pub async fn insert_into_seller_table(db_client: &Client, seller: &Seller) -> Result<(), Error> {
-    log::debug!("insertin into seller table");
-    let seller_insert_req = format!(
-        r#"INSERT INTO seller ({}, {}, {}, {}, {}) VALUES ($1, $2, $3, $4, $5)"#,
-        "seller_id", "seller_name", "seller_url", "seller_nb_announces", "seller_is_pro",
-    );
-    let _ = db_client
-        .query(
-            &seller_insert_req,
-            &[
-                &(seller.id as i32),
-                &seller.name,
-                &seller.url,
-                &(seller.nb_announces as i32),
-                &seller.is_pro,
-            ],
+async fn upsert_into_shipping_table(
+    transaction: &Transaction<'_>,
+    id: i32,
+    shipping: &HashMap<String, f32>,
+) -> Result<(), Error> {
+    let shippers: Vec<&String> = shipping.keys().collect();
+    transaction
+        .execute(
+            "DELETE FROM shipping WHERE ship_oa_id = $1 AND NOT (ship_shipper = ANY($2))",
+            &[&id, &shippers],
         )
         .await?;
-    DB_IO.with_label_values(&["insert", "seller"]).inc();
+    DB_IO.with_label_values(&["delete", "shipping"]).inc();
 
-    Ok(())
-}
-pub async fn insert_into_deal_table(db_client: &Client, id: i32, deal: &Deal) -> Result<(), Error> {
-    let deal_insert_req = format!(
-        r#"INSERT INTO deal ({}, {}, {}) VALUES ($1, $2, $3)"#,
-        "deal_oa_id", "deal_price", "deal_percentage",
+    let shipping_upsert_req = format!(
+        r#"INSERT INTO shipping ({}, {}, {}) VALUES ($1, $2, $3)
+            ON CONFLICT (ship_oa_id, ship_shipper) DO UPDATE SET ship_price = EXCLUDED.ship_price"#,
+        "ship_oa_id", "ship_shipper", "ship_price",
     );
-    let _ = db_client
-        .query(
-            &deal_insert_req,
-            &[&id, &deal.deal_price, &deal.deal_percentage],
-        )
-        .await?;
-    DB_IO.with_label_values(&["insert", "deal"]).inc();
+    for (key, value) in shipping.iter() {
+        transaction
+            .execute(&shipping_upsert_req, &[&id, &key, &value])
+            .await?;
+    }
+    DB_IO.with_label_values(&["upsert", "shipping"]).inc();
 
     Ok(())
 }
 
-pub async fn insert_into_reference_table(
-    db_client: &Client,
+async fn upsert_into_reference_table(
+    transaction: &Transaction<'_>,
     id: i32,
     references: &HashMap<String, Reference>,
 ) -> Result<(), Error> {
-    let references_insert_req = format!(
-        r#"INSERT INTO reference ({}, {}, {}, {}) VALUES ($1, $2, $3, $4)"#,
+    let names: Vec<&String> = references.values().map(|val| &val.name).collect();
+    transaction
+        .execute(
+            "DELETE FROM reference WHERE ref_oa_id = $1 AND NOT (ref_name = ANY($2))",
+            &[&id, &names],
+        )
+        .await?;
+    DB_IO.with_label_values(&["delete", "reference"]).inc();
+
+    let references_upsert_req = format!(
+        r#"INSERT INTO reference ({}, {}, {}, {}) VALUES ($1, $2, $3, $4)
+            ON CONFLICT (ref_oa_id, ref_name) DO UPDATE SET
+            ref_price = EXCLUDED.ref_price,
+            ref_url = EXCLUDED.ref_url"#,
         "ref_oa_id", "ref_name", "ref_price", "ref_url",
     );
-
     for val in references.values() {
-        let _ = db_client
-            .query(
-                &references_insert_req,
+        transaction
+            .execute(
+                &references_upsert_req,
                 &[&id, &val.name, &val.price, &val.url],
             )
             .await?;
     }
-    DB_IO.with_label_values(&["insert", "reference"]).inc();
+    DB_IO.with_label_values(&["upsert", "reference"]).inc();
+
     Ok(())
 }
 
-pub async fn insert_into_reviewer_table(
-    db_client: &Client,
+async fn upsert_into_reviewer_table(
+    transaction: &Transaction<'_>,
     id: i32,
-    reviewers: &HashMap<std::string::String, Reviewer>,
+    reviewers: &HashMap<String, Reviewer>,
 ) -> Result<(), Error> {
-    let references_insert_req = format!(
-        r#"INSERT INTO reviewer ({}, {}, {}, {}, {}) VALUES ($1, $2, $3, $4, $5)"#,
+    let names: Vec<&String> = reviewers.values().map(|val| &val.name).collect();
+    transaction
+        .execute(
+            "DELETE FROM reviewer WHERE reviewer_oa_id = $1 AND NOT (reviewer_name = ANY($2))",
+            &[&id, &names],
+        )
+        .await?;
+    DB_IO.with_label_values(&["delete", "reviewer"]).inc();
+
+    let reviewers_upsert_req = format!(
+        r#"INSERT INTO reviewer ({}, {}, {}, {}, {}) VALUES ($1, $2, $3, $4, $5)
+            ON CONFLICT (reviewer_oa_id, reviewer_name) DO UPDATE SET
+            reviewer_url = EXCLUDED.reviewer_url,
+            reviewer_note = EXCLUDED.reviewer_note,
+            reviewer_number = EXCLUDED.reviewer_number"#,
         "reviewer_oa_id", "reviewer_name", "reviewer_url", "reviewer_note", "reviewer_number",
     );
-
     for val in reviewers.values() {
-        let _ = db_client
-            .query(
-                &references_insert_req,
+        transaction
+            .execute(
+                &reviewers_upsert_req,
                 &[&id, &val.name, &val.url, &val.note, &(val.number as i32)],
             )
             .await?;
     }
-    DB_IO.with_label_values(&["insert", "reviewer"]).inc();
-    Ok(())
-}
-
-pub async fn insert_announce_into_db(db_client: &Client, game: &Box<Game>) -> Result<(), Error> {
-    log::debug!("inserting {} into DB ", game.okkazeo_announce.name);
-    //chck if seller already hs announes, if yes update, if not insert
-    if check_if_seller_in_db(db_client, game.okkazeo_announce.seller.id as i32).await? > 0 {
-        log::debug!("seller {:?} present in DB", game.okkazeo_announce.seller);
-        update_seller_table_from_db(db_client, &game.okkazeo_announce.seller).await?;
-    } else {
-        log::debug!(
-            "seller {:?} not present in DB",
-            game.okkazeo_announce.seller
-        );
-        insert_into_seller_table(db_client, &game.okkazeo_announce.seller).await?;
-    }
-    let id = game.okkazeo_announce.id as i32;
-    insert_into_okkazeo_announce_table(db_client, game).await?;
-    insert_into_shipping_table(db_client, id, &game.okkazeo_announce.shipping).await?;
-    insert_into_deal_table(db_client, id, &game.deal).await?;
-    insert_into_reference_table(db_client, id, &game.references).await?;
-    insert_into_reviewer_table(db_client, id, &game.review.reviews).await?;
+    DB_IO.with_label_values(&["upsert", "reviewer"]).inc();
 
     Ok(())
 }
 
-pub async fn update_seller_table_from_db(db_client: &Client, seller: &Seller) -> Result<(), Error> {
-    log::debug!("updating seller table");
-    let references_insert_req = format!(
-        r#"UPDATE seller SET {} = $1 WHERE {} = $2"#,
-        "seller_nb_announces", "seller_id",
+async fn insert_into_price_history_table(
+    transaction: &Transaction<'_>,
+    id: i32,
+    price: f32,
+    date: &DateTime<Utc>,
+) -> Result<(), Error> {
+    let price_history_insert_req = format!(
+        r#"INSERT INTO price_history ({}, {}, {}) VALUES ($1, $2, $3)"#,
+        "ph_oa_id", "ph_price", "ph_date",
     );
-
-    let _ = db_client
-        .query(
-            &references_insert_req,
-            &[&(seller.nb_announces as i32), &(seller.id as i32)],
-        )
+    transaction
+        .execute(&price_history_insert_req, &[&id, &price, date])
         .await?;
-    DB_IO.with_label_values(&["update", "seller"]).inc();
+    DB_IO.with_label_values(&["insert", "price_history"]).inc();
+
     Ok(())
 }
-pub async fn update_okkazeo_announce_table_from_db(
-    db_client: &Client,
-    game: &Game,
-) -> Result<(), Error> {
-    let references_insert_req = format!(
-        r#"UPDATE okkazeo_announce SET {} = $1, {} = $2 WHERE {} = $3"#,
-        "oa_last_modification_date", "oa_price", "oa_id",
-    );
 
-    let _ = db_client
-        .query(
-            &references_insert_req,
-            &[
-                &game.okkazeo_announce.last_modification_date,
-                &game.okkazeo_announce.price,
-                &(game.okkazeo_announce.id as i32),
-            ],
-        )
+/// Insert a new announce or update a known one, with its seller, deal, shipping, references
+/// and reviews, in a single transaction. The price history gets a new point when the
+/// announce is new or its price changed
+pub async fn upsert_announce_into_db(db_client: &mut Client, game: &Game) -> Result<(), Error> {
+    log::debug!("upserting {} into DB ", game.okkazeo_announce.name);
+    let id = game.okkazeo_announce.id as i32;
+    let transaction = db_client.transaction().await?;
+
+    // serializes concurrent upserts of the same announce, even a new one, so that
+    // only one of them records the new price
+    transaction
+        .execute("SELECT pg_advisory_xact_lock($1)", &[&(id as i64)])
         .await?;
+    let previous_price: Option<f32> = transaction
+        .query_opt(
+            "SELECT oa_price FROM okkazeo_announce WHERE oa_id = $1",
+            &[&id],
+        )
+        .await?
+        .map(|row| row.get(0));
     DB_IO
-        .with_label_values(&["update", "okkazeo_announce"])
+        .with_label_values(&["select", "okkazeo_announce"])
         .inc();
-    Ok(())
-}
 
-pub async fn update_deal_table(db_client: &Client, id: i32, deal: &Deal) -> Result<(), Error> {
-    let deal_insert_req = format!(
-        r#"UPDATE deal SET {} = $1, {} = $2 WHERE {} = $3"#,
-        "deal_price", "deal_percentage", "deal_oa_id",
-    );
-    let _ = db_client
-        .query(
-            &deal_insert_req,
-            &[&deal.deal_price, &deal.deal_percentage, &id],
+    upsert_into_seller_table(&transaction, &game.okkazeo_announce.seller).await?;
+    upsert_into_okkazeo_announce_table(&transaction, game).await?;
+    upsert_into_deal_table(&transaction, id, &game.deal).await?;
+    upsert_into_shipping_table(&transaction, id, &game.okkazeo_announce.shipping).await?;
+    upsert_into_reference_table(&transaction, id, &game.references).await?;
+    upsert_into_reviewer_table(&transaction, id, &game.review.reviews).await?;
+    if previous_price != Some(game.okkazeo_announce.price) {
+        insert_into_price_history_table(
+            &transaction,
+            id,
+            game.okkazeo_announce.price,
+            &game.okkazeo_announce.last_modification_date,
         )
         .await?;
-    DB_IO.with_label_values(&["update", "deal"]).inc();
-
-    Ok(())
-}
-
-pub async fn delete_from_reference_table(db_client: &Client, id: i32) -> Result<(), Error> {
-    let result = db_client
-        .execute("DELETE FROM reference WHERE ref_oa_id = $1", &[&id])
-        .await;
-
-    match result {
-        Ok(_v) => {
-            DB_IO.with_label_values(&["delete", "reference"]).inc();
-            Ok(())
-        }
-        Err(e) => Err(e),
-    }
-}
-
-pub async fn delete_from_reviewer_table(db_client: &Client, id: i32) -> Result<(), Error> {
-    let result = db_client
-        .execute("DELETE FROM reviewer WHERE reviewer_oa_id = $1", &[&id])
-        .await;
-
-    match result {
-        Ok(_v) => {
-            DB_IO.with_label_values(&["delete", "reviewer"]).inc();
-            Ok(())
-        }
-        Err(e) => Err(e),
     }
-}
-
-pub async fn update_reference_table(
-    db_client: &Client,
-    id: i32,
-    refs: &HashMap<String, Reference>,
-) -> Result<(), Error> {
-    delete_from_reference_table(db_client, id).await?;
-    insert_into_reference_table(db_client, id, refs).await
-}
-
-pub async fn update_reviewer_table(
-    db_client: &Client,
-    id: i32,
-    reviews: &Review,
-) -> Result<(), Error> {
-    delete_from_reviewer_table(db_client, id).await?;
-    insert_into_reviewer_table(db_client, id, &reviews.reviews).await
-}
 
-pub async fn update_game_from_db(db_client: &Client, game: &Game) -> Result<(), Error> {
-    update_okkazeo_announce_table_from_db(db_client, game).await?;
-    update_deal_table(db_client, game.okkazeo_announce.id as i32, &game.deal).await?;
-    update_reviewer_table(db_client, game.okkazeo_announce.id as i32, &game.review).await?;
-    update_reference_table(db_client, game.okkazeo_announce.id as i32, &game.references).await?;
-    Ok(())
+    transaction.commit().await
 }
 
-pub async fn craft_game_from_row(db_client: &Client, row: Row) -> Result<Game, Error> {
+fn game_from_row(
+    row: &Row,
+    shipping: HashMap<String, f32>,
+    references: HashMap<String, Reference>,
+    review: Review,
+) -> Result<Game, Error> {
     let id: i32 = row.try_get("oa_id")?;
     let nb_announces: i32 = row.try_get("seller_nb_announces")?;
     let seller_id: i32 = row.try_get("seller_id")?;
+    let nb_ratings: Option<i32> = row.try_get("seller_nb_ratings")?;
 
     let game = Game {
         okkazeo_announce: OkkazeoAnnounce {
@@ -358,13 +439,17 @@ pub async fn craft_game_from_row(db_client: &Client, row: Row) -> Result<Game, E
             price: row.try_get("oa_price")?,
             url: row.try_get("oa_url")?,
             extension: row.try_get("oa_extension").unwrap_or_default(),
-            shipping: select_shipping_from_db(db_client, id).await?,
+            shipping,
             seller: Seller {
                 id: seller_id as u32,
                 name: row.try_get("seller_name")?,
                 url: row.try_get("seller_url")?,
                 nb_announces: nb_announces as u32,
                 is_pro: row.try_get("seller_is_pro")?,
+                rating: row
+                    .try_get::<&str, Option<f32>>("seller_rating")?
+                    .unwrap_or_default(),
+                nb_ratings: nb_ratings.unwrap_or_default() as u32,
             },
             barcode: match row.try_get::<&str, i64>("oa_barcode") {
                 Ok(v) => Some(v as u64),
@@ -373,8 +458,8 @@ pub async fn craft_game_from_row(db_client: &Client, row: Row) -> Result<Game, E
             city: row.try_get("oa_city")?,
             last_modification_date: row.try_get("oa_last_modification_date")?,
         },
-        references: select_references_from_db(db_client, id).await?,
-        review: select_reviews_from_db(db_client, id).await?,
+        references,
+        review,
         deal: Deal {
             deal_price: row.try_get("deal_price")?,
             deal_percentage: row.try_get("deal_percentage")?,
@@ -384,17 +469,48 @@ pub async fn craft_game_from_row(db_client: &Client, row: Row) -> Result<Game, E
     Ok(game)
 }
 
+/// Build the games of announce rows (joined with their deal and seller), the shipping,
+/// references and reviews of all the announces being fetched with one query each
+pub async fn craft_games_from_rows(db_client: &Client, rows: Vec<Row>) -> Result<Games, Error> {
+    let ids = rows
+        .iter()
+        .map(|row| row.try_get("oa_id"))
+        .collect::<Result<Vec<i32>, Error>>()?;
+    // the three queries are pipelined on the connection
+    let (mut shippings, mut references, mut reviews) = tokio::try_join!(
+        select_shipping_by_ids_from_db(db_client, &ids),
+        select_references_by_ids_from_db(db_client, &ids),
+        select_reviews_by_ids_from_db(db_client, &ids),
+    )?;
+
+    let mut games = Games::new();
+    for (row, id) in rows.iter().zip(ids) {
+        let game = game_from_row(
+            row,
+            shippings.remove(&id).unwrap_or_default(),
+            references.remove(&id).unwrap_or_default(),
+            reviews.remove(&id).unwrap_or_default(),
+        )?;
+        games.games.push(Box::new(game));
+    }
+
+    Ok(games)
+}
+
+pub async fn craft_game_from_row(db_client: &Client, row: Row) -> Result<Game, Error> {
+    let mut games = craft_games_from_rows(db_client, vec![row]).await?;
+    Ok(*games.games.remove(0))
+}
+
 pub async fn select_game_with_id_from_db(db_client: &Client, id: u32) -> Option<Game> {
     log::debug!("[DB] select game with id from db : {}", id);
-    let select_req = format!(
-        "SELECT *
+    let select_req = "SELECT *
                 FROM okkazeo_announce oa
                 JOIN deal d on d.deal_oa_id = oa.oa_id
                 JOIN seller s on s.seller_id = oa.oa_seller
-                WHERE oa.oa_id = $1"
-    );
+                WHERE oa.oa_id = $1";
 
-    let res = match db_client.query(&select_req, &[&(id as i32)]).await {
+    let res = match db_client.query(select_req, &[&(id as i32)]).await {
         Ok(r) => r,
         Err(e) => {
             log::error!("select game with id erro : {}", e);
@@ -416,163 +532,61 @@ pub async fn select_game_with_id_from_db(db_client: &Client, id: u32) -> Option<
     }
 }
 
-fn sql_partial_rating_filter(note: Option<f32>) -> String {
-    return note.map_or("".to_string(), |n| format!(
-        "HAVING SUM(CASE WHEN r.reviewer_number > 0 THEN r.reviewer_note * r.reviewer_number ELSE 0 END) / SUM(CASE WHEN r.reviewer_number > 0 THEN r.reviewer_number ELSE 1 END) >= {}",
-        n
-    ));
+/// Refresh the listable_game view read by the listing, without blocking its readers
+pub async fn refresh_listable_game_view(db_client: &Client) -> Result<(), Error> {
+    db_client
+        .batch_execute("REFRESH MATERIALIZED VIEW CONCURRENTLY listable_game")
+        .await?;
+    DB_IO.with_label_values(&["refresh", "listable_game"]).inc();
+
+    Ok(())
 }
 
 pub async fn select_games_from_db(db_client: &Client, state: &State) -> Result<Games, Error> {
     let now = chrono::Utc::now();
     let order_by = match state.sort.sort.as_str() {
-        "price" => "d.deal_price ASC",
-        "percent" => "d.deal_percentage ASC",
-        _ => "oa.oa_last_modification_date DESC",
+        "price" => "lg.deal_price ASC",
+        "percent" => "lg.deal_percentage ASC",
+        _ => "lg.oa_last_modification_date DESC",
     };
 
-    let type_filter = format!(
-        "AND (({} = true AND oa_extension = 'Jeu') OR
-    ({} = true AND oa_extension = 'Extension') OR
-    ({} = true AND oa_extension = 'Jeu + Extension') OR
-    ({} = true AND oa_extension NOT IN ('Jeu', 'Jeu + Extension', 'Extension')))",
-        state.filters.type_game,
-        state.filters.type_ext,
-        state.filters.type_game_ext,
-        state.filters.type_misc,
-    );
+    let mut filter_query = FilterQuery::new(&state.filters);
+    // the most relevant announces first when searching a name, the chosen sort breaking ties
+    let order_by = match filter_query.relevance_sql() {
+        Some(relevance) => format!("{} DESC, {}", relevance, order_by),
+        None => order_by.to_string(),
+    };
+    let limit = filter_query.bind(state.pagination.per_page as i64);
+    let offset = filter_query.bind((state.pagination.page * state.pagination.per_page) as i64);
 
     let select_req = format!(
-        "SELECT 
-            oa.oa_name,
-            oa.oa_last_modification_date,
-            oa.oa_id,
-            oa.oa_price,
-            oa.oa_url,
-            oa.oa_extension,
-            oa.oa_image,
-            oa.oa_city,
-            s.seller_id,
-            s.seller_name,
-            s.seller_url,
-            s.seller_is_pro,
-            s.seller_nb_announces,
-            d.deal_price,
-            d.deal_percentage
-         FROM okkazeo_announce oa
-                JOIN deal d on d.deal_oa_id = oa.oa_id
-                LEFT JOIN reviewer r on r.reviewer_oa_id = oa.oa_id
-                JOIN seller s on s.seller_id = oa.oa_seller
-                WHERE oa.oa_id IN (
-                    SELECT oa.oa_id
-                    FROM okkazeo_announce oa
-                    LEFT JOIN reviewer r on r.reviewer_oa_id = oa.oa_id
-                    JOIN seller s on s.seller_id = oa.oa_seller
-                    WHERE unaccent(oa.oa_name) ilike unaccent($1) AND unaccent(oa.oa_city) ilike unaccent($2)
-                    AND unaccent(s.seller_name) ilike unaccent($3)
-                    AND oa.oa_price > $4
-                    AND oa.oa_price < $5
-                    {}
-                    {}
-                    {}
-                    {}
-                    GROUP BY oa.oa_id
-                    {}
-                )
-                GROUP BY
-                    oa.oa_last_modification_date,
-                    oa.oa_name,
-                    oa.oa_id,
-                    oa.oa_price,
-                    oa.oa_url,
-                    oa.oa_extension,
-                    oa.oa_barcode,
-                    oa.oa_image,
-                    oa.oa_city,
-                    s.seller_id,
-                    s.seller_name,
-                    s.seller_url,
-                    s.seller_is_pro,
-                    s.seller_nb_announces,
-                    d.deal_price,
-                    d.deal_percentage
-                ORDER BY {} LIMIT $6 OFFSET $7;",
-        if state.filters.pro.is_some() {
-            "AND NOT s.seller_is_pro"
-        } else {
-            ""
-        },
-        type_filter,
-        if state.filters.date.is_some() {
-            format!("AND oa.oa_last_modification_date >= '{}'", state.filters.date.as_ref().unwrap())
-        } else {
-            String::new()
-        },
-        if state.filters.delivery.is_some() {
-            "AND oa.oa_id in ( select distinct ship_oa_id from shipping where ship_shipper != 'hand_delivery')"
-        } else {
-            ""
-        },
-        sql_partial_rating_filter(state.filters.note),
-        order_by
+        "SELECT *
+                FROM listable_game lg
+                {}
+                ORDER BY {} LIMIT {} OFFSET {};",
+        filter_query.where_sql(),
+        order_by,
+        limit,
+        offset
     );
     log::debug!(
         "Took {} befoe req",
         (chrono::Utc::now() - now).num_milliseconds()
     );
 
-    // this is a trick, if city filter is a number, it means that we're
-    // looking for postcode. Okkazeo format for city is : "city (postcode)"
-    let mut match_start = "";
-    if state.filters.city.is_some() && state.filters.city.as_ref().unwrap().parse::<i32>().is_ok() {
-        match_start = "(";
-    }
-
-    let res = db_client
-        .query(
-            &select_req,
-            &[
-                &format!(
-                    "%{}%",
-                    state.filters.name.as_ref().unwrap_or(&String::new())
-                ),
-                &format!(
-                    "%{}{}%",
-                    match_start,
-                    state.filters.city.as_ref().unwrap_or(&String::new())
-                ),
-                &format!(
-                    "%{}%",
-                    state.filters.vendor.as_ref().unwrap_or(&String::new())
-                ),
-                &(state.filters.min_price.unwrap_or_default() as f32),
-                &(state.filters.max_price.unwrap_or_else(|| 10000) as f32),
-                &(state.pagination.per_page as i64),
-                &((state.pagination.page * state.pagination.per_page) as i64),
-            ],
-        )
-        .await?;
+    let res = db_client.query(&select_req, &filter_query.params()).await?;
 
     log::debug!(
         "Took {} after req",
         (chrono::Utc::now() - now).num_milliseconds()
     );
-    let mut games = Games {
-        ..Default::default()
+    let games = match craft_games_from_rows(db_client, res).await {
+        Ok(games) => games,
+        Err(e) => {
+            log::error!("[DB] craft games from rows error : {}", e);
+            return Err(e);
+        }
     };
-    for row in res {
-        let game = match craft_game_from_row(db_client, row).await {
-            Ok(game) => {
-                //log::debug!("[DB] game crafted from DB: {:#?}", game);
-                game
-            }
-            Err(e) => {
-                log::error!("[DB] craft game from row error : {}", e);
-                return Err(e);
-            }
-        };
-        games.games.push(Box::new(game))
-    }
     DB_IO.with_label_values(&["select", "game"]).inc();
 
     Ok(games)
@@ -582,105 +596,84 @@ pub async fn select_count_filtered_games_from_db(
     db_client: &Client,
     filters: Filters,
 ) -> Result<i64, Error> {
-    let type_filter = format!(
-        "AND (({} = true AND oa_extension = 'Jeu') OR
-    ({} = true AND oa_extension = 'Extension') OR
-    ({} = true AND oa_extension = 'Jeu + Extension') OR
-    ({} = true AND oa_extension NOT IN ('Jeu', 'Jeu + Extension', 'Extension')))",
-        filters.type_game, filters.type_ext, filters.type_game_ext, filters.type_misc,
-    );
-
+    let filter_query = FilterQuery::new(&filters);
     let select_req = format!(
-        "SELECT COUNT(*) FROM (
-                SELECT oa.oa_id
-                FROM okkazeo_announce oa
-                JOIN deal d on d.deal_oa_id = oa.oa_id
-                LEFT JOIN reviewer r on r.reviewer_oa_id = oa.oa_id
-                JOIN seller s on s.seller_id = oa.oa_seller
-                WHERE unaccent(oa.oa_name) ilike unaccent($1) AND unaccent(oa.oa_city) ilike unaccent($2)
-                AND unaccent(s.seller_name) ilike unaccent($3)
-                AND oa.oa_price > $4 AND oa.oa_price < $5
-                {}
-                {}
-                {}
-                {}
-                GROUP BY oa.oa_id
-                {}
-        ) AS c;",
-        if filters.pro.is_some() {
-            "AND NOT s.seller_is_pro"
-        } else {
-            ""
-        },
-        type_filter,
-        if filters.date.is_some() {
-            format!("AND oa.oa_last_modification_date >= '{}'", filters.date.as_ref().unwrap())
-        } else {
-            String::new()
-        },
-        if filters.delivery.is_some() {
-            "AND oa.oa_id in ( select distinct ship_oa_id from shipping where ship_shipper != 'hand_delivery')"
-        } else {
-            ""
-        },
-        sql_partial_rating_filter(filters.note),
+        "SELECT COUNT(*) FROM listable_game lg {};",
+        filter_query.where_sql()
     );
 
-    let mut match_start = "";
-    if filters.city.is_some() && filters.city.as_ref().unwrap().parse::<i32>().is_ok() {
-        match_start = "(";
-    }
-
-    let res = db_client
-        .query(
-            &select_req,
-            &[
-                &format!("%{}%", filters.name.unwrap_or_default()),
-                &format!("%{}{}%", match_start, filters.city.unwrap_or_default()),
-                &format!("%{}%", filters.vendor.unwrap_or_default()),
-                &(filters.min_price.unwrap_or_default() as f32),
-                &(filters.max_price.unwrap_or_else(|| 10000) as f32),
-            ],
-        )
+    let row = db_client
+        .query_one(&select_req, &filter_query.params())
         .await?;
 
-    let nbr: i64 = res.get(0).unwrap().try_get(0)?;
+    let nbr: i64 = row.try_get(0)?;
     DB_IO.with_label_values(&["select", "game"]).inc();
 
     Ok(nbr)
 }
 
-pub async fn check_if_seller_in_db(db_client: &Client, id: i32) -> Result<i32, Error> {
-    log::debug!("checkin if seller is in db");
+/// Distinct announce names matching the beginning of a search, the closest first
+pub async fn select_name_suggestions_from_db(
+    db_client: &Client,
+    query: &str,
+    limit: i64,
+) -> Result<Vec<String>, Error> {
+    let select_req = "SELECT oa_name,
+                    MAX(word_similarity(immutable_unaccent($1), immutable_unaccent(oa_name))) AS score
+                FROM okkazeo_announce
+                WHERE immutable_unaccent(oa_name) ILIKE immutable_unaccent($2)
+                    OR immutable_unaccent(oa_name) %> immutable_unaccent($1)
+                GROUP BY oa_name
+                ORDER BY score DESC, oa_name
+                LIMIT $3";
+
+    let res = db_client
+        .query(select_req, &[&query, &format!("%{}%", query), &limit])
+        .await?;
+    DB_IO
+        .with_label_values(&["select", "okkazeo_announce"])
+        .inc();
+
+    res.into_iter().map(|row| row.try_get("oa_name")).collect()
+}
+
+/// Among the given announce ids, select the ones matching the filters
+pub async fn select_matching_ids_from_db(
+    db_client: &Client,
+    filters: &Filters,
+    ids: &[i32],
+) -> Result<Vec<i32>, Error> {
+    let mut filter_query = FilterQuery::new(filters);
+    let ids_param = filter_query.bind(ids.to_vec());
     let select_req = format!(
-        "SELECT seller_id
-                FROM seller
-                WHERE seller_id = $1"
+        "SELECT c.oa_id FROM ({}) AS c WHERE c.oa_id = ANY({});",
+        filter_query.ids_sql(),
+        ids_param
     );
 
-    let res = db_client.query(&select_req, &[&id]).await?;
-    DB_IO.with_label_values(&["select", "seller"]).inc();
+    let res = db_client.query(&select_req, &filter_query.params()).await?;
+    DB_IO.with_label_values(&["select", "game"]).inc();
 
-    Ok(res.len() as i32)
+    res.into_iter().map(|row| row.try_get("oa_id")).collect()
 }
 
-pub async fn select_shipping_from_db(
+/// Shipping prices of the given announces, by announce id
+pub async fn select_shipping_by_ids_from_db(
     db_client: &Client,
-    id: i32,
-) -> Result<HashMap<String, f32>, Error> {
-    let select_req = format!(
-        "SELECT *
+    ids: &[i32],
+) -> Result<HashMap<i32, HashMap<String, f32>>, Error> {
+    let select_req = "SELECT *
                 FROM shipping
-                WHERE ship_oa_id = $1"
-    );
+                WHERE ship_oa_id = ANY($1)";
 
-    let res = db_client.query(&select_req, &[&id]).await?;
+    let res = db_client.query(select_req, &[&ids]).await?;
 
-    let mut ships = HashMap::<String, f32>::new();
+    let mut ships = HashMap::<i32, HashMap<String, f32>>::new();
     for row in res {
+        let id = row.try_get("ship_oa_id")?;
         let shipper = row.try_get("ship_shipper")?;
         let price = row.try_get("ship_price")?;
-        ships.insert(shipper, price);
+        ships.entry(id).or_default().insert(shipper, price);
     }
     DB_IO.with_label_values(&["select", "shipping"]).inc();
 
@@ -692,13 +685,11 @@ pub async fn select_intervalled_ids_from_oa_table_from_db(
     start_date: DateTime<Utc>,
     end_date: DateTime<Utc>,
 ) -> Result<Vec<i32>, Error> {
-    let select_req = format!(
-        "SELECT oa_id
-                FROM okkazeo_announce oa WHERE oa_last_modification_date > $1 AND oa_last_modification_date < $2"
-    );
+    let select_req = "SELECT oa_id
+                FROM okkazeo_announce oa WHERE oa_last_modification_date > $1 AND oa_last_modification_date < $2";
 
     let res = db_client
-        .query(&select_req, &[&start_date, &end_date])
+        .query(select_req, &[&start_date, &end_date])
         .await?;
 
     DB_IO
@@ -708,12 +699,10 @@ pub async fn select_intervalled_ids_from_oa_table_from_db(
 }
 
 pub async fn select_all_ids_from_oa_table_from_db(db_client: &Client) -> Result<Vec<i32>, Error> {
-    let select_req = format!(
-        "SELECT oa_id
-                FROM okkazeo_announce"
-    );
+    let select_req = "SELECT oa_id
+                FROM okkazeo_announce";
 
-    let res = db_client.query(&select_req, &[]).await?;
+    let res = db_client.query(select_req, &[]).await?;
     DB_IO
         .with_label_values(&["select", "okkazeo_announce"])
         .inc();
@@ -721,46 +710,143 @@ pub async fn select_all_ids_from_oa_table_from_db(db_client: &Client) -> Result<
     res.into_iter().map(|row| row.try_get("oa_id")).collect()
 }
 
-pub async fn select_references_from_db(
+/// Announces whose shop prices were last queried before `older_than` and that have no
+/// refresh queued nor dead-lettered for less than `dead_cooldown`, the best deals first,
+/// with their deal percentage
+pub async fn select_stale_references_ids_from_db(
     db_client: &Client,
-    id: i32,
-) -> Result<HashMap<String, Reference>, Error> {
-    let select_req = format!(
-        "SELECT *
+    older_than: DateTime<Utc>,
+    dead_cooldown: Duration,
+    limit: i64,
+) -> Result<Vec<(i32, Option<i32>)>, Error> {
+    let select_req = r#"SELECT oa.oa_id, d.deal_percentage
+                FROM okkazeo_announce oa LEFT JOIN deal d ON d.deal_oa_id = oa.oa_id
+                WHERE oa.oa_references_date < $1
+                AND NOT EXISTS (
+                    SELECT 1 FROM job WHERE job_kind = 'refresh_references'
+                    AND job_oa_id = oa.oa_id AND (job_status <> 'dead'
+                        OR job_dead_date > now() - make_interval(secs => $3))
+                )
+                ORDER BY d.deal_percentage ASC NULLS LAST, oa.oa_references_date
+                LIMIT $2"#;
+
+    let res = db_client
+        .query(
+            select_req,
+            &[&older_than, &limit, &dead_cooldown.as_secs_f64()],
+        )
+        .await?;
+    DB_IO
+        .with_label_values(&["select", "okkazeo_announce"])
+        .inc();
+
+    res.into_iter()
+        .map(|row| Ok((row.try_get(0)?, row.try_get(1)?)))
+        .collect()
+}
+
+/// Record that the shop prices of an announce were just queried
+pub async fn update_references_date(db_client: &Client, id: i32) -> Result<(), Error> {
+    db_client
+        .execute(
+            "UPDATE okkazeo_announce SET oa_references_date = now() WHERE oa_id = $1",
+            &[&id],
+        )
+        .await?;
+    DB_IO
+        .with_label_values(&["update", "okkazeo_announce"])
+        .inc();
+
+    Ok(())
+}
+
+pub async fn select_feed_state_from_db(
+    db_client: &Client,
+    feed: &str,
+) -> Result<Option<FeedState>, Error> {
+    let row = db_client
+        .query_opt(
+            "SELECT fs_last_id, fs_last_date FROM feed_state WHERE fs_feed = $1",
+            &[&feed],
+        )
+        .await?;
+    DB_IO.with_label_values(&["select", "feed_state"]).inc();
+
+    row.map(|row| {
+        let last_id: i32 = row.try_get("fs_last_id")?;
+        Ok(FeedState {
+            last_id: last_id as u32,
+            last_date: row.try_get("fs_last_date")?,
+        })
+    })
+    .transpose()
+}
+
+pub async fn upsert_into_feed_state_table(
+    db_client: &Client,
+    feed: &str,
+    state: &FeedState,
+) -> Result<(), Error> {
+    db_client
+        .execute(
+            r#"INSERT INTO feed_state (fs_feed, fs_last_id, fs_last_date) VALUES ($1, $2, $3)
+            ON CONFLICT (fs_feed) DO UPDATE SET
+            fs_last_id = EXCLUDED.fs_last_id,
+            fs_last_date = EXCLUDED.fs_last_date,
+            fs_update_date = now()"#,
+            &[&feed, &(state.last_id as i32), &state.last_date],
+        )
+        .await?;
+    DB_IO.with_label_values(&["upsert", "feed_state"]).inc();
+
+    Ok(())
+}
+
+/// Shop references of the given announces, by announce id
+pub async fn select_references_by_ids_from_db(
+    db_client: &Client,
+    ids: &[i32],
+) -> Result<HashMap<i32, HashMap<String, Reference>>, Error> {
+    let select_req = "SELECT *
                 FROM reference
-                WHERE ref_oa_id = $1"
-    );
+                WHERE ref_oa_id = ANY($1)";
 
-    let res = db_client.query(&select_req, &[&id]).await?;
+    let res = db_client.query(select_req, &[&ids]).await?;
 
-    let mut refs = HashMap::<String, Reference>::new();
+    let mut refs = HashMap::<i32, HashMap<String, Reference>>::new();
     for row in res {
+        let id = row.try_get("ref_oa_id")?;
         let name: String = row.try_get("ref_name")?;
         let price = row.try_get("ref_price")?;
         let url = row.try_get("ref_url")?;
-        refs.insert(name.clone(), Reference { name, price, url });
+        refs.entry(id)
+            .or_default()
+            .insert(name.clone(), Reference { name, price, url });
     }
 
     DB_IO.with_label_values(&["select", "reference"]).inc();
     Ok(refs)
 }
 
-pub async fn select_reviews_from_db(db_client: &Client, id: i32) -> Result<Review, Error> {
-    let select_req = format!(
-        "SELECT *
+/// Reviews of the given announces, by announce id
+pub async fn select_reviews_by_ids_from_db(
+    db_client: &Client,
+    ids: &[i32],
+) -> Result<HashMap<i32, Review>, Error> {
+    let select_req = "SELECT *
                 FROM reviewer
-                WHERE reviewer_oa_id = $1"
-    );
+                WHERE reviewer_oa_id = ANY($1)";
 
-    let res = db_client.query(&select_req, &[&id]).await?;
+    let res = db_client.query(select_req, &[&ids]).await?;
 
-    let mut revs = HashMap::<String, Reviewer>::new();
+    let mut revs = HashMap::<i32, Review>::new();
     for row in res {
+        let id = row.try_get("reviewer_oa_id")?;
         let name: String = row.try_get("reviewer_name")?;
         let url = row.try_get("reviewer_url")?;
         let note = row.try_get("reviewer_note")?;
         let number: i32 = row.try_get("reviewer_number")?;
-        revs.insert(
+        revs.entry(id).or_default().reviews.insert(
             name.clone(),
             Reviewer {
                 name,
@@ -771,18 +857,635 @@ pub async fn select_reviews_from_db(db_client: &Client, id: i32) -> Result<Revie
         );
     }
 
-    let mut rev = Review {
-        reviews: revs,
-        average_note: 0.0,
-    };
-    rev.compute_average_note();
+    for rev in revs.values_mut() {
+        rev.compute_average_note();
+    }
     DB_IO.with_label_values(&["select", "reviews"]).inc();
 
-    Ok(rev)
+    Ok(revs)
+}
+
+pub async fn select_price_history_from_db(
+    db_client: &Client,
+    id: i32,
+) -> Result<Vec<PricePoint>, Error> {
+    let select_req = "SELECT *
+                FROM price_history
+                WHERE ph_oa_id = $1
+                ORDER BY ph_date ASC";
+
+    let res = db_client.query(select_req, &[&id]).await?;
+    DB_IO.with_label_values(&["select", "price_history"]).inc();
+
+    res.into_iter()
+        .map(|row| {
+            Ok(PricePoint {
+                price: row.try_get("ph_price")?,
+                date: row.try_get("ph_date")?,
+            })
+        })
+        .collect()
+}
+
+/// Select the other announces currently listed for the same game, matched
+/// by barcode when we have one, by name otherwise
+pub async fn select_other_announces_from_db(
+    db_client: &Client,
+    game: &Game,
+) -> Result<Games, Error> {
+    let select_req = "SELECT *
+                FROM okkazeo_announce oa
+                JOIN deal d on d.deal_oa_id = oa.oa_id
+                JOIN seller s on s.seller_id = oa.oa_seller
+                WHERE oa.oa_id <> $1
+                AND ((oa.oa_barcode = $2 AND oa.oa_barcode <> 0)
+                    OR unaccent(oa.oa_name) ilike unaccent($3))
+                ORDER BY oa.oa_price ASC
+                LIMIT 50";
+
+    let res = db_client
+        .query(
+            select_req,
+            &[
+                &(game.okkazeo_announce.id as i32),
+                &(game.okkazeo_announce.barcode.unwrap_or_default() as i64),
+                &game.okkazeo_announce.name.trim(),
+            ],
+        )
+        .await?;
+    DB_IO
+        .with_label_values(&["select", "okkazeo_announce"])
+        .inc();
+
+    craft_games_from_rows(db_client, res).await
+}
+
+pub async fn select_seller_from_db(db_client: &Client, id: i32) -> Result<Option<Seller>, Error> {
+    let select_req = "SELECT *
+                FROM seller
+                WHERE seller_id = $1";
+
+    let res = db_client.query(select_req, &[&id]).await?;
+    DB_IO.with_label_values(&["select", "seller"]).inc();
+
+    let row = match res.into_iter().next() {
+        Some(row) => row,
+        None => return Ok(None),
+    };
+    let nb_announces: i32 = row.try_get("seller_nb_announces")?;
+    let nb_ratings: Option<i32> = row.try_get("seller_nb_ratings")?;
+
+    Ok(Some(Seller {
+        id: id as u32,
+        name: row.try_get("seller_name")?,
+        url: row.try_get("seller_url")?,
+        nb_announces: nb_announces as u32,
+        is_pro: row.try_get("seller_is_pro")?,
+        rating: row
+            .try_get::<&str, Option<f32>>("seller_rating")?
+            .unwrap_or_default(),
+        nb_ratings: nb_ratings.unwrap_or_default() as u32,
+    }))
+}
+
+pub async fn select_games_from_seller_from_db(
+    db_client: &Client,
+    seller_id: i32,
+) -> Result<Games, Error> {
+    let select_req = "SELECT *
+                FROM okkazeo_announce oa
+                JOIN deal d on d.deal_oa_id = oa.oa_id
+                JOIN seller s on s.seller_id = oa.oa_seller
+                WHERE oa.oa_seller = $1
+                ORDER BY oa.oa_last_modification_date DESC";
+
+    let res = db_client.query(select_req, &[&seller_id]).await?;
+    DB_IO
+        .with_label_values(&["select", "okkazeo_announce"])
+        .inc();
+
+    craft_games_from_rows(db_client, res).await
+}
+
+pub async fn select_archived_announces_from_seller_from_db(
+    db_client: &Client,
+    seller_id: i32,
+) -> Result<Vec<ArchivedAnnounce>, Error> {
+    let select_req = "SELECT *
+                FROM announce_archive
+                WHERE ar_seller = $1
+                ORDER BY ar_removal_date DESC
+                LIMIT 200";
+
+    let res = db_client.query(select_req, &[&seller_id]).await?;
+    DB_IO
+        .with_label_values(&["select", "announce_archive"])
+        .inc();
+
+    res.into_iter()
+        .map(|row| {
+            let id: i32 = row.try_get("ar_oa_id")?;
+            Ok(ArchivedAnnounce {
+                id: id as u32,
+                name: row.try_get("ar_name")?,
+                price: row.try_get("ar_price")?,
+                deal_percentage: row.try_get("ar_deal_percentage")?,
+                creation_date: row.try_get("ar_creation_date")?,
+                removal_date: row.try_get("ar_removal_date")?,
+            })
+        })
+        .collect()
+}
+
+/// Compute the statistics of a seller over its live and archived announces.
+/// Announces without any reference (deal percentage of 0) are not counted in the average discount
+pub async fn select_seller_stats_from_db(
+    db_client: &Client,
+    seller_id: i32,
+) -> Result<SellerStats, Error> {
+    let select_req = "SELECT
+                (SELECT COUNT(*) FROM okkazeo_announce WHERE oa_seller = $1) AS nb_live,
+                (SELECT COUNT(*) FROM announce_archive WHERE ar_seller = $1) AS nb_past,
+                (SELECT AVG(percentage)::real FROM (
+                    SELECT d.deal_percentage AS percentage
+                    FROM okkazeo_announce oa
+                    JOIN deal d on d.deal_oa_id = oa.oa_id
+                    WHERE oa.oa_seller = $1 AND d.deal_percentage <> 0
+                    UNION ALL
+                    SELECT ar_deal_percentage AS percentage
+                    FROM announce_archive
+                    WHERE ar_seller = $1 AND ar_deal_percentage <> 0
+                ) AS p) AS average_deal_percentage,
+                (SELECT (AVG(EXTRACT(EPOCH FROM (ar_removal_date - ar_creation_date))) / 86400)::real
+                    FROM announce_archive
+                    WHERE ar_seller = $1) AS average_days_to_sell";
+
+    let res = db_client.query(select_req, &[&seller_id]).await?;
+    DB_IO.with_label_values(&["select", "seller_stats"]).inc();
+
+    let row = match res.into_iter().next() {
+        Some(row) => row,
+        None => return Ok(SellerStats::default()),
+    };
+    let nb_live: i64 = row.try_get("nb_live")?;
+    let nb_past: i64 = row.try_get("nb_past")?;
+
+    Ok(SellerStats {
+        nb_live_announces: nb_live as u32,
+        nb_past_announces: nb_past as u32,
+        average_deal_percentage: row
+            .try_get::<&str, Option<f32>>("average_deal_percentage")?
+            .unwrap_or_default(),
+        average_days_to_sell: row.try_get("average_days_to_sell")?,
+    })
+}
+
+pub async fn insert_into_saved_search_table(
+    db_client: &Client,
+    name: &str,
+    filters: &Filters,
+    sort: &Sort,
+) -> Result<(), Error> {
+    let saved_search_insert_req = format!(
+        r#"INSERT INTO saved_search ({}, {}, {}) VALUES ($1, $2, $3)
+            ON CONFLICT ({}) DO UPDATE SET {} = EXCLUDED.{}, {} = EXCLUDED.{}"#,
+        "ss_name",
+        "ss_filters",
+        "ss_sort",
+        "ss_name",
+        "ss_filters",
+        "ss_filters",
+        "ss_sort",
+        "ss_sort",
+    );
+    let filters = serde_json::to_string(filters).unwrap_or_default();
+
+    let _ = db_client
+        .query(&saved_search_insert_req, &[&name, &filters, &sort.sort])
+        .await?;
+    DB_IO.with_label_values(&["insert", "saved_search"]).inc();
+
+    Ok(())
+}
+
+pub async fn delete_from_saved_search_table(db_client: &Client, id: i32) -> Result<(), Error> {
+    db_client
+        .execute("DELETE FROM saved_search WHERE ss_id = $1", &[&id])
+        .await?;
+    DB_IO.with_label_values(&["delete", "saved_search"]).inc();
+
+    Ok(())
+}
+
+fn craft_saved_search_from_row(row: Row) -> Result<SavedSearch, Error> {
+    let id: i32 = row.try_get("ss_id")?;
+    let filters: String = row.try_get("ss_filters")?;
+    let nb_new_matches: i64 = row.try_get("nb_new_matches")?;
+
+    Ok(SavedSearch {
+        id: id as u32,
+        name: row.try_get("ss_name")?,
+        filters: serde_json::from_str(&filters).unwrap_or_else(|e| {
+            log::error!("[DB] cannot parse filters of saved search {} : {}", id, e);
+            Filters::default()
+        }),
+        sort: Sort {
+            sort: row.try_get("ss_sort")?,
+        },
+        last_visit_date: row.try_get("ss_last_visit_date")?,
+        nb_new_matches: nb_new_matches as u32,
+    })
+}
+
+pub async fn select_saved_searches_from_db(db_client: &Client) -> Result<Vec<SavedSearch>, Error> {
+    let select_req = "SELECT ss.*, COUNT(ssm.ssm_oa_id) AS nb_new_matches
+                FROM saved_search ss
+                LEFT JOIN saved_search_match ssm
+                    ON ssm.ssm_ss_id = ss.ss_id AND ssm.ssm_match_date > ss.ss_last_visit_date
+                GROUP BY ss.ss_id
+                ORDER BY ss.ss_name";
+
+    let res = db_client.query(select_req, &[]).await?;
+    DB_IO.with_label_values(&["select", "saved_search"]).inc();
+
+    res.into_iter().map(craft_saved_search_from_row).collect()
+}
+
+pub async fn select_saved_search_from_db(
+    db_client: &Client,
+    id: i32,
+) -> Result<Option<SavedSearch>, Error> {
+    let select_req = "SELECT ss.*, COUNT(ssm.ssm_oa_id) AS nb_new_matches
+                FROM saved_search ss
+                LEFT JOIN saved_search_match ssm
+                    ON ssm.ssm_ss_id = ss.ss_id AND ssm.ssm_match_date > ss.ss_last_visit_date
+                WHERE ss.ss_id = $1
+                GROUP BY ss.ss_id";
+
+    let res = db_client.query(select_req, &[&id]).await?;
+    DB_IO.with_label_values(&["select", "saved_search"]).inc();
+
+    res.into_iter()
+        .next()
+        .map(craft_saved_search_from_row)
+        .transpose()
+}
+
+pub async fn update_saved_search_last_visit(db_client: &Client, id: i32) -> Result<(), Error> {
+    db_client
+        .execute(
+            "UPDATE saved_search SET ss_last_visit_date = now() WHERE ss_id = $1",
+            &[&id],
+        )
+        .await?;
+    DB_IO.with_label_values(&["update", "saved_search"]).inc();
+
+    Ok(())
+}
+
+pub async fn insert_into_saved_search_match_table(
+    db_client: &Client,
+    search_id: i32,
+    ids: &[i32],
+) -> Result<(), Error> {
+    // a repriced announce is matched again, so it shows up as new for the next visit
+    db_client
+        .execute(
+            "INSERT INTO saved_search_match (ssm_ss_id, ssm_oa_id, ssm_price, ssm_match_date)
+                SELECT $1, oa_id, oa_price, now() FROM okkazeo_announce WHERE oa_id = ANY($2)
+                ON CONFLICT (ssm_ss_id, ssm_oa_id) DO UPDATE
+                SET ssm_price = EXCLUDED.ssm_price, ssm_match_date = EXCLUDED.ssm_match_date
+                WHERE saved_search_match.ssm_price <> EXCLUDED.ssm_price",
+            &[&search_id, &ids],
+        )
+        .await?;
+    DB_IO
+        .with_label_values(&["insert", "saved_search_match"])
+        .inc();
+
+    Ok(())
+}
+
+/// Evaluate every saved search against the given (new or repriced) announces
+/// and record the matches. Returns the matching announce ids of each search
+pub async fn record_saved_search_matches(
+    db_client: &Client,
+    ids: &[i32],
+) -> Result<Vec<(SavedSearch, Vec<i32>)>, Error> {
+    let mut matches = Vec::new();
+    if ids.is_empty() {
+        return Ok(matches);
+    }
+
+    for search in select_saved_searches_from_db(db_client).await? {
+        let matching_ids = select_matching_ids_from_db(db_client, &search.filters, ids).await?;
+        if matching_ids.is_empty() {
+            continue;
+        }
+
+        log::debug!(
+            "saved search {} matches {} announces",
+            search.name,
+            matching_ids.len()
+        );
+        insert_into_saved_search_match_table(db_client, search.id as i32, &matching_ids).await?;
+        matches.push((search, matching_ids));
+    }
+
+    Ok(matches)
+}
+
+/// Select the announces matched by a saved search since its last visit
+pub async fn select_new_saved_search_matches_from_db(
+    db_client: &Client,
+    search: &SavedSearch,
+) -> Result<Games, Error> {
+    let select_req = "SELECT *
+                FROM saved_search_match ssm
+                JOIN okkazeo_announce oa on oa.oa_id = ssm.ssm_oa_id
+                JOIN deal d on d.deal_oa_id = oa.oa_id
+                JOIN seller s on s.seller_id = oa.oa_seller
+                WHERE ssm.ssm_ss_id = $1 AND ssm.ssm_match_date > $2
+                ORDER BY ssm.ssm_match_date DESC";
+
+    let res = db_client
+        .query(select_req, &[&(search.id as i32), &search.last_visit_date])
+        .await?;
+    DB_IO
+        .with_label_values(&["select", "saved_search_match"])
+        .inc();
+
+    craft_games_from_rows(db_client, res).await
+}
+
+/// Insert a wishlist item, an item with an already known bgg id is updated instead
+/// (the constraints set by hand are kept if the new item has none)
+pub async fn insert_into_wishlist_table(
+    db_client: &Client,
+    item: &WishlistItem,
+) -> Result<i32, Error> {
+    let wishlist_insert_req = format!(
+        r#"INSERT INTO wishlist ({}, {}, {}, {}, {}, {}, {}, {}) VALUES ($1, $2, $3, $4, $5, $6, $7, $8)
+            ON CONFLICT (wl_bgg_id) DO UPDATE SET
+                wl_name = EXCLUDED.wl_name,
+                wl_barcode = COALESCE(EXCLUDED.wl_barcode, wishlist.wl_barcode),
+                wl_max_price = COALESCE(EXCLUDED.wl_max_price, wishlist.wl_max_price),
+                wl_language = COALESCE(EXCLUDED.wl_language, wishlist.wl_language),
+                wl_edition = COALESCE(EXCLUDED.wl_edition, wishlist.wl_edition),
+                wl_priority = EXCLUDED.wl_priority,
+                wl_want_to_buy = EXCLUDED.wl_want_to_buy
+            RETURNING wl_id"#,
+        "wl_name",
+        "wl_barcode",
+        "wl_bgg_id",
+        "wl_max_price",
+        "wl_language",
+        "wl_edition",
+        "wl_priority",
+        "wl_want_to_buy",
+    );
+
+    let row = db_client
+        .query_one(
+            &wishlist_insert_req,
+            &[
+                &item.name,
+                &item.barcode.map(|b| b as i64),
+                &item.bgg_id.map(|id| id as i32),
+                &item.max_price,
+                &item.language,
+                &item.edition,
+                &item.priority.map(|p| p as i32),
+                &item.want_to_buy,
+            ],
+        )
+        .await?;
+    DB_IO.with_label_values(&["insert", "wishlist"]).inc();
+
+    row.try_get("wl_id")
+}
+
+pub async fn delete_from_wishlist_table(db_client: &Client, id: i32) -> Result<(), Error> {
+    db_client
+        .execute("DELETE FROM wishlist WHERE wl_id = $1", &[&id])
+        .await?;
+    DB_IO.with_label_values(&["delete", "wishlist"]).inc();
+
+    Ok(())
+}
+
+fn craft_wishlist_item_from_row(row: Row) -> Result<WishlistItem, Error> {
+    let id: i32 = row.try_get("wl_id")?;
+    let barcode: Option<i64> = row.try_get("wl_barcode")?;
+    let bgg_id: Option<i32> = row.try_get("wl_bgg_id")?;
+    let priority: Option<i32> = row.try_get("wl_priority")?;
+    let want_to_buy: Option<bool> = row.try_get("wl_want_to_buy")?;
+
+    Ok(WishlistItem {
+        id: id as u32,
+        name: row.try_get("wl_name")?,
+        barcode: barcode.map(|b| b as u64),
+        bgg_id: bgg_id.map(|id| id as u32),
+        max_price: row.try_get("wl_max_price")?,
+        language: row.try_get("wl_language")?,
+        edition: row.try_get("wl_edition")?,
+        priority: priority.map(|p| p as u32),
+        want_to_buy: want_to_buy.unwrap_or_default(),
+        creation_date: row.try_get("wl_creation_date")?,
+    })
+}
+
+pub async fn select_wishlist_from_db(db_client: &Client) -> Result<Vec<WishlistItem>, Error> {
+    let res = db_client
+        .query(
+            "SELECT * FROM wishlist ORDER BY wl_priority ASC NULLS LAST, wl_name",
+            &[],
+        )
+        .await?;
+    DB_IO.with_label_values(&["select", "wishlist"]).inc();
+
+    res.into_iter().map(craft_wishlist_item_from_row).collect()
+}
+
+pub async fn insert_into_wishlist_match_table(
+    db_client: &Client,
+    item_id: i32,
+    ids: &[i32],
+) -> Result<(), Error> {
+    db_client
+        .execute(
+            "INSERT INTO wishlist_match (wlm_wl_id, wlm_oa_id, wlm_price, wlm_match_date)
+                SELECT $1, oa_id, oa_price, now() FROM okkazeo_announce WHERE oa_id = ANY($2)
+                ON CONFLICT (wlm_wl_id, wlm_oa_id) DO UPDATE
+                SET wlm_price = EXCLUDED.wlm_price, wlm_match_date = EXCLUDED.wlm_match_date
+                WHERE wishlist_match.wlm_price <> EXCLUDED.wlm_price",
+            &[&item_id, &ids],
+        )
+        .await?;
+    DB_IO.with_label_values(&["insert", "wishlist_match"]).inc();
+
+    Ok(())
+}
+
+/// Select the announces that may be a wishlist item, by barcode, bgg id or name.
+/// The candidates still have to be checked with WishlistItem::matches
+pub async fn select_wishlist_candidates_from_db(
+    db_client: &Client,
+    item: &WishlistItem,
+) -> Result<Games, Error> {
+    let barcode = item.barcode.unwrap_or_default() as i64;
+    let name = format!("%{}%", escape_like(item.name.trim()));
+    let bgg_url = item.bgg_id.map(|id| format!("%/boardgame/{}/%", id));
+
+    let mut select_req = "SELECT *
+                FROM okkazeo_announce oa
+                JOIN deal d on d.deal_oa_id = oa.oa_id
+                JOIN seller s on s.seller_id = oa.oa_seller
+                WHERE (oa.oa_barcode = $1 AND oa.oa_barcode <> 0)
+                    OR unaccent(oa.oa_name) ilike unaccent($2)"
+        .to_string();
+    let mut params: Vec<&(dyn ToSql + Sync)> = vec![&barcode, &name];
+    if let Some(bgg_url) = &bgg_url {
+        select_req.push_str(
+            "
+                    OR oa.oa_id IN (SELECT reviewer_oa_id FROM reviewer
+                        WHERE reviewer_name = 'bgg' AND reviewer_url LIKE $3)",
+        );
+        params.push(bgg_url);
+    }
+
+    let res = db_client.query(&select_req, &params).await?;
+    DB_IO
+        .with_label_values(&["select", "okkazeo_announce"])
+        .inc();
+
+    craft_games_from_rows(db_client, res).await
+}
+
+/// Match a wishlist item against the announces already in db and record the matches
+pub async fn record_wishlist_item_matches(
+    db_client: &Client,
+    item_id: i32,
+    item: &WishlistItem,
+) -> Result<Vec<i32>, Error> {
+    let matching_ids: Vec<i32> = select_wishlist_candidates_from_db(db_client, item)
+        .await?
+        .games
+        .iter()
+        .filter(|game| item.matches(game))
+        .map(|game| game.okkazeo_announce.id as i32)
+        .collect();
+
+    insert_into_wishlist_match_table(db_client, item_id, &matching_ids).await?;
+    Ok(matching_ids)
+}
+
+/// Match the given (new or repriced) announces against the wishlist
+/// and record the matches. Returns the matching announce ids of each item
+pub async fn record_wishlist_matches(
+    db_client: &Client,
+    games: &[Game],
+) -> Result<Vec<(WishlistItem, Vec<i32>)>, Error> {
+    let mut matches = Vec::new();
+    if games.is_empty() {
+        return Ok(matches);
+    }
+
+    for item in select_wishlist_from_db(db_client).await? {
+        let matching_ids: Vec<i32> = games
+            .iter()
+            .filter(|game| item.matches(game))
+            .map(|game| game.okkazeo_announce.id as i32)
+            .collect();
+        if matching_ids.is_empty() {
+            continue;
+        }
+
+        log::debug!(
+            "wishlist item {} matches {} announces",
+            item.name,
+            matching_ids.len()
+        );
+        insert_into_wishlist_match_table(db_client, item.id as i32, &matching_ids).await?;
+        matches.push((item, matching_ids));
+    }
+
+    Ok(matches)
+}
+
+/// Select the live announces matched by a wishlist item under its target price, cheapest first
+pub async fn select_wishlist_offers_from_db(
+    db_client: &Client,
+    item_id: i32,
+) -> Result<Games, Error> {
+    let select_req = "SELECT *
+                FROM wishlist_match wlm
+                JOIN wishlist wl on wl.wl_id = wlm.wlm_wl_id
+                JOIN okkazeo_announce oa on oa.oa_id = wlm.wlm_oa_id
+                JOIN deal d on d.deal_oa_id = oa.oa_id
+                JOIN seller s on s.seller_id = oa.oa_seller
+                WHERE wlm.wlm_wl_id = $1
+                -- a matched announce may have been repriced above the target since
+                AND (wl.wl_max_price IS NULL OR oa.oa_price <= wl.wl_max_price)
+                ORDER BY oa.oa_price ASC";
+
+    let res = db_client.query(select_req, &[&item_id]).await?;
+    DB_IO.with_label_values(&["select", "wishlist_match"]).inc();
+
+    craft_games_from_rows(db_client, res).await
+}
+
+/// Check if an announce has already been notified on a channel at this price or lower
+pub async fn check_if_notification_sent(
+    db_client: &Client,
+    channel: &str,
+    oa_id: i32,
+    price: f32,
+) -> Result<bool, Error> {
+    let select_req = "SELECT 1 FROM notification_sent
+                WHERE ns_channel = $1 AND ns_oa_id = $2 AND ns_price <= $3";
+
+    let res = db_client
+        .query(select_req, &[&channel, &oa_id, &price])
+        .await?;
+    DB_IO
+        .with_label_values(&["select", "notification_sent"])
+        .inc();
+
+    Ok(!res.is_empty())
+}
+
+pub async fn insert_into_notification_sent_table(
+    db_client: &Client,
+    channel: &str,
+    oa_id: i32,
+    price: f32,
+) -> Result<(), Error> {
+    let insert_req = format!(
+        r#"INSERT INTO notification_sent ({}, {}, {}, {}) VALUES ($1, $2, $3, now())
+            ON CONFLICT (ns_channel, ns_oa_id)
+            DO UPDATE SET ns_price = EXCLUDED.ns_price, ns_date = EXCLUDED.ns_date"#,
+        "ns_channel", "ns_oa_id", "ns_price", "ns_date"
+    );
+
+    db_client
+        .execute(&insert_req, &[&channel, &oa_id, &price])
+        .await?;
+    DB_IO
+        .with_label_values(&["insert", "notification_sent"])
+        .inc();
+
+    Ok(())
 }
 
 lazy_static! {
-    static ref DB_IO: IntCounterVec = register_int_counter_vec!(
+    static ref DB_POOL_CONNECTIONS: IntGaugeVec = register_int_gauge_vec!(
+        "db_pool_connections",
+        "Number of connections in the DB pool",
+        &["state"]
+    )
+    .unwrap();
+    pub(crate) static ref DB_IO: IntCounterVec = register_int_counter_vec!(
         "db_io",
         "Number of select/delete/insert on the db",
         &["operation", "table"]
@@ -0,0 +1,216 @@
+use std::fmt;
+
+use hyper::StatusCode;
+use lazy_static::lazy_static;
+use prometheus::{register_int_counter_vec, IntCounterVec};
+
+use crate::httpclient::HttpError;
+
+/// Why scraping a website failed, for the callers to tell what is worth retrying later
+/// from a page whose layout changed
+#[derive(Debug)]
+pub enum ScrapeError {
+    /// the website could not be reached or its response could not be read
+    Network(HttpError),
+    /// the website answered with an unexpected status
+    Status { url: String, status: StatusCode },
+    /// the website asked us to slow down, or is paused after repeated failures
+    RateLimited { host: String },
+    /// the robots.txt of the website forbids this page
+    Disallowed { url: String },
+    /// the page does not exist (anymore)
+    NotFound { url: String },
+    /// an element expected on the page is missing, its structure probably changed
+    SelectorMiss {
+        website: &'static str,
+        selector: String,
+    },
+    /// a value of the page could not be parsed
+    Parse {
+        website: &'static str,
+        message: String,
+    },
+    /// the scraped data could not be saved locally
+    Io(std::io::Error),
+}
+
+impl ScrapeError {
+    pub fn selector_miss(website: &'static str, selector: &str) -> Self {
+        ScrapeError::SelectorMiss {
+            website,
+            selector: selector.to_string(),
+        }
+    }
+
+    pub fn parse(website: &'static str, message: impl fmt::Display) -> Self {
+        ScrapeError::Parse {
+            website,
+            message: message.to_string(),
+        }
+    }
+
+    /// The kind of the error, as labelled in the metrics
+    pub fn kind(&self) -> &'static str {
+        match self {
+            ScrapeError::Network(_) => "network",
+            ScrapeError::Status { .. } => "status",
+            ScrapeError::RateLimited { .. } => "rate_limited",
+            ScrapeError::Disallowed { .. } => "disallowed",
+            ScrapeError::NotFound { .. } => "not_found",
+            ScrapeError::SelectorMiss { .. } => "selector_miss",
+            ScrapeError::Parse { .. } => "parse",
+            ScrapeError::Io(_) => "io",
+        }
+    }
+
+    /// Whether the same request may succeed later
+    pub fn is_transient(&self) -> bool {
+        matches!(
+            self,
+            ScrapeError::Network(_) | ScrapeError::Status { .. } | ScrapeError::RateLimited { .. }
+        )
+    }
+
+    /// Count the error in the scrape_errors metric of a website
+    pub fn record(&self, website: &str) {
+        SCRAPE_ERRORS
+            .with_label_values(&[website, self.kind()])
+            .inc();
+    }
+}
+
+impl fmt::Display for ScrapeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ScrapeError::Network(err) => write!(f, "{}", err),
+            ScrapeError::Status { url, status } => write!(f, "{} answered {}", url, status),
+            ScrapeError::RateLimited { host } => write!(f, "{} is rate limited", host),
+            ScrapeError::Disallowed { url } => write!(f, "{} is disallowed by robots.txt", url),
+            ScrapeError::NotFound { url } => write!(f, "{} not found", url),
+            ScrapeError::SelectorMiss { website, selector } => {
+                write!(f, "{} : nothing matches {}", website, selector)
+            }
+            ScrapeError::Parse { website, message } => write!(f, "{} : {}", website, message),
+            ScrapeError::Io(err) => write!(f, "{}", err),
+        }
+    }
+}
+
+impl std::error::Error for ScrapeError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            ScrapeError::Network(err) => Some(err),
+            ScrapeError::Io(err) => Some(err),
+            _ => None,
+        }
+    }
+}
+
+impl From<HttpError> for ScrapeError {
+    fn from(err: HttpError) -> Self {
+        match err {
+            HttpError::Status { url, status } if status == StatusCode::TOO_MANY_REQUESTS => {
+                ScrapeError::RateLimited {
+                    host: url.host_str().unwrap_or_default().to_string(),
+                }
+            }
+            HttpError::Status { url, status } => ScrapeError::Status {
+                url: url.to_string(),
+                status,
+            },
+            HttpError::CircuitOpen { host } => ScrapeError::RateLimited { host },
+            HttpError::Disallowed { url } => ScrapeError::Disallowed {
+                url: url.to_string(),
+            },
+            err => ScrapeError::Network(err),
+        }
+    }
+}
+
+impl From<reqwest::Error> for ScrapeError {
+    fn from(err: reqwest::Error) -> Self {
+        ScrapeError::Network(HttpError::Request(err))
+    }
+}
+
+impl From<std::io::Error> for ScrapeError {
+    fn from(err: std::io::Error) -> Self {
+        ScrapeError::Io(err)
+    }
+}
+
+lazy_static! {
+    static ref SCRAPE_ERRORS: IntCounterVec = register_int_counter_vec!(
+        "scrape_errors",
+        "Number of scraping errors by website and kind (network, status, rate_limited, disallowed, not_found, selector_miss, parse, io)",
+        &["website", "kind"]
+    )
+    .unwrap();
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+
+    use hyper::StatusCode;
+    use reqwest::Url;
+
+    use super::ScrapeError;
+    use crate::httpclient::HttpError;
+
+    #[test]
+    fn test_from_http_error() {
+        let url = Url::parse("https://www.okkazeo.com/annonces/view/1234").unwrap();
+        let status = |status| {
+            ScrapeError::from(HttpError::Status {
+                url: url.clone(),
+                status,
+            })
+        };
+
+        assert!(matches!(
+            status(StatusCode::TOO_MANY_REQUESTS),
+            ScrapeError::RateLimited { host } if host == "www.okkazeo.com"
+        ));
+        assert!(matches!(
+            status(StatusCode::BAD_GATEWAY),
+            ScrapeError::Status {
+                status: StatusCode::BAD_GATEWAY,
+                ..
+            }
+        ));
+        assert_eq!(
+            ScrapeError::from(HttpError::CircuitOpen {
+                host: "www.okkazeo.com".to_string()
+            })
+            .kind(),
+            "rate_limited"
+        );
+        assert_eq!(
+            ScrapeError::from(HttpError::Disallowed { url: url.clone() }).kind(),
+            "disallowed"
+        );
+        assert_eq!(
+            ScrapeError::from(HttpError::MissingFixture {
+                url,
+                path: PathBuf::from("tests/fixtures")
+            })
+            .kind(),
+            "network"
+        );
+    }
+
+    #[test]
+    fn test_is_transient() {
+        assert!(ScrapeError::RateLimited {
+            host: "boardgamegeek.com".to_string()
+        }
+        .is_transient());
+        assert!(!ScrapeError::selector_miss("okkazeo", ".desc_jeu .prix").is_transient());
+        assert!(!ScrapeError::parse("ultrajeux", "invalid price ,").is_transient());
+        assert!(!ScrapeError::NotFound {
+            url: "https://www.okkazeo.com/annonces/view/1".to_string()
+        }
+        .is_transient());
+    }
+}
@@ -0,0 +1,342 @@
+use tokio_postgres::types::ToSql;
+
+use crate::frontlib::Filters;
+
+/// Conditions on the listable_game view (aliased `lg`) selecting the announces matching
+/// some filters, every value of the filters being bound as a parameter instead of being
+/// written in the request
+pub struct FilterQuery {
+    conditions: Vec<String>,
+    params: Vec<Box<dyn ToSql + Sync + Send>>,
+    /// placeholder of the searched name, if any
+    name: Option<String>,
+}
+
+impl FilterQuery {
+    pub fn new(filters: &Filters) -> Self {
+        let mut query = FilterQuery {
+            conditions: Vec::new(),
+            params: Vec::new(),
+            name: None,
+        };
+
+        // this is a trick, if city filter is a number, it means that we're
+        // looking for postcode. Okkazeo format for city is : "city (postcode)"
+        let match_start = if filters
+            .city
+            .as_ref()
+            .is_some_and(|city| city.parse::<i32>().is_ok())
+        {
+            "("
+        } else {
+            ""
+        };
+
+        if let Some(name) = filters
+            .name
+            .as_deref()
+            .filter(|name| !name.trim().is_empty())
+        {
+            // matching words (stemmed), substring or close enough to catch typos, see
+            // the 0010_listable_game migration for the indexes
+            let pattern = query.bind(format!("%{}%", name));
+            let name = query.bind(name.to_string());
+            query.condition(format!(
+                "(lg.oa_name_search @@ plainto_tsquery('french', immutable_unaccent({name}))
+    OR immutable_unaccent(lg.oa_name) ILIKE immutable_unaccent({pattern})
+    OR immutable_unaccent(lg.oa_name) %> immutable_unaccent({name}))"
+            ));
+            query.name = Some(name);
+        }
+        let city = query.bind(format!(
+            "%{}{}%",
+            match_start,
+            filters.city.as_deref().unwrap_or_default()
+        ));
+        query.condition(format!("unaccent(lg.oa_city) ilike unaccent({})", city));
+        let vendor = query.bind(format!(
+            "%{}%",
+            filters.vendor.as_deref().unwrap_or_default()
+        ));
+        query.condition(format!(
+            "unaccent(lg.seller_name) ilike unaccent({})",
+            vendor
+        ));
+        let min_price = query.bind(filters.min_price.unwrap_or_default() as f32);
+        query.condition(format!("lg.oa_price > {}", min_price));
+        let max_price = query.bind(filters.max_price.unwrap_or(10000) as f32);
+        query.condition(format!("lg.oa_price < {}", max_price));
+
+        if filters.pro.is_some() {
+            query.condition("NOT lg.seller_is_pro".to_string());
+        }
+
+        let type_game = query.bind(filters.type_game);
+        let type_ext = query.bind(filters.type_ext);
+        let type_game_ext = query.bind(filters.type_game_ext);
+        let type_misc = query.bind(filters.type_misc);
+        query.condition(format!(
+            "(({} AND lg.oa_extension = 'Jeu') OR
+    ({} AND lg.oa_extension = 'Extension') OR
+    ({} AND lg.oa_extension = 'Jeu + Extension') OR
+    ({} AND lg.oa_extension NOT IN ('Jeu', 'Jeu + Extension', 'Extension')))",
+            type_game, type_ext, type_game_ext, type_misc
+        ));
+
+        if let Some(date) = &filters.date {
+            // the date is parsed by postgres, as it was when written in the request
+            let date = query.bind(date.clone());
+            query.condition(format!(
+                "lg.oa_last_modification_date >= {}::text::timestamptz",
+                date
+            ));
+        }
+
+        if filters.delivery.is_some() {
+            query.condition("lg.lg_delivery".to_string());
+        }
+
+        if let Some(rating) = filters.seller_rating {
+            let rating = query.bind(rating);
+            query.condition(format!("lg.seller_rating >= {}::real", rating));
+        }
+
+        if let Some(note) = filters.note {
+            let note = query.bind(note);
+            query.condition(format!("lg.lg_average_note >= {}::real", note));
+        }
+
+        query
+    }
+
+    /// Relevance of an announce for the searched name, higher is better. None when
+    /// there is no name filter
+    pub fn relevance_sql(&self) -> Option<String> {
+        self.name.as_ref().map(|name| {
+            format!(
+                "ts_rank(lg.oa_name_search, plainto_tsquery('french', immutable_unaccent({name})))
+    + word_similarity(immutable_unaccent({name}), immutable_unaccent(lg.oa_name))"
+            )
+        })
+    }
+
+    /// Bind a value as the next parameter of the request, and return its placeholder.
+    /// Used for the parameters of the rest of the request, like the pagination
+    pub fn bind<T: ToSql + Sync + Send + 'static>(&mut self, value: T) -> String {
+        self.params.push(Box::new(value));
+        format!("${}", self.params.len())
+    }
+
+    fn condition(&mut self, condition: String) {
+        self.conditions.push(condition);
+    }
+
+    /// The WHERE clause of a request on the view, empty without any condition
+    pub fn where_sql(&self) -> String {
+        if self.conditions.is_empty() {
+            return String::new();
+        }
+        format!("WHERE {}", self.conditions.join("\n                AND "))
+    }
+
+    /// The request selecting the ids of the matching announces
+    pub fn ids_sql(&self) -> String {
+        format!("SELECT lg.oa_id FROM listable_game lg {}", self.where_sql())
+    }
+
+    /// The parameters to give to the request, in the order they were bound
+    pub fn params(&self) -> Vec<&(dyn ToSql + Sync)> {
+        self.params
+            .iter()
+            .map(|p| p.as_ref() as &(dyn ToSql + Sync))
+            .collect()
+    }
+}
+
+/// Escape the wildcards of a value searched with LIKE, backslash being the default escape
+pub fn escape_like(value: &str) -> String {
+    let mut escaped = String::with_capacity(value.len());
+    for c in value.chars() {
+        if matches!(c, '%' | '_' | '\\') {
+            escaped.push('\\');
+        }
+        escaped.push(c);
+    }
+    escaped
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{escape_like, FilterQuery};
+    use crate::frontlib::Filters;
+
+    fn debug_params(query: &FilterQuery) -> Vec<String> {
+        query.params().iter().map(|p| format!("{:?}", p)).collect()
+    }
+
+    #[test]
+    fn test_default_filters() {
+        let query = FilterQuery::new(&Filters::default());
+        let sql = query.ids_sql();
+
+        assert!(sql.contains("unaccent(lg.oa_city) ilike unaccent($1)"));
+        assert!(sql.contains("unaccent(lg.seller_name) ilike unaccent($2)"));
+        assert!(sql.contains("lg.oa_price > $3"));
+        assert!(sql.contains("lg.oa_price < $4"));
+        assert!(sql.contains("($5 AND lg.oa_extension = 'Jeu')"));
+        assert!(sql.contains("($8 AND lg.oa_extension NOT IN"));
+        assert!(!sql.contains("oa_name"));
+        assert!(!sql.contains("seller_is_pro"));
+        assert!(!sql.contains("oa_last_modification_date"));
+        assert!(!sql.contains("lg_delivery"));
+        assert!(!sql.contains("seller_rating"));
+        assert!(!sql.contains("lg_average_note"));
+        assert!(query.relevance_sql().is_none());
+        assert_eq!(
+            debug_params(&query),
+            vec!["\"%%\"", "\"%%\"", "0.0", "10000.0", "true", "true", "true", "true"]
+        );
+    }
+
+    #[test]
+    fn test_text_filters() {
+        let query = FilterQuery::new(&Filters {
+            city: Some("Paris".to_string()),
+            vendor: Some("bob".to_string()),
+            min_price: Some(5),
+            max_price: Some(50),
+            ..Default::default()
+        });
+
+        assert_eq!(
+            debug_params(&query)[..4],
+            ["\"%Paris%\"", "\"%bob%\"", "5.0", "50.0"]
+        );
+    }
+
+    #[test]
+    fn test_name_filter() {
+        let query = FilterQuery::new(&Filters {
+            name: Some("Catane".to_string()),
+            ..Default::default()
+        });
+        let sql = query.ids_sql();
+
+        assert!(
+            sql.contains("lg.oa_name_search @@ plainto_tsquery('french', immutable_unaccent($2))")
+        );
+        assert!(sql.contains("immutable_unaccent(lg.oa_name) ILIKE immutable_unaccent($1)"));
+        assert!(sql.contains("immutable_unaccent(lg.oa_name) %> immutable_unaccent($2)"));
+        assert!(sql.contains("unaccent(lg.oa_city) ilike unaccent($3)"));
+        assert!(query
+            .relevance_sql()
+            .unwrap()
+            .contains("immutable_unaccent($2)"));
+        assert_eq!(debug_params(&query)[..2], ["\"%Catane%\"", "\"Catane\""]);
+    }
+
+    #[test]
+    fn test_blank_name_filter() {
+        let query = FilterQuery::new(&Filters {
+            name: Some(" ".to_string()),
+            ..Default::default()
+        });
+
+        assert!(!query.ids_sql().contains("oa_name"));
+        assert!(query.relevance_sql().is_none());
+        assert_eq!(query.params().len(), 8);
+    }
+
+    #[test]
+    fn test_postcode_filter() {
+        let query = FilterQuery::new(&Filters {
+            city: Some("75".to_string()),
+            ..Default::default()
+        });
+
+        assert_eq!(debug_params(&query)[0], "\"%(75%\"");
+    }
+
+    #[test]
+    fn test_type_filters() {
+        let query = FilterQuery::new(&Filters {
+            type_game: false,
+            type_misc: false,
+            ..Default::default()
+        });
+
+        assert_eq!(
+            debug_params(&query)[4..],
+            ["false", "true", "true", "false"]
+        );
+    }
+
+    #[test]
+    fn test_flag_filters() {
+        let query = FilterQuery::new(&Filters {
+            pro: Some(true),
+            delivery: Some(true),
+            ..Default::default()
+        });
+        let sql = query.ids_sql();
+
+        assert!(sql.contains("AND NOT lg.seller_is_pro"));
+        assert!(sql.contains("AND lg.lg_delivery"));
+        assert_eq!(query.params().len(), 8);
+    }
+
+    #[test]
+    fn test_date_filter() {
+        let date = "2024-01-01' OR '1'='1".to_string();
+        let query = FilterQuery::new(&Filters {
+            date: Some(date.clone()),
+            ..Default::default()
+        });
+        let sql = query.ids_sql();
+
+        assert!(sql.contains("lg.oa_last_modification_date >= $9::text::timestamptz"));
+        assert!(!sql.contains(&date));
+        assert_eq!(debug_params(&query)[8], format!("{:?}", date));
+    }
+
+    #[test]
+    fn test_rating_filters() {
+        let query = FilterQuery::new(&Filters {
+            seller_rating: Some(4.5),
+            note: Some(7.0),
+            ..Default::default()
+        });
+        let sql = query.ids_sql();
+
+        assert!(sql.contains("AND lg.seller_rating >= $9::real"));
+        assert!(sql.contains("AND lg.lg_average_note >= $10::real"));
+        assert_eq!(debug_params(&query)[8..], ["4.5", "7.0"]);
+    }
+
+    #[test]
+    fn test_all_filters() {
+        let mut query = FilterQuery::new(&Filters {
+            name: Some("Catan".to_string()),
+            date: Some("2024-01-01".to_string()),
+            pro: Some(true),
+            delivery: Some(true),
+            seller_rating: Some(4.0),
+            note: Some(6.0),
+            ..Default::default()
+        });
+        let limit = query.bind(25_i64);
+        let sql = query.ids_sql();
+
+        assert!(sql.contains("$11::text::timestamptz"));
+        assert!(sql.contains("lg.seller_rating >= $12::real"));
+        assert!(sql.contains("lg.lg_average_note >= $13::real"));
+        assert_eq!(limit, "$14");
+        assert_eq!(query.params().len(), 14);
+    }
+
+    #[test]
+    fn test_escape_like() {
+        assert_eq!(escape_like("Catane"), "Catane");
+        assert_eq!(escape_like("100% _Jeu_ \\o/"), "100\\% \\_Jeu\\_ \\\\o/");
+    }
+}
@@ -0,0 +1,102 @@
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use super::Filters;
+
+/// Short-lived cache of the number of games matching some filters, so that popular
+/// combinations (like the front page without any filter) are not counted on every hit.
+/// The listable_game view is only refreshed once per feed cycle anyway
+pub struct CountCache {
+    ttl: Duration,
+    capacity: usize,
+    entries: Mutex<HashMap<String, (Instant, i64)>>,
+}
+
+impl CountCache {
+    pub fn new(ttl: Duration, capacity: usize) -> Self {
+        Self {
+            ttl,
+            capacity,
+            entries: Mutex::new(HashMap::new()),
+        }
+    }
+
+    fn key(filters: &Filters) -> String {
+        serde_json::to_string(filters).unwrap_or_default()
+    }
+
+    /// The count cached for these filters, if it has not expired
+    pub fn get(&self, filters: &Filters) -> Option<i64> {
+        let entries = self.entries.lock().unwrap();
+        entries
+            .get(&Self::key(filters))
+            .filter(|(inserted, _)| inserted.elapsed() < self.ttl)
+            .map(|(_, count)| *count)
+    }
+
+    /// Cache a count, making room by dropping the expired entries, then the oldest one
+    pub fn insert(&self, filters: &Filters, count: i64) {
+        let mut entries = self.entries.lock().unwrap();
+        if entries.len() >= self.capacity {
+            entries.retain(|_, (inserted, _)| inserted.elapsed() < self.ttl);
+        }
+        if entries.len() >= self.capacity {
+            let oldest = entries
+                .iter()
+                .min_by_key(|(_, (inserted, _))| *inserted)
+                .map(|(key, _)| key.clone());
+            if let Some(oldest) = oldest {
+                entries.remove(&oldest);
+            }
+        }
+        entries.insert(Self::key(filters), (Instant::now(), count));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::CountCache;
+    use crate::frontlib::Filters;
+
+    fn filters_with_name(name: &str) -> Filters {
+        Filters {
+            name: Some(name.to_string()),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_get() {
+        let cache = CountCache::new(Duration::from_secs(60), 10);
+        assert_eq!(cache.get(&Filters::default()), None);
+
+        cache.insert(&Filters::default(), 42);
+        cache.insert(&filters_with_name("catan"), 3);
+        assert_eq!(cache.get(&Filters::default()), Some(42));
+        assert_eq!(cache.get(&filters_with_name("catan")), Some(3));
+        assert_eq!(cache.get(&filters_with_name("azul")), None);
+    }
+
+    #[test]
+    fn test_expiry() {
+        let cache = CountCache::new(Duration::ZERO, 10);
+        cache.insert(&Filters::default(), 42);
+        assert_eq!(cache.get(&Filters::default()), None);
+    }
+
+    #[test]
+    fn test_capacity() {
+        let cache = CountCache::new(Duration::from_secs(60), 2);
+        cache.insert(&filters_with_name("catan"), 1);
+        std::thread::sleep(Duration::from_millis(2));
+        cache.insert(&filters_with_name("azul"), 2);
+        cache.insert(&filters_with_name("splendor"), 3);
+
+        assert_eq!(cache.get(&filters_with_name("catan")), None);
+        assert_eq!(cache.get(&filters_with_name("azul")), Some(2));
+        assert_eq!(cache.get(&filters_with_name("splendor")), Some(3));
+    }
+}
@@ -0,0 +1,241 @@
+use atom_syndication::{Content, Entry, Feed, Link, Text};
+use chrono::Utc;
+use rss::{Channel, Enclosure, Guid, Item};
+use tera::escape_html;
+
+use crate::game::{Game, Games};
+
+const FEED_TITLE: &str = "aubonmeeple.fr - bonnes affaires";
+const FEED_DESCRIPTION: &str = "Annonces okkazeo comparées aux prix des boutiques";
+
+fn entry_title(game: &Game) -> String {
+    let announce = &game.okkazeo_announce;
+    if game.deal.deal_price != 0 {
+        format!(
+            "{} - {:.2}€ ({}{}%)",
+            announce.name,
+            announce.price,
+            if game.deal.deal_price > 0 { "+" } else { "" },
+            game.deal.deal_percentage
+        )
+    } else {
+        format!("{} - {:.2}€", announce.name, announce.price)
+    }
+}
+
+fn entry_description(game: &Game, base_url: &str) -> String {
+    let announce = &game.okkazeo_announce;
+    let mut description = format!(
+        r#"<p><img src="{}/{}" alt="{}" width="150" height="150" /></p><p><b>{:.2}€</b>"#,
+        base_url,
+        announce.image,
+        escape_html(&announce.name),
+        announce.price
+    );
+    if game.deal.deal_price != 0 {
+        description.push_str(&format!(
+            " ({}{}%)",
+            if game.deal.deal_price > 0 { "+" } else { "" },
+            game.deal.deal_percentage
+        ));
+    }
+    description.push_str("</p>");
+
+    if let Some(reference) = game.cheapest_reference() {
+        description.push_str(&format!(
+            r#"<p>Neuf : <a href="{}">{:.2}€ chez {}</a></p>"#,
+            escape_html(&reference.url),
+            reference.price,
+            escape_html(&reference.name)
+        ));
+    }
+    description.push_str(&format!(
+        "<p>Vendu par {}{}</p>",
+        escape_html(&announce.seller.name),
+        announce
+            .city
+            .as_ref()
+            .map_or(String::new(), |city| format!(" ({})", escape_html(city)))
+    ));
+
+    description
+}
+
+fn entry_link(game: &Game, base_url: &str) -> String {
+    format!("{}/game/{}", base_url, game.okkazeo_announce.id)
+}
+
+/// Build an rss 2.0 feed of the given games, self_url being the url of the feed itself
+pub fn build_rss_feed(games: &Games, base_url: &str, self_url: &str) -> String {
+    let items: Vec<Item> = games
+        .games
+        .iter()
+        .map(|game| {
+            let link = entry_link(game, base_url);
+            Item {
+                title: Some(entry_title(game)),
+                link: Some(link.clone()),
+                description: Some(entry_description(game, base_url)),
+                guid: Some(Guid {
+                    value: link,
+                    permalink: true,
+                }),
+                pub_date: Some(game.okkazeo_announce.last_modification_date.to_rfc2822()),
+                enclosure: Some(Enclosure {
+                    url: format!("{}/{}", base_url, game.okkazeo_announce.image),
+                    length: String::from("0"),
+                    mime_type: String::from("image/jpeg"),
+                }),
+                ..Default::default()
+            }
+        })
+        .collect();
+
+    let channel = Channel {
+        title: FEED_TITLE.to_string(),
+        link: self_url.to_string(),
+        description: FEED_DESCRIPTION.to_string(),
+        language: Some(String::from("fr")),
+        last_build_date: Some(Utc::now().to_rfc2822()),
+        items,
+        ..Default::default()
+    };
+
+    channel.to_string()
+}
+
+/// Build an atom feed of the given games, self_url being the url of the feed itself
+pub fn build_atom_feed(games: &Games, base_url: &str, self_url: &str) -> String {
+    let entries: Vec<Entry> = games
+        .games
+        .iter()
+        .map(|game| {
+            let link = entry_link(game, base_url);
+            Entry {
+                title: Text::plain(entry_title(game)),
+                id: link.clone(),
+                updated: game.okkazeo_announce.last_modification_date.into(),
+                links: vec![
+                    Link {
+                        href: link,
+                        rel: String::from("alternate"),
+                        ..Default::default()
+                    },
+                    Link {
+                        href: format!("{}/{}", base_url, game.okkazeo_announce.image),
+                        rel: String::from("enclosure"),
+                        mime_type: Some(String::from("image/jpeg")),
+                        ..Default::default()
+                    },
+                ],
+                content: Some(Content {
+                    value: Some(entry_description(game, base_url)),
+                    content_type: Some(String::from("html")),
+                    ..Default::default()
+                }),
+                ..Default::default()
+            }
+        })
+        .collect();
+
+    let updated = games
+        .games
+        .iter()
+        .map(|game| game.okkazeo_announce.last_modification_date)
+        .max()
+        .unwrap_or_else(Utc::now);
+
+    let feed = Feed {
+        title: Text::plain(FEED_TITLE),
+        subtitle: Some(Text::plain(FEED_DESCRIPTION)),
+        id: self_url.to_string(),
+        updated: updated.into(),
+        links: vec![Link {
+            href: self_url.to_string(),
+            rel: String::from("self"),
+            ..Default::default()
+        }],
+        entries,
+        ..Default::default()
+    };
+
+    feed.to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::game::{Game, Games, Reference};
+
+    use super::{build_atom_feed, build_rss_feed};
+
+    fn games() -> Games {
+        let mut game = Game::default();
+        game.okkazeo_announce.id = 42;
+        game.okkazeo_announce.name = "Skaal & co".to_string();
+        game.okkazeo_announce.image = "img/42.jpg".to_string();
+        game.okkazeo_announce.price = 20.0;
+        game.okkazeo_announce.seller.name = "bob".to_string();
+        game.deal.deal_price = -10;
+        game.deal.deal_percentage = -33;
+        for (name, price) in [("philibert", 35.0), ("ludocortex", 30.0)] {
+            game.references.insert(
+                name.to_string(),
+                Reference {
+                    name: name.to_string(),
+                    price,
+                    url: format!("https://{}.example/skaal", name),
+                },
+            );
+        }
+
+        let mut games = Games::new();
+        games.games.push(Box::new(game));
+        games
+    }
+
+    #[test]
+    fn test_rss() {
+        let xml = build_rss_feed(
+            &games(),
+            "https://aubonmeeple.fr",
+            "https://aubonmeeple.fr/feed.xml",
+        );
+        let feed = feed_rs::parser::parse(xml.as_bytes()).unwrap();
+        assert_eq!(feed.entries.len(), 1);
+
+        let entry = &feed.entries[0];
+        assert_eq!(
+            entry.title.as_ref().unwrap().content,
+            "Skaal & co - 20.00€ (-33%)"
+        );
+        assert_eq!(entry.links[0].href, "https://aubonmeeple.fr/game/42");
+        let description = &entry.summary.as_ref().unwrap().content;
+        assert!(description.contains("https://aubonmeeple.fr/img/42.jpg"));
+        assert!(description.contains("30.00€ chez ludocortex"));
+    }
+
+    #[test]
+    fn test_atom() {
+        let xml = build_atom_feed(
+            &games(),
+            "https://aubonmeeple.fr",
+            "https://aubonmeeple.fr/feed.atom",
+        );
+        let feed = feed_rs::parser::parse(xml.as_bytes()).unwrap();
+        assert_eq!(feed.entries.len(), 1);
+
+        let entry = &feed.entries[0];
+        assert_eq!(entry.id, "https://aubonmeeple.fr/game/42");
+        assert_eq!(
+            entry.title.as_ref().unwrap().content,
+            "Skaal & co - 20.00€ (-33%)"
+        );
+        assert!(entry
+            .links
+            .iter()
+            .any(|link| link.href == "https://aubonmeeple.fr/img/42.jpg"));
+        let content = entry.content.as_ref().unwrap().body.as_ref().unwrap();
+        assert!(content.contains("30.00€ chez ludocortex"));
+        assert!(content.contains("(-33%)"));
+    }
+}
@@ -1,5 +1,8 @@
+pub mod count_cache;
+pub mod feed;
 pub mod server;
 
+use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -24,6 +27,7 @@ pub struct Filters {
     pub pro: Option<bool>,
     pub delivery: Option<bool>,
     pub note: Option<f32>,
+    pub seller_rating: Option<f32>,
     pub max_price: Option<i32>,
     pub min_price: Option<i32>,
     pub type_game: bool,
@@ -46,12 +50,35 @@ impl Default for Filters {
             pro: None,
             delivery: None,
             note: None,
+            seller_rating: None,
             max_price: None,
             min_price: None,
         }
     }
 }
 
+/// A named combination of filters and sort, evaluated by the backend against new announces
+#[derive(Debug, Clone, Serialize)]
+pub struct SavedSearch {
+    pub id: u32,
+    pub name: String,
+    pub filters: Filters,
+    pub sort: Sort,
+    pub last_visit_date: DateTime<Utc>,
+    pub nb_new_matches: u32,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct SavedSearchForm {
+    pub name: String,
+}
+
+/// Beginning of a name typed in the search box
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct SuggestQuery {
+    pub q: String,
+}
+
 // this is ugly, but otherwise the Form from axum doesnt work properly
 #[derive(Debug, Clone, Default, Serialize, Deserialize)]
 pub struct FiltersForm {
@@ -62,6 +89,7 @@ pub struct FiltersForm {
     pub pro_form: Option<String>,
     pub delivery_form: Option<String>,
     pub note_form: Option<String>,
+    pub seller_rating_form: Option<String>,
     pub max_price_form: Option<String>,
     pub min_price_form: Option<String>,
     pub per_page_form: Option<String>,
@@ -1,20 +1,47 @@
-use axum::extract::Query;
-use axum::response::Html;
-use axum::Extension;
-use axum::{extract::Form, routing::get, Router};
+use axum::async_trait;
+use axum::extract::{FromRequestParts, Host, Path, Query};
+use axum::http::request::Parts;
+use axum::http::{header, StatusCode};
+use axum::response::{Html, IntoResponse, Redirect, Response};
+use axum::{
+    extract::Form,
+    routing::{get, post},
+    Router,
+};
+use axum::{Extension, Json};
 use prometheus::{register_int_counter_vec, IntCounter, IntCounterVec};
 use serde::Serialize;
 use std::sync::Arc;
+use std::time::Duration;
 use tera::{Context, Tera};
-use tokio_postgres::Client;
 use tower_http::services::ServeDir;
 
 use lazy_static::lazy_static;
 use prometheus::register_int_counter;
 
-use crate::db::{connect_db, select_count_filtered_games_from_db, select_games_from_db};
+use crate::db::{
+    delete_from_saved_search_table, delete_from_wishlist_table, get_db_client,
+    insert_into_saved_search_table, insert_into_wishlist_table, record_wishlist_item_matches,
+    select_archived_announces_from_seller_from_db, select_games_from_seller_from_db,
+    select_new_saved_search_matches_from_db, select_other_announces_from_db,
+    select_price_history_from_db, select_saved_search_from_db, select_saved_searches_from_db,
+    select_seller_from_db, select_seller_stats_from_db, select_wishlist_from_db,
+    select_wishlist_offers_from_db, update_saved_search_last_visit, DbPool,
+};
 
-use super::{Filters, FiltersForm, Pagination, Sort};
+use super::count_cache::CountCache;
+use super::feed::{build_atom_feed, build_rss_feed};
+use super::{Filters, FiltersForm, Pagination, SavedSearchForm, Sort, SuggestQuery};
+use crate::game::Games;
+use crate::storage::{open_storage, Storage};
+use crate::wishlist::{WishlistEntry, WishlistForm};
+
+/// shorter searches would match almost every announce
+const SUGGEST_MIN_LEN: usize = 2;
+const SUGGEST_LIMIT: i64 = 10;
+/// how long a count of filtered games is reused, and how many filter combinations are kept
+const COUNT_CACHE_TTL: Duration = Duration::from_secs(30);
+const COUNT_CACHE_CAPACITY: usize = 1000;
 
 #[derive(Debug, Clone, Serialize)]
 pub struct State {
@@ -23,61 +50,106 @@ pub struct State {
     pub sort: Sort,
 }
 
+/// A connection taken from the pool for the time of a request, a broken connection
+/// being replaced by the pool instead of failing every following request
+pub struct DbClient(pub deadpool_postgres::Client);
+
+#[async_trait]
+impl<S: Send + Sync> FromRequestParts<S> for DbClient {
+    type Rejection = (StatusCode, &'static str);
+
+    async fn from_request_parts(parts: &mut Parts, state: &S) -> Result<Self, Self::Rejection> {
+        let Extension(pool) = Extension::<DbPool>::from_request_parts(parts, state)
+            .await
+            .map_err(|_| {
+                (
+                    StatusCode::NOT_IMPLEMENTED,
+                    "fonctionnalité disponible uniquement avec Postgres",
+                )
+            })?;
+
+        match get_db_client(&pool).await {
+            Ok(client) => Ok(DbClient(client)),
+            Err(e) => {
+                DB_ERRORS.with_label_values(&[&e.to_string()]).inc();
+                Err((
+                    StatusCode::SERVICE_UNAVAILABLE,
+                    "base de données indisponible",
+                ))
+            }
+        }
+    }
+}
+
+/// A query string value, percent-encoded
+fn encode_param(value: &str) -> String {
+    form_urlencoded::byte_serialize(value.as_bytes()).collect()
+}
+
 pub fn format_url_params(state: &State) -> String {
     format!(
-        "?page={}&per_page={}{}{}{}{}{}&type_ext={}&type_game_ext={}&type_game={}&type_misc={}{}{}{}{}&sort={}",
+        "?page={}&per_page={}{}{}{}{}{}&type_ext={}&type_game_ext={}&type_game={}&type_misc={}{}{}{}{}{}&sort={}",
         state.pagination.page,
         state.pagination.per_page,
         state
             .filters
             .city
             .as_ref()
-            .map_or(String::new(), |city| format!("&city={}", city)),
+            .map_or(String::new(), |city| format!("&city={}", encode_param(city))),
         state
             .filters
             .date
             .as_ref()
-            .map_or(String::new(), |date| format!("&date={}", date)),
+            .map_or(String::new(), |date| format!("&date={}", encode_param(date))),
         state
             .filters
             .name
             .as_ref()
-            .map_or(String::new(), |name| format!("&name={}", name)),
-        if state.filters.vendor.is_some() {
-            format!("&vendor={}", state.filters.vendor.as_ref().unwrap())
-        } else {
-            String::new()
-        },
-        if state.filters.pro.is_some() {
-            format!("&pro={}", state.filters.pro.as_ref().unwrap())
-        } else {
-            String::new()
-        },
+            .map_or(String::new(), |name| format!("&name={}", encode_param(name))),
+        state
+            .filters
+            .vendor
+            .as_ref()
+            .map_or(String::new(), |vendor| {
+                format!("&vendor={}", encode_param(vendor))
+            }),
+        state
+            .filters
+            .pro
+            .as_ref()
+            .map_or(String::new(), |pro| format!("&pro={}", pro)),
         state.filters.type_ext,
         state.filters.type_game_ext,
         state.filters.type_game,
         state.filters.type_misc,
-        if state.filters.delivery.is_some() {
-            format!("&delivery={}", state.filters.delivery.as_ref().unwrap())
-        } else {
-            String::new()
-        },
-        if state.filters.note.is_some() {
-            format!("&note={}", state.filters.note.as_ref().unwrap())
-        } else {
-            String::new()
-        },
-        if state.filters.max_price.is_some() {
-            format!("&max_price={}", state.filters.max_price.as_ref().unwrap())
-        } else {
-            String::new()
-        },
-        if state.filters.min_price.is_some() {
-            format!("&min_price={}", state.filters.min_price.as_ref().unwrap())
-        } else {
-            String::new()
-        },
-        state.sort.sort,
+        state
+            .filters
+            .delivery
+            .as_ref()
+            .map_or(String::new(), |delivery| format!("&delivery={}", delivery)),
+        state
+            .filters
+            .note
+            .as_ref()
+            .map_or(String::new(), |note| format!("&note={}", note)),
+        state
+            .filters
+            .seller_rating
+            .as_ref()
+            .map_or(String::new(), |seller_rating| {
+                format!("&seller_rating={}", seller_rating)
+            }),
+        state
+            .filters
+            .max_price
+            .as_ref()
+            .map_or(String::new(), |max_price| format!("&max_price={}", max_price)),
+        state
+            .filters
+            .min_price
+            .as_ref()
+            .map_or(String::new(), |min_price| format!("&min_price={}", min_price)),
+        encode_param(&state.sort.sort),
     )
 }
 
@@ -85,7 +157,8 @@ pub async fn root(
     pagination: Option<Query<Pagination>>,
     sort: Option<Query<Sort>>,
     filters: Option<Query<Filters>>,
-    Extension(db_client): Extension<Arc<Client>>,
+    Extension(count_cache): Extension<Arc<CountCache>>,
+    Extension(storage): Extension<Arc<dyn Storage>>,
     filters_form: Form<FiltersForm>,
 ) -> Html<String> {
     AXUM_ROOT_GET.inc();
@@ -100,25 +173,16 @@ pub async fn root(
     log::debug!("FILTER PARAM : {:?}", &filters_param);
     log::debug!("FILTER FORM : {:?}", &filters_form);
 
-    if filters_form.0.city_form.is_some() {
-        let note = filters_form
+    if let Some(city_form) = filters_form.0.city_form {
+        let note = filters_form.0.note_form.unwrap().parse::<f32>().ok();
+        let seller_rating = filters_form
             .0
-            .note_form
-            .unwrap()
+            .seller_rating_form
+            .unwrap_or_default()
             .parse::<f32>()
-            .map_or_else(|_| None, Some);
-        let max_price = filters_form
-            .0
-            .max_price_form
-            .unwrap()
-            .parse::<i32>()
-            .map_or_else(|_| None, Some);
-        let min_price = filters_form
-            .0
-            .min_price_form
-            .unwrap()
-            .parse::<i32>()
-            .map_or_else(|_| None, Some);
+            .ok();
+        let max_price = filters_form.0.max_price_form.unwrap().parse::<i32>().ok();
+        let min_price = filters_form.0.min_price_form.unwrap().parse::<i32>().ok();
         let pro: Option<bool> = if filters_form.0.pro_form == Some("on".to_string()) {
             Some(true)
         } else {
@@ -133,10 +197,10 @@ pub async fn root(
         } else {
             None
         };
-        let city = if filters_form.0.city_form.as_ref().unwrap().is_empty() {
+        let city = if city_form.is_empty() {
             None
         } else {
-            filters_form.0.city_form
+            Some(city_form)
         };
         let date = if filters_form.0.date_form.as_ref().unwrap().is_empty() {
             None
@@ -175,6 +239,7 @@ pub async fn root(
             pro,
             delivery,
             note,
+            seller_rating,
             max_price,
             min_price,
             type_game,
@@ -184,15 +249,20 @@ pub async fn root(
         }
     }
 
-    let total_items =
-        match select_count_filtered_games_from_db(&db_client, filters_param.clone()).await {
-            Ok(val) => val as usize,
+    let total_items = match count_cache.get(&filters_param) {
+        Some(val) => val as usize,
+        None => match storage.count_games(&filters_param).await {
+            Ok(val) => {
+                count_cache.insert(&filters_param, val);
+                val as usize
+            }
             Err(e) => {
                 DB_ERRORS.with_label_values(&[&e.to_string()]).inc();
                 log::error!("[SERVER] error getting count filtered games : {}", e);
                 0
             }
-        };
+        },
+    };
 
     log::debug!("[SERVER] counting {} games entries from db", total_items);
 
@@ -207,7 +277,7 @@ pub async fn root(
         filters: filters_param,
     };
 
-    let part_games = match select_games_from_db(&db_client, &state).await {
+    let part_games = match storage.select_games(&state).await {
         Ok(g) => g,
         Err(e) => {
             DB_ERRORS.with_label_values(&[&e.to_string()]).inc();
@@ -222,13 +292,6 @@ pub async fn root(
         part_games.games.len()
     );
 
-    let tera = match Tera::new("templates/*") {
-        Ok(t) => t,
-        Err(e) => {
-            log::error!("error tera loading template : {}", e);
-            return Html(String::new());
-        }
-    };
     let mut ctx = Context::new();
     ctx.insert("style_css", &"css/style.css");
     ctx.insert("background_img", &"assets/banner.jpg");
@@ -247,7 +310,7 @@ pub async fn root(
 
     ctx.insert("games", &part_games.games);
 
-    let total_pages = (total_items + state.pagination.per_page - 1) / state.pagination.per_page;
+    let total_pages = total_items.div_ceil(state.pagination.per_page);
     ctx.insert("total_pages", &total_pages);
 
     // this is dumb but there is no way in tera to do an iteration
@@ -258,7 +321,435 @@ pub async fn root(
     }
     ctx.insert("pages_vec", &pages_vec);
 
-    match tera.render("frontpage.tera", &ctx) {
+    render_template("frontpage.tera", &ctx)
+}
+
+pub async fn game_page(
+    Path(oa_id): Path<u32>,
+    Host(host): Host,
+    Extension(storage): Extension<Arc<dyn Storage>>,
+    db_client: Option<DbClient>,
+) -> (StatusCode, Html<String>) {
+    AXUM_GAME_GET.inc();
+    let game = match storage.select_game(oa_id).await {
+        Ok(Some(g)) => g,
+        Ok(None) => {
+            log::debug!("[SERVER] no game with id {} in db", oa_id);
+            return not_found("Cette annonce n'existe pas ou a été vendue.");
+        }
+        Err(e) => {
+            DB_ERRORS.with_label_values(&[&e.to_string()]).inc();
+            log::error!("[SERVER] error getting game {} : {}", oa_id, e);
+            return (StatusCode::INTERNAL_SERVER_ERROR, Html(String::new()));
+        }
+    };
+
+    // the price history and the other announces are only kept in Postgres
+    let (price_history, other_announces) = match db_client {
+        Some(DbClient(db_client)) => {
+            let price_history =
+                match select_price_history_from_db(&db_client, game.okkazeo_announce.id as i32)
+                    .await
+                {
+                    Ok(h) => h,
+                    Err(e) => {
+                        DB_ERRORS.with_label_values(&[&e.to_string()]).inc();
+                        log::error!("[SERVER] error getting price history : {}", e);
+                        Vec::new()
+                    }
+                };
+
+            let other_announces = match select_other_announces_from_db(&db_client, &game).await {
+                Ok(g) => g,
+                Err(e) => {
+                    DB_ERRORS.with_label_values(&[&e.to_string()]).inc();
+                    log::error!("[SERVER] error getting other announces : {}", e);
+                    Default::default()
+                }
+            };
+            (price_history, other_announces)
+        }
+        None => (Vec::new(), Games::default()),
+    };
+
+    let mut ctx = Context::new();
+    ctx.insert("style_css", &"/css/style.css");
+    ctx.insert("background_img", &"/assets/banner.jpg");
+    ctx.insert(
+        "permalink",
+        &format!("{}/game/{}", public_base_url(&host), oa_id),
+    );
+    ctx.insert("game", &game);
+    ctx.insert("price_history", &price_history);
+    ctx.insert("other_announces", &other_announces.games);
+
+    (StatusCode::OK, render_template("game.tera", &ctx))
+}
+
+pub async fn seller_page(
+    Path(seller_id): Path<u32>,
+    DbClient(db_client): DbClient,
+) -> (StatusCode, Html<String>) {
+    AXUM_SELLER_GET.inc();
+    let seller_id = seller_id as i32;
+    let seller = match select_seller_from_db(&db_client, seller_id).await {
+        Ok(Some(s)) => s,
+        Ok(None) => {
+            log::debug!("[SERVER] no seller with id {} in db", seller_id);
+            return not_found("Ce vendeur n'a aucune annonce enregistrée.");
+        }
+        Err(e) => {
+            DB_ERRORS.with_label_values(&[&e.to_string()]).inc();
+            log::error!("[SERVER] error getting seller : {}", e);
+            return (StatusCode::INTERNAL_SERVER_ERROR, Html(String::new()));
+        }
+    };
+
+    let stats = match select_seller_stats_from_db(&db_client, seller_id).await {
+        Ok(s) => s,
+        Err(e) => {
+            DB_ERRORS.with_label_values(&[&e.to_string()]).inc();
+            log::error!("[SERVER] error getting seller stats : {}", e);
+            Default::default()
+        }
+    };
+
+    let live_announces = match select_games_from_seller_from_db(&db_client, seller_id).await {
+        Ok(g) => g,
+        Err(e) => {
+            DB_ERRORS.with_label_values(&[&e.to_string()]).inc();
+            log::error!("[SERVER] error getting seller announces : {}", e);
+            Default::default()
+        }
+    };
+
+    let past_announces =
+        match select_archived_announces_from_seller_from_db(&db_client, seller_id).await {
+            Ok(a) => a,
+            Err(e) => {
+                DB_ERRORS.with_label_values(&[&e.to_string()]).inc();
+                log::error!("[SERVER] error getting seller archived announces : {}", e);
+                Vec::new()
+            }
+        };
+
+    let mut ctx = Context::new();
+    ctx.insert("style_css", &"/css/style.css");
+    ctx.insert("background_img", &"/assets/banner.jpg");
+    ctx.insert("seller", &seller);
+    ctx.insert("stats", &stats);
+    ctx.insert("live_announces", &live_announces.games);
+    ctx.insert("past_announces", &past_announces);
+
+    (StatusCode::OK, render_template("seller.tera", &ctx))
+}
+
+pub async fn save_search(
+    sort: Option<Query<Sort>>,
+    filters: Option<Query<Filters>>,
+    DbClient(db_client): DbClient,
+    Form(form): Form<SavedSearchForm>,
+) -> Redirect {
+    let filters = filters.unwrap_or_default().0;
+    let sort = sort.unwrap_or_default().0;
+    let name = form.name.trim();
+    if name.is_empty() {
+        return Redirect::to("/searches");
+    }
+
+    log::debug!("[SERVER] saving search {} : {:?}", name, filters);
+    if let Err(e) = insert_into_saved_search_table(&db_client, name, &filters, &sort).await {
+        DB_ERRORS.with_label_values(&[&e.to_string()]).inc();
+        log::error!("[SERVER] error saving search : {}", e);
+    }
+
+    Redirect::to("/searches")
+}
+
+pub async fn delete_search(Path(search_id): Path<u32>, DbClient(db_client): DbClient) -> Redirect {
+    if let Err(e) = delete_from_saved_search_table(&db_client, search_id as i32).await {
+        DB_ERRORS.with_label_values(&[&e.to_string()]).inc();
+        log::error!("[SERVER] error deleting saved search : {}", e);
+    }
+
+    Redirect::to("/searches")
+}
+
+pub async fn searches_page(DbClient(db_client): DbClient) -> Html<String> {
+    AXUM_SEARCHES_GET.inc();
+    let searches = match select_saved_searches_from_db(&db_client).await {
+        Ok(s) => s,
+        Err(e) => {
+            DB_ERRORS.with_label_values(&[&e.to_string()]).inc();
+            log::error!("[SERVER] error getting saved searches : {}", e);
+            Vec::new()
+        }
+    };
+
+    // url params to apply each saved search on the front page
+    let searches_url_params: Vec<String> = searches
+        .iter()
+        .map(|search| {
+            format_url_params(&State {
+                pagination: Pagination::default(),
+                filters: search.filters.clone(),
+                sort: search.sort.clone(),
+            })
+        })
+        .collect();
+
+    let mut ctx = Context::new();
+    ctx.insert("style_css", &"/css/style.css");
+    ctx.insert("background_img", &"/assets/banner.jpg");
+    ctx.insert("searches", &searches);
+    ctx.insert("searches_url_params", &searches_url_params);
+
+    render_template("searches.tera", &ctx)
+}
+
+pub async fn search_matches_page(
+    Path(search_id): Path<u32>,
+    DbClient(db_client): DbClient,
+) -> (StatusCode, Html<String>) {
+    AXUM_SEARCHES_GET.inc();
+    let search = match select_saved_search_from_db(&db_client, search_id as i32).await {
+        Ok(Some(s)) => s,
+        Ok(None) => return not_found("Cette recherche n'existe pas ou a été supprimée."),
+        Err(e) => {
+            DB_ERRORS.with_label_values(&[&e.to_string()]).inc();
+            log::error!("[SERVER] error getting saved search : {}", e);
+            return (StatusCode::INTERNAL_SERVER_ERROR, Html(String::new()));
+        }
+    };
+
+    let games = match select_new_saved_search_matches_from_db(&db_client, &search).await {
+        Ok(g) => g,
+        Err(e) => {
+            DB_ERRORS.with_label_values(&[&e.to_string()]).inc();
+            log::error!("[SERVER] error getting saved search matches : {}", e);
+            return (StatusCode::INTERNAL_SERVER_ERROR, Html(String::new()));
+        }
+    };
+
+    // the matches are now seen, next visit only shows what arrived in between
+    if let Err(e) = update_saved_search_last_visit(&db_client, search_id as i32).await {
+        DB_ERRORS.with_label_values(&[&e.to_string()]).inc();
+        log::error!("[SERVER] error updating saved search last visit : {}", e);
+    }
+
+    let mut ctx = Context::new();
+    ctx.insert("style_css", &"/css/style.css");
+    ctx.insert("background_img", &"/assets/banner.jpg");
+    ctx.insert("search", &search);
+    ctx.insert(
+        "url_params",
+        &format_url_params(&State {
+            pagination: Pagination::default(),
+            filters: search.filters.clone(),
+            sort: search.sort.clone(),
+        }),
+    );
+    ctx.insert("games", &games.games);
+
+    (StatusCode::OK, render_template("search_matches.tera", &ctx))
+}
+
+pub async fn add_to_wishlist(
+    DbClient(db_client): DbClient,
+    Form(form): Form<WishlistForm>,
+) -> Redirect {
+    let item = match form.to_item() {
+        Some(item) => item,
+        None => return Redirect::to("/wishlist"),
+    };
+
+    log::debug!("[SERVER] adding to wishlist : {:?}", item);
+    let item_id = match insert_into_wishlist_table(&db_client, &item).await {
+        Ok(id) => id,
+        Err(e) => {
+            DB_ERRORS.with_label_values(&[&e.to_string()]).inc();
+            log::error!("[SERVER] error adding to wishlist : {}", e);
+            return Redirect::to("/wishlist");
+        }
+    };
+
+    // the announces already online are matched right away, the backend handles the next ones
+    if let Err(e) = record_wishlist_item_matches(&db_client, item_id, &item).await {
+        DB_ERRORS.with_label_values(&[&e.to_string()]).inc();
+        log::error!("[SERVER] error recording wishlist matches : {}", e);
+    }
+
+    Redirect::to("/wishlist")
+}
+
+pub async fn delete_from_wishlist(
+    Path(item_id): Path<u32>,
+    DbClient(db_client): DbClient,
+) -> Redirect {
+    if let Err(e) = delete_from_wishlist_table(&db_client, item_id as i32).await {
+        DB_ERRORS.with_label_values(&[&e.to_string()]).inc();
+        log::error!("[SERVER] error deleting wishlist item : {}", e);
+    }
+
+    Redirect::to("/wishlist")
+}
+
+pub async fn wishlist_page(DbClient(db_client): DbClient) -> Html<String> {
+    AXUM_WISHLIST_GET.inc();
+    let items = match select_wishlist_from_db(&db_client).await {
+        Ok(items) => items,
+        Err(e) => {
+            DB_ERRORS.with_label_values(&[&e.to_string()]).inc();
+            log::error!("[SERVER] error getting wishlist : {}", e);
+            Vec::new()
+        }
+    };
+
+    let mut entries = Vec::new();
+    for item in items {
+        let offers = match select_wishlist_offers_from_db(&db_client, item.id as i32).await {
+            Ok(g) => g,
+            Err(e) => {
+                DB_ERRORS.with_label_values(&[&e.to_string()]).inc();
+                log::error!("[SERVER] error getting wishlist offers : {}", e);
+                Games::new()
+            }
+        };
+        entries.push(WishlistEntry::new(item, offers));
+    }
+
+    let mut ctx = Context::new();
+    ctx.insert("style_css", &"/css/style.css");
+    ctx.insert("background_img", &"/assets/banner.jpg");
+    ctx.insert("entries", &entries);
+
+    render_template("wishlist.tera", &ctx)
+}
+
+/// number of games of a feed, whatever the per_page asked
+const MAX_FEED_ITEMS: usize = 100;
+
+/// Base url used for the absolute links (permalinks, feeds)
+fn public_base_url(host: &str) -> String {
+    std::env::var("FRONTEND_PUBLIC_URL")
+        .map(|url| url.trim_end_matches('/').to_string())
+        .unwrap_or_else(|_| format!("http://{}", host))
+}
+
+/// Select the games of a filtered view for a feed, the feed url itself is returned along.
+/// A feed has MAX_FEED_ITEMS items at most
+async fn select_feed_games(
+    storage: &dyn Storage,
+    host: &str,
+    path: &str,
+    mut state: State,
+) -> Option<(Games, String, String)> {
+    state.pagination.per_page = state.pagination.per_page.min(MAX_FEED_ITEMS);
+    let base_url = public_base_url(host);
+    let self_url = format!("{}{}{}", base_url, path, format_url_params(&state));
+
+    match storage.select_games(&state).await {
+        Ok(g) => Some((g, base_url, self_url)),
+        Err(e) => {
+            DB_ERRORS.with_label_values(&[&e.to_string()]).inc();
+            log::error!("[SERVER] error getting games for feed : {}", e);
+            None
+        }
+    }
+}
+
+pub async fn rss_feed(
+    pagination: Option<Query<Pagination>>,
+    sort: Option<Query<Sort>>,
+    filters: Option<Query<Filters>>,
+    Host(host): Host,
+    Extension(storage): Extension<Arc<dyn Storage>>,
+) -> Response {
+    AXUM_FEED_GET.inc();
+    let state = State {
+        pagination: pagination.unwrap_or_default().0,
+        sort: sort.unwrap_or_default().0,
+        filters: filters.unwrap_or_default().0,
+    };
+
+    match select_feed_games(storage.as_ref(), &host, "/feed.xml", state).await {
+        Some((games, base_url, self_url)) => (
+            [(header::CONTENT_TYPE, "application/rss+xml; charset=utf-8")],
+            build_rss_feed(&games, &base_url, &self_url),
+        )
+            .into_response(),
+        None => StatusCode::INTERNAL_SERVER_ERROR.into_response(),
+    }
+}
+
+pub async fn atom_feed(
+    pagination: Option<Query<Pagination>>,
+    sort: Option<Query<Sort>>,
+    filters: Option<Query<Filters>>,
+    Host(host): Host,
+    Extension(storage): Extension<Arc<dyn Storage>>,
+) -> Response {
+    AXUM_FEED_GET.inc();
+    let state = State {
+        pagination: pagination.unwrap_or_default().0,
+        sort: sort.unwrap_or_default().0,
+        filters: filters.unwrap_or_default().0,
+    };
+
+    match select_feed_games(storage.as_ref(), &host, "/feed.atom", state).await {
+        Some((games, base_url, self_url)) => (
+            [(header::CONTENT_TYPE, "application/atom+xml; charset=utf-8")],
+            build_atom_feed(&games, &base_url, &self_url),
+        )
+            .into_response(),
+        None => StatusCode::INTERNAL_SERVER_ERROR.into_response(),
+    }
+}
+
+/// Typeahead suggestions of announce names for the name filter, as a JSON array
+pub async fn suggest(
+    Query(query): Query<SuggestQuery>,
+    Extension(storage): Extension<Arc<dyn Storage>>,
+) -> Response {
+    AXUM_SUGGEST_GET.inc();
+    let q = query.q.trim();
+    if q.chars().count() < SUGGEST_MIN_LEN {
+        return Json(Vec::<String>::new()).into_response();
+    }
+
+    match storage.select_name_suggestions(q, SUGGEST_LIMIT).await {
+        Ok(names) => Json(names).into_response(),
+        Err(e) => {
+            DB_ERRORS.with_label_values(&[&e.to_string()]).inc();
+            log::error!("[SERVER] error getting name suggestions : {}", e);
+            StatusCode::INTERNAL_SERVER_ERROR.into_response()
+        }
+    }
+}
+
+/// A 404 page explaining what could not be found
+fn not_found(message: &str) -> (StatusCode, Html<String>) {
+    let mut ctx = Context::new();
+    ctx.insert("style_css", &"/css/style.css");
+    ctx.insert("background_img", &"/assets/banner.jpg");
+    ctx.insert("message", message);
+
+    (
+        StatusCode::NOT_FOUND,
+        render_template("not_found.tera", &ctx),
+    )
+}
+
+fn render_template(template: &str, ctx: &Context) -> Html<String> {
+    let tera = match Tera::new("templates/*") {
+        Ok(t) => t,
+        Err(e) => {
+            log::error!("error tera loading template : {}", e);
+            return Html(String::new());
+        }
+    };
+
+    match tera.render(template, ctx) {
         Ok(r) => Html(r),
         Err(e) => {
             log::error!("error tera rendering : {}", e);
@@ -268,15 +759,32 @@ pub async fn root(
 }
 
 pub async fn run_server(bind_addr: String) {
-    let client = Arc::new(connect_db().await.unwrap());
+    let (storage, pool) = open_storage().await.expect("cannot open storage");
     log::info!("[SERVER] connected with DB");
 
-    let app = Router::new()
+    let mut app = Router::new()
         .route("/", get(root).post(root))
+        .route("/game/:oa_id", get(game_page))
+        .route("/seller/:seller_id", get(seller_page))
+        .route("/searches", get(searches_page).post(save_search))
+        .route("/searches/:search_id", get(search_matches_page))
+        .route("/searches/:search_id/delete", post(delete_search))
+        .route("/feed.xml", get(rss_feed))
+        .route("/feed.atom", get(atom_feed))
+        .route("/wishlist", get(wishlist_page).post(add_to_wishlist))
+        .route("/wishlist/:item_id/delete", post(delete_from_wishlist))
+        .route("/api/v1/suggest", get(suggest))
         .nest_service("/img", ServeDir::new("img"))
         .nest_service("/assets", ServeDir::new("assets"))
         .nest_service("/css", ServeDir::new("css"))
-        .layer(Extension(client));
+        .layer(Extension(storage))
+        .layer(Extension(Arc::new(CountCache::new(
+            COUNT_CACHE_TTL,
+            COUNT_CACHE_CAPACITY,
+        ))));
+    if let Some(pool) = pool {
+        app = app.layer(Extension(pool));
+    }
 
     log::info!("[SERVER] starting server on {}", bind_addr);
     let listener = tokio::net::TcpListener::bind(bind_addr).await.unwrap();
@@ -290,7 +798,61 @@ lazy_static! {
         "Number of get or post resquests to root route"
     )
     .unwrap();
+    static ref AXUM_GAME_GET: IntCounter =
+        register_int_counter!("axum_game_get", "Number of get resquests to game route").unwrap();
+    static ref AXUM_SELLER_GET: IntCounter =
+        register_int_counter!("axum_seller_get", "Number of get resquests to seller route")
+            .unwrap();
+    static ref AXUM_SEARCHES_GET: IntCounter = register_int_counter!(
+        "axum_searches_get",
+        "Number of get resquests to saved searches routes"
+    )
+    .unwrap();
+    static ref AXUM_WISHLIST_GET: IntCounter = register_int_counter!(
+        "axum_wishlist_get",
+        "Number of get resquests to wishlist route"
+    )
+    .unwrap();
+    static ref AXUM_FEED_GET: IntCounter =
+        register_int_counter!("axum_feed_get", "Number of get resquests to feed routes").unwrap();
+    static ref AXUM_SUGGEST_GET: IntCounter = register_int_counter!(
+        "axum_suggest_get",
+        "Number of get resquests to suggest route"
+    )
+    .unwrap();
     static ref DB_ERRORS: IntCounterVec =
         register_int_counter_vec!("db_errors", "Number of error from db queries", &["error"])
             .unwrap();
 }
+
+#[cfg(test)]
+mod tests {
+    use axum::http::StatusCode;
+
+    use super::{format_url_params, not_found, State};
+    use crate::frontlib::Filters;
+
+    #[test]
+    fn test_not_found() {
+        let (status, html) = not_found("Cette annonce n'existe pas ou a été vendue.");
+        assert_eq!(status, StatusCode::NOT_FOUND);
+        assert!(html.0.contains("Page introuvable"));
+        assert!(html.0.contains("a été vendue"));
+    }
+
+    #[test]
+    fn test_format_url_params_encoding() {
+        let state = State {
+            filters: Filters {
+                name: Some("Dungeons & Dragons #1".to_string()),
+                vendor: Some("bob=alice".to_string()),
+                ..Default::default()
+            },
+            pagination: Default::default(),
+            sort: Default::default(),
+        };
+        let params = format_url_params(&state);
+        assert!(params.contains("&name=Dungeons+%26+Dragons+%231&"));
+        assert!(params.contains("&vendor=bob%3Dalice&"));
+    }
+}
@@ -1,17 +1,20 @@
+use crate::error::ScrapeError;
+use crate::health::{self, ScrapeOutcome};
 use crate::website::agorajeux::get_agorajeux_price_and_url_by_name;
 use crate::website::knapix::get_knapix_prices;
 use crate::website::ludifolie::get_ludifolie_price_and_url_by_name;
 use crate::website::ludocortex::get_ludocortex_price_and_url;
 use crate::website::okkazeo::{
     get_okkazeo_announce_page, get_okkazeo_barcode, get_okkazeo_city, get_okkazeo_seller,
+    get_okkazeo_seller_rating,
 };
 use crate::website::philibert::get_philibert_price_and_url;
 use chrono::{DateTime, Utc};
 use feed_rs::model::Entry;
+use scraper::Html;
 use serde::{Deserialize, Serialize};
 use std::cmp::Ordering;
 use std::collections::HashMap;
-use std::error;
 
 use crate::website::okkazeo::{
     download_okkazeo_game_image, get_okkazeo_announce_extension, get_okkazeo_announce_image,
@@ -54,6 +57,34 @@ pub struct Seller {
     pub url: String,
     pub nb_announces: u32,
     pub is_pro: bool,
+    pub rating: f32,
+    pub nb_ratings: u32,
+}
+
+impl Seller {
+    /// The okkazeo rating and its number of feedbacks, none when it could not be fetched
+    pub fn known_rating(&self) -> Option<(f32, u32)> {
+        (self.rating > 0.0).then_some((self.rating, self.nb_ratings))
+    }
+}
+
+/// An announce that is not available on okkazeo anymore, kept to compute seller statistics
+#[derive(Debug, Default, Clone, Serialize)]
+pub struct ArchivedAnnounce {
+    pub id: u32,
+    pub name: String,
+    pub price: f32,
+    pub deal_percentage: i32,
+    pub creation_date: Option<DateTime<Utc>>,
+    pub removal_date: DateTime<Utc>,
+}
+
+#[derive(Debug, Default, Clone, Serialize)]
+pub struct SellerStats {
+    pub nb_live_announces: u32,
+    pub nb_past_announces: u32,
+    pub average_deal_percentage: f32,
+    pub average_days_to_sell: Option<f32>,
 }
 
 #[derive(Debug, Default, Clone, Serialize)]
@@ -79,12 +110,20 @@ pub struct Game {
     pub deal: Deal,
 }
 
+#[derive(Debug, Default, Clone, Serialize)]
+pub struct PricePoint {
+    pub price: f32,
+    pub date: DateTime<Utc>,
+}
+
 #[derive(Debug, Default, Clone, Serialize)]
 pub struct Deal {
     pub deal_price: i32,
     pub deal_percentage: i32,
 }
 
+/// Games are ordered by last modification, the most recent first. PartialOrd agrees with
+/// it, so that `sort` and `max` order games the same way
 impl Ord for Game {
     fn cmp(&self, other: &Self) -> Ordering {
         other
@@ -95,7 +134,7 @@ impl Ord for Game {
 }
 impl PartialOrd for Game {
     fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
-        Some(other.cmp(self))
+        Some(self.cmp(other))
     }
 }
 
@@ -109,6 +148,14 @@ impl PartialEq for Game {
 impl Eq for Game {}
 
 impl Game {
+    /// Cheapest shop selling the game new
+    pub fn cheapest_reference(&self) -> Option<&Reference> {
+        self.references
+            .values()
+            .filter(|reference| reference.price > 0.0)
+            .min_by(|a, b| a.price.total_cmp(&b.price))
+    }
+
     pub fn get_deal_advantage(&mut self) {
         // okkazeo is counted as a ref, so we need at least 2 refs
         if self.references.is_empty() {
@@ -138,9 +185,179 @@ impl Game {
         self.deal.deal_percentage = percent;
     }
 
+    /// Query the shops for the prices of the game new, adding them to its references.
+    /// Returns the shops whose query failed
+    pub async fn get_references(&mut self) -> Vec<&'static str> {
+        let mut failed = Vec::new();
+        let knapix = get_knapix_prices(self).await;
+        health::record(
+            "knapix",
+            match knapix {
+                Ok(0) => ScrapeOutcome::NoMatch,
+                _ => ScrapeOutcome::of(&knapix),
+            },
+        );
+        if let Err(e) = knapix {
+            e.record("knapix");
+            failed.push("knapix");
+            log::error!("Error gettin knapix prices : {:?}", e);
+        }
+
+        if !self.references.contains_key("philibert") {
+            let result = get_philibert_price_and_url(
+                &self.okkazeo_announce.name,
+                self.okkazeo_announce.barcode,
+            )
+            .await;
+            health::record("philibert", ScrapeOutcome::of_search(&result));
+            match result {
+                Err(e) => {
+                    e.record("philibert");
+                    failed.push("philibert");
+                    log::error!("error getting philibert price : {}", e)
+                }
+                Ok(v) => {
+                    if let Some((price, url)) = v {
+                        self.references.insert(
+                            "philibert".to_string(),
+                            Reference {
+                                name: "philibert".to_string(),
+                                price,
+                                url,
+                            },
+                        );
+                    }
+                }
+            }
+        }
+
+        if !self.references.contains_key("agorajeux") {
+            let result = get_agorajeux_price_and_url_by_name(&self.okkazeo_announce.name).await;
+            health::record("agorajeux", ScrapeOutcome::of_search(&result));
+            match result {
+                Err(e) => {
+                    e.record("agorajeux");
+                    failed.push("agorajeux");
+                    log::error!("error getting agorajeux price : {}", e)
+                }
+                Ok(v) => {
+                    if let Some((price, url)) = v {
+                        self.references.insert(
+                            "agorajeux".to_string(),
+                            Reference {
+                                name: "agorajeux".to_string(),
+                                price,
+                                url,
+                            },
+                        );
+                    }
+                }
+            }
+        }
+
+        if !self.references.contains_key("ludifolie") {
+            let result = get_ludifolie_price_and_url_by_name(&self.okkazeo_announce.name).await;
+            health::record("ludifolie", ScrapeOutcome::of_search(&result));
+            match result {
+                Err(e) => {
+                    e.record("ludifolie");
+                    failed.push("ludifolie");
+                    log::error!("error getting ludifolie price : {}", e)
+                }
+                Ok(v) => {
+                    if let Some((price, url)) = v {
+                        self.references.insert(
+                            "ludifolie".to_string(),
+                            Reference {
+                                name: "ludifolie".to_string(),
+                                price,
+                                url,
+                            },
+                        );
+                    }
+                }
+            }
+        }
+
+        /*if self.references.get("ultrajeux").is_none() {
+            match get_ultrajeux_price_and_url(
+                &self.okkazeo_announce.name,
+                self.okkazeo_announce.barcode,
+            )
+            .await
+            {
+                Err(e) => log::error!("error getting ultrajeux price : {}", e),
+                Ok(v) => {
+                    if let Some((price, url)) = v {
+                        self.references.insert(
+                            "ultrajeux".to_string(),
+                            Reference {
+                                name: "ultrajeux".to_string(),
+                                price,
+                                url,
+                            },
+                        );
+                    }
+                }
+            }
+        }*/
+
+        if !self.references.contains_key("ludocortex") {
+            let result = get_ludocortex_price_and_url(
+                &self.okkazeo_announce.name,
+                self.okkazeo_announce.barcode,
+            )
+            .await;
+            health::record("ludocortex", ScrapeOutcome::of_search(&result));
+            match result {
+                Err(e) => {
+                    e.record("ludocortex");
+                    failed.push("ludocortex");
+                    log::error!("error getting ludocortex price : {}", e)
+                }
+                Ok(v) => {
+                    if let Some((price, url)) = v {
+                        self.references.insert(
+                            "ludocortex".to_string(),
+                            Reference {
+                                name: "ludocortex".to_string(),
+                                price,
+                                url,
+                            },
+                        );
+                    }
+                }
+            }
+        }
+
+        failed
+    }
+
+    /// Query the shops again for the prices of the game, keeping the previous reference of
+    /// the shops whose query failed, and recompute the deal
+    pub async fn refresh_references(&mut self) {
+        let previous = std::mem::take(&mut self.references);
+        for shop in self.get_references().await {
+            // the shops compared by knapix are queried directly too, except ultrajeux
+            let shop = if shop == "knapix" { "ultrajeux" } else { shop };
+            if let Some(reference) = previous.get(shop) {
+                self.references
+                    .entry(shop.to_string())
+                    .or_insert_with(|| reference.clone());
+            }
+        }
+        self.deal = Deal::default();
+        self.get_deal_advantage();
+    }
+
     pub async fn get_reviews(&mut self) {
-        match get_bgg_note(&self.okkazeo_announce.name).await {
-            Err(e) => log::error!("error getting bgg note : {}", e),
+        let result = get_bgg_note(&self.okkazeo_announce.name).await;
+        health::record("bgg", ScrapeOutcome::of_search(&result));
+        match result {
+            Err(e) => {
+                e.record("bgg");
+                log::error!("error getting bgg note : {}", e)
+            }
             Ok(v) => {
                 if let Some(r) = v {
                     self.review.reviews.insert("bgg".to_string(), r);
@@ -178,13 +395,50 @@ impl Review {
     }
 }
 
-pub async fn get_game_infos(
+/// Fill an announce from its okkazeo page, returning the url of its image
+fn parse_okkazeo_announce(
+    announce: &mut OkkazeoAnnounce,
     entry: Option<&Entry>,
-    id: u32,
-) -> Result<Box<Game>, Box<dyn error::Error + Send + Sync>> {
+    document: &Html,
+) -> Result<String, ScrapeError> {
+    announce.url = format!("https://www.okkazeo.com/annonces/view/{}", announce.id);
+    announce.price = get_okkazeo_announce_price(document)?;
+    announce.extension = get_okkazeo_announce_extension(document)?;
+    announce.last_modification_date = match entry.and_then(|e| e.updated) {
+        Some(updated) => updated,
+        None => get_okkazeo_announce_modification_date(document)?,
+    };
+    let image_url = get_okkazeo_announce_image(document)?;
+    announce.barcode = get_okkazeo_barcode(document);
+    announce.city = get_okkazeo_city(document);
+    announce.shipping = get_okkazeo_shipping(document);
+    if let Some(s) = get_okkazeo_seller(document) {
+        announce.seller = s;
+    }
+    announce.seller.is_pro = okkazeo_is_pro_seller(document);
+
+    let name = get_okkazeo_announce_name(document)?;
+    let mut inside_parentheses = false;
+    let mut name_result = String::new();
+
+    for c in name.chars() {
+        match c {
+            '(' => inside_parentheses = true,
+            ')' => inside_parentheses = false,
+            _ if !inside_parentheses => name_result.push(c),
+            _ => (),
+        }
+    }
+    announce.name = name_result;
+
+    Ok(image_url)
+}
+
+/// Scrap an okkazeo announce and the prices of the game in the shops. Only the errors of
+/// okkazeo are returned, the shops that fail being skipped
+pub async fn get_game_infos(entry: Option<&Entry>, id: u32) -> Result<Box<Game>, ScrapeError> {
     log::debug!("fetching game infos for id {:?}", id);
 
-    let image_url: String;
     let mut game = Box::new(Game {
         okkazeo_announce: OkkazeoAnnounce {
             id,
@@ -194,154 +448,61 @@ pub async fn get_game_infos(
         ..Default::default()
     });
 
-    {
-        let (document, _) = get_okkazeo_announce_page(id).await;
-        game.okkazeo_announce.url = format!("https://www.okkazeo.com/annonces/view/{}", id);
-        game.okkazeo_announce.price = get_okkazeo_announce_price(&document)?;
-        game.okkazeo_announce.extension = get_okkazeo_announce_extension(&document)?;
-        game.okkazeo_announce.last_modification_date = if let Some(e) = entry {
-            e.updated.unwrap()
-        } else {
-            get_okkazeo_announce_modification_date(&document)?
-        };
-        image_url = get_okkazeo_announce_image(&document)?;
-        game.okkazeo_announce.barcode = get_okkazeo_barcode(&document);
-        game.okkazeo_announce.city = get_okkazeo_city(&document);
-        game.okkazeo_announce.shipping = get_okkazeo_shipping(&document);
-        if let Some(s) = get_okkazeo_seller(&document) {
-            game.okkazeo_announce.seller = s;
+    let image_url = {
+        let announce = get_okkazeo_announce_page(id).await.and_then(|document| {
+            parse_okkazeo_announce(&mut game.okkazeo_announce, entry, &document)
+        });
+        health::record("okkazeo", ScrapeOutcome::of(&announce));
+        announce?
+    };
+    match get_okkazeo_seller_rating(&game.okkazeo_announce.seller).await {
+        Err(e) => {
+            e.record("okkazeo");
+            log::error!("error getting okkazeo seller rating : {}", e)
         }
-        game.okkazeo_announce.seller.is_pro = okkazeo_is_pro_seller(&document);
-
-        let name = get_okkazeo_announce_name(&document)?;
-        let mut inside_parentheses = false;
-        let mut name_result = String::new();
-
-        for c in name.chars() {
-            match c {
-                '(' => inside_parentheses = true,
-                ')' => inside_parentheses = false,
-                _ if !inside_parentheses => name_result.push(c),
-                _ => (),
-            }
+        Ok(Some((rating, nb_ratings))) => {
+            game.okkazeo_announce.seller.rating = rating;
+            game.okkazeo_announce.seller.nb_ratings = nb_ratings;
         }
-        game.okkazeo_announce.name = name_result;
+        Ok(None) => log::debug!("no rating for seller {}", game.okkazeo_announce.seller.name),
     }
+
     let image = download_okkazeo_game_image(&image_url).await?;
     game.okkazeo_announce.image = image;
 
-    if let Err(e) = get_knapix_prices(&mut game).await {
-        log::error!("Error gettin knapix prices : {:?}", e);
-    }
+    game.get_references().await;
+    game.get_reviews().await;
+    game.get_deal_advantage();
 
-    if game.references.get("philibert").is_none() {
-        match get_philibert_price_and_url(
-            &game.okkazeo_announce.name,
-            game.okkazeo_announce.barcode,
-        )
-        .await
-        {
-            Err(e) => log::error!("error getting philibert price : {}", e),
-            Ok(v) => {
-                if let Some((price, url)) = v {
-                    game.references.insert(
-                        "philibert".to_string(),
-                        Reference {
-                            name: "philibert".to_string(),
-                            price,
-                            url,
-                        },
-                    );
-                }
-            }
-        }
-    }
+    log::debug!("returning game {:?}", game);
+    Ok(game)
+}
 
-    if game.references.get("agorajeux").is_none() {
-        match get_agorajeux_price_and_url_by_name(&game.okkazeo_announce.name).await {
-            Err(e) => log::error!("error getting agorajeux price : {}", e),
-            Ok(v) => {
-                if let Some((price, url)) = v {
-                    game.references.insert(
-                        "agorajeux".to_string(),
-                        Reference {
-                            name: "agorajeux".to_string(),
-                            price,
-                            url,
-                        },
-                    );
-                }
-            }
-        }
-    }
+#[cfg(test)]
+mod tests {
+    use chrono::{TimeZone, Utc};
 
-    if game.references.get("ludifolie").is_none() {
-        match get_ludifolie_price_and_url_by_name(&game.okkazeo_announce.name).await {
-            Err(e) => log::error!("error getting ludifolie price : {}", e),
-            Ok(v) => {
-                if let Some((price, url)) = v {
-                    game.references.insert(
-                        "ludifolie".to_string(),
-                        Reference {
-                            name: "ludifolie".to_string(),
-                            price,
-                            url,
-                        },
-                    );
-                }
-            }
-        }
-    }
+    use super::Game;
 
-    /*if game.references.get("ultrajeux").is_none() {
-        match get_ultrajeux_price_and_url(
-            &game.okkazeo_announce.name,
-            game.okkazeo_announce.barcode,
-        )
-        .await
-        {
-            Err(e) => log::error!("error getting ultrajeux price : {}", e),
-            Ok(v) => {
-                if let Some((price, url)) = v {
-                    game.references.insert(
-                        "ultrajeux".to_string(),
-                        Reference {
-                            name: "ultrajeux".to_string(),
-                            price,
-                            url,
-                        },
-                    );
-                }
-            }
-        }
-    }*/
-
-    if game.references.get("ludocortex").is_none() {
-        match get_ludocortex_price_and_url(
-            &game.okkazeo_announce.name,
-            game.okkazeo_announce.barcode,
-        )
-        .await
-        {
-            Err(e) => log::error!("error getting ultrajeux price : {}", e),
-            Ok(v) => {
-                if let Some((price, url)) = v {
-                    game.references.insert(
-                        "ludocortex".to_string(),
-                        Reference {
-                            name: "ludocortex".to_string(),
-                            price,
-                            url,
-                        },
-                    );
-                }
-            }
-        }
+    fn game(id: u32, day: u32) -> Game {
+        let mut game = Game::default();
+        game.okkazeo_announce.id = id;
+        game.okkazeo_announce.last_modification_date =
+            Utc.with_ymd_and_hms(2024, 1, day, 12, 0, 0).unwrap();
+        game
     }
 
-    game.get_reviews().await;
-    game.get_deal_advantage();
-
-    log::debug!("returning game {:?}", game);
-    Ok(game)
+    #[test]
+    fn test_ordering() {
+        let mut games = [game(1, 2), game(2, 3), game(3, 1)];
+        games.sort();
+        let ids: Vec<u32> = games.iter().map(|g| g.okkazeo_announce.id).collect();
+        assert_eq!(ids, vec![2, 1, 3]);
+
+        assert!(game(1, 3) < game(2, 1));
+        assert_eq!(
+            game(1, 3).partial_cmp(&game(2, 1)),
+            Some(game(1, 3).cmp(&game(2, 1)))
+        );
+    }
 }
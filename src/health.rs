@@ -0,0 +1,377 @@
+use std::collections::{BTreeMap, VecDeque};
+use std::sync::Mutex;
+
+use chrono::{DateTime, Utc};
+use lazy_static::lazy_static;
+use prometheus::{
+    register_gauge_vec, register_int_counter_vec, register_int_gauge_vec, GaugeVec, IntCounterVec,
+    IntGaugeVec,
+};
+use serde::Serialize;
+
+use crate::error::ScrapeError;
+
+/// length of a bucket of outcomes, in seconds
+const BUCKET_SECONDS: i64 = 3600;
+/// number of buckets kept for each source
+const HISTORY_BUCKETS: usize = 24;
+/// buckets making the recent window, compared to the older ones
+const RECENT_BUCKETS: usize = 2;
+/// scrapes needed in a window before judging it
+const MIN_SAMPLES: u64 = 10;
+/// share of recent scrapes finding a broken page from which a source is unhealthy
+const MAX_BROKEN_RATE: f64 = 0.5;
+/// a source is unhealthy when its recent success rate falls under this share of the older one
+const COLLAPSE_RATIO: f64 = 0.5;
+
+lazy_static! {
+    static ref HEALTH: ScraperHealth = ScraperHealth::default();
+}
+
+/// What came out of scraping a page
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ScrapeOutcome {
+    /// the page was parsed and had what we looked for
+    Matched,
+    /// the page was parsed but had nothing for us, like a search without results
+    NoMatch,
+    /// the page is not structured as expected, the parser probably needs fixing
+    Broken,
+    /// the page could not be fetched
+    Failed,
+}
+
+impl ScrapeOutcome {
+    pub fn of_error(err: &ScrapeError) -> Self {
+        match err {
+            ScrapeError::SelectorMiss { .. } | ScrapeError::Parse { .. } => ScrapeOutcome::Broken,
+            ScrapeError::NotFound { .. } => ScrapeOutcome::NoMatch,
+            _ => ScrapeOutcome::Failed,
+        }
+    }
+
+    /// The outcome of the scrape of a page that should have what we look for
+    pub fn of<T>(result: &Result<T, ScrapeError>) -> Self {
+        match result {
+            Ok(_) => ScrapeOutcome::Matched,
+            Err(err) => Self::of_error(err),
+        }
+    }
+
+    /// The outcome of a search, finding nothing when the website has no match
+    pub fn of_search<T>(result: &Result<Option<T>, ScrapeError>) -> Self {
+        match result {
+            Ok(Some(_)) => ScrapeOutcome::Matched,
+            Ok(None) => ScrapeOutcome::NoMatch,
+            Err(err) => Self::of_error(err),
+        }
+    }
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            ScrapeOutcome::Matched => "matched",
+            ScrapeOutcome::NoMatch => "no_match",
+            ScrapeOutcome::Broken => "broken",
+            ScrapeOutcome::Failed => "failed",
+        }
+    }
+}
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Serialize)]
+pub struct OutcomeCounts {
+    pub matched: u64,
+    pub no_match: u64,
+    pub broken: u64,
+    pub failed: u64,
+}
+
+impl OutcomeCounts {
+    fn add(&mut self, outcome: ScrapeOutcome) {
+        match outcome {
+            ScrapeOutcome::Matched => self.matched += 1,
+            ScrapeOutcome::NoMatch => self.no_match += 1,
+            ScrapeOutcome::Broken => self.broken += 1,
+            ScrapeOutcome::Failed => self.failed += 1,
+        }
+    }
+
+    fn merge(&mut self, other: &OutcomeCounts) {
+        self.matched += other.matched;
+        self.no_match += other.no_match;
+        self.broken += other.broken;
+        self.failed += other.failed;
+    }
+
+    pub fn total(&self) -> u64 {
+        self.matched + self.no_match + self.broken + self.failed
+    }
+
+    /// Share of the scrapes that found what we looked for
+    pub fn success_rate(&self) -> Option<f64> {
+        (self.total() > 0).then(|| self.matched as f64 / self.total() as f64)
+    }
+
+    /// Share of the scrapes that found a page not structured as expected
+    pub fn broken_rate(&self) -> Option<f64> {
+        (self.total() > 0).then(|| self.broken as f64 / self.total() as f64)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum HealthStatus {
+    Healthy,
+    /// too few recent scrapes to tell
+    Unknown,
+    /// too many recent pages are broken
+    Broken,
+    /// the recent success rate collapsed compared to the older one
+    Collapsed,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct HealthBucket {
+    pub start: DateTime<Utc>,
+    pub counts: OutcomeCounts,
+}
+
+/// The health of a source, from its recent scrapes compared to the older ones
+#[derive(Debug, Clone, Serialize)]
+pub struct SourceHealth {
+    pub source: String,
+    pub status: HealthStatus,
+    pub recent: OutcomeCounts,
+    pub older: OutcomeCounts,
+    pub recent_success_rate: Option<f64>,
+    pub older_success_rate: Option<f64>,
+    pub recent_broken_rate: Option<f64>,
+    /// most recent first
+    pub buckets: Vec<HealthBucket>,
+}
+
+impl SourceHealth {
+    fn new(source: &str, buckets: &VecDeque<(i64, OutcomeCounts)>, now: i64) -> Self {
+        let current = now / BUCKET_SECONDS;
+        // a source not scraped for a while still has its old buckets
+        let buckets = buckets
+            .iter()
+            .filter(|(bucket, _)| current - bucket < HISTORY_BUCKETS as i64)
+            .collect::<Vec<_>>();
+        let mut recent = OutcomeCounts::default();
+        let mut older = OutcomeCounts::default();
+        for (bucket, counts) in &buckets {
+            if current - bucket < RECENT_BUCKETS as i64 {
+                recent.merge(counts);
+            } else {
+                older.merge(counts);
+            }
+        }
+
+        let status = if recent.total() < MIN_SAMPLES {
+            HealthStatus::Unknown
+        } else if recent.broken_rate().unwrap_or_default() >= MAX_BROKEN_RATE {
+            HealthStatus::Broken
+        } else if older.total() >= MIN_SAMPLES
+            && recent.success_rate().unwrap_or_default()
+                < older.success_rate().unwrap_or_default() * COLLAPSE_RATIO
+        {
+            HealthStatus::Collapsed
+        } else {
+            HealthStatus::Healthy
+        };
+
+        Self {
+            source: source.to_string(),
+            status,
+            recent,
+            older,
+            recent_success_rate: recent.success_rate(),
+            older_success_rate: older.success_rate(),
+            recent_broken_rate: recent.broken_rate(),
+            buckets: buckets
+                .into_iter()
+                .rev()
+                .map(|(bucket, counts)| HealthBucket {
+                    start: DateTime::from_timestamp(bucket * BUCKET_SECONDS, 0).unwrap_or_default(),
+                    counts: *counts,
+                })
+                .collect(),
+        }
+    }
+
+    pub fn is_unhealthy(&self) -> bool {
+        matches!(self.status, HealthStatus::Broken | HealthStatus::Collapsed)
+    }
+}
+
+/// Outcomes of the scrapes of each source (website) by hour, over the last day
+#[derive(Default)]
+pub struct ScraperHealth {
+    sources: Mutex<BTreeMap<String, VecDeque<(i64, OutcomeCounts)>>>,
+}
+
+impl ScraperHealth {
+    /// Count an outcome at a unix timestamp, returning the health of the source
+    pub fn record_at(&self, source: &str, outcome: ScrapeOutcome, now: i64) -> SourceHealth {
+        let bucket = now / BUCKET_SECONDS;
+        let mut sources = self.sources.lock().unwrap();
+        let buckets = sources.entry(source.to_string()).or_default();
+
+        // the buckets are sorted, a thread may record an outcome a bit late
+        match buckets.binary_search_by_key(&bucket, |(start, _)| *start) {
+            Ok(index) => buckets[index].1.add(outcome),
+            Err(index) => {
+                let mut counts = OutcomeCounts::default();
+                counts.add(outcome);
+                buckets.insert(index, (bucket, counts));
+            }
+        }
+        let last = buckets.back().map(|(last, _)| *last).unwrap_or(bucket);
+        while buckets
+            .front()
+            .is_some_and(|(first, _)| last - first >= HISTORY_BUCKETS as i64)
+        {
+            buckets.pop_front();
+        }
+
+        SourceHealth::new(source, buckets, now)
+    }
+
+    pub fn report_at(&self, now: i64) -> Vec<SourceHealth> {
+        let sources = self.sources.lock().unwrap();
+        sources
+            .iter()
+            .map(|(source, buckets)| SourceHealth::new(source, buckets, now))
+            .collect()
+    }
+}
+
+/// Count the outcome of a scrape of a source, updating its health metrics
+pub fn record(source: &str, outcome: ScrapeOutcome) {
+    SCRAPER_OUTCOMES
+        .with_label_values(&[source, outcome.label()])
+        .inc();
+
+    let health = HEALTH.record_at(source, outcome, Utc::now().timestamp());
+    if let Some(rate) = health.recent_success_rate {
+        SCRAPER_SUCCESS_RATE.with_label_values(&[source]).set(rate);
+    }
+    SCRAPER_HEALTHY
+        .with_label_values(&[source])
+        .set(!health.is_unhealthy() as i64);
+    if health.is_unhealthy() {
+        log::warn!("scraper {} is unhealthy : {:?}", source, health.status);
+    }
+}
+
+/// The health of every source scraped by this process
+pub fn report() -> Vec<SourceHealth> {
+    HEALTH.report_at(Utc::now().timestamp())
+}
+
+lazy_static! {
+    static ref SCRAPER_OUTCOMES: IntCounterVec = register_int_counter_vec!(
+        "scraper_outcomes",
+        "Number of scrapes by source and outcome (matched, no_match, broken, failed)",
+        &["source", "outcome"]
+    )
+    .unwrap();
+    static ref SCRAPER_SUCCESS_RATE: GaugeVec = register_gauge_vec!(
+        "scraper_success_rate",
+        "Share of the scrapes of the last two hours that found what was looked for",
+        &["source"]
+    )
+    .unwrap();
+    static ref SCRAPER_HEALTHY: IntGaugeVec = register_int_gauge_vec!(
+        "scraper_healthy",
+        "0 when too many pages of a source are broken or its success rate collapsed",
+        &["source"]
+    )
+    .unwrap();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{HealthStatus, ScrapeOutcome, ScraperHealth, BUCKET_SECONDS, HISTORY_BUCKETS};
+    use crate::error::ScrapeError;
+
+    const NOW: i64 = 1_760_000_000;
+
+    fn record(health: &ScraperHealth, outcome: ScrapeOutcome, n: usize, hours_ago: i64) {
+        for _ in 0..n {
+            health.record_at("ludifolie", outcome, NOW - hours_ago * BUCKET_SECONDS);
+        }
+    }
+
+    #[test]
+    fn test_outcome() {
+        assert_eq!(
+            ScrapeOutcome::of_search::<f32>(&Ok(Some(42.5))),
+            ScrapeOutcome::Matched
+        );
+        assert_eq!(
+            ScrapeOutcome::of_search::<f32>(&Ok(None)),
+            ScrapeOutcome::NoMatch
+        );
+        assert_eq!(
+            ScrapeOutcome::of_search::<f32>(&Err(ScrapeError::selector_miss(
+                "ludifolie",
+                ".products"
+            ))),
+            ScrapeOutcome::Broken
+        );
+        assert_eq!(
+            ScrapeOutcome::of_error(&ScrapeError::RateLimited {
+                host: "www.ludifolie.com".to_string()
+            }),
+            ScrapeOutcome::Failed
+        );
+    }
+
+    #[test]
+    fn test_healthy() {
+        let health = ScraperHealth::default();
+        record(&health, ScrapeOutcome::Matched, 5, 0);
+        assert_eq!(health.report_at(NOW)[0].status, HealthStatus::Unknown);
+
+        record(&health, ScrapeOutcome::NoMatch, 5, 1);
+        record(&health, ScrapeOutcome::Failed, 2, 0);
+        let report = health.report_at(NOW);
+        assert_eq!(report[0].status, HealthStatus::Healthy);
+        assert_eq!(report[0].recent.total(), 12);
+        assert_eq!(report[0].buckets.len(), 2);
+        assert_eq!(report[0].buckets[0].counts.matched, 5);
+    }
+
+    #[test]
+    fn test_broken() {
+        let health = ScraperHealth::default();
+        record(&health, ScrapeOutcome::Matched, 4, 0);
+        record(&health, ScrapeOutcome::Broken, 6, 0);
+        assert_eq!(health.report_at(NOW)[0].status, HealthStatus::Broken);
+    }
+
+    #[test]
+    fn test_collapsed() {
+        let health = ScraperHealth::default();
+        record(&health, ScrapeOutcome::Matched, 16, 5);
+        record(&health, ScrapeOutcome::NoMatch, 4, 5);
+        record(&health, ScrapeOutcome::Matched, 2, 0);
+        record(&health, ScrapeOutcome::NoMatch, 8, 0);
+        let report = health.report_at(NOW);
+        assert_eq!(report[0].status, HealthStatus::Collapsed);
+        assert!(report[0].is_unhealthy());
+
+        // the old buckets are forgotten after a day
+        let later = NOW + HISTORY_BUCKETS as i64 * BUCKET_SECONDS;
+        let source = health.record_at("ludifolie", ScrapeOutcome::Matched, later);
+        assert_eq!(source.buckets.len(), 1);
+        assert_eq!(source.status, HealthStatus::Unknown);
+        assert_eq!(
+            health.report_at(later + BUCKET_SECONDS * 30)[0]
+                .buckets
+                .len(),
+            0
+        );
+    }
+}
@@ -0,0 +1,371 @@
+use std::collections::hash_map::DefaultHasher;
+use std::collections::HashMap;
+use std::hash::{Hash, Hasher};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::{Duration, SystemTime};
+
+use bytes::Bytes;
+use chrono::{DateTime, Utc};
+use lazy_static::lazy_static;
+use prometheus::{register_int_counter_vec, IntCounterVec};
+use reqwest::header::{HeaderMap, CACHE_CONTROL, CONTENT_TYPE, ETAG, LAST_MODIFIED};
+use reqwest::{ResponseBuilderExt, StatusCode, Url};
+use serde::{Deserialize, Serialize};
+
+use crate::httpclient::env_or;
+
+/// TTLs used when HTTP_CACHE_TTL is not set: an hour for the shops and BGG, while the
+/// okkazeo pages are always revalidated
+const DEFAULT_TTLS: &str = "*=3600,www.okkazeo.com=0";
+/// Files untouched for longer than HTTP_CACHE_MAX_AGE seconds are removed, a week by default
+const DEFAULT_MAX_AGE: u64 = 7 * 24 * 3600;
+/// The old files are swept on the first write, then every SWEEP_EVERY writes
+const SWEEP_EVERY: usize = 1000;
+
+/// How long a cached response can be served without asking the server, by host
+#[derive(Debug, Clone, PartialEq)]
+pub struct HostTtls {
+    default: Duration,
+    hosts: HashMap<String, Duration>,
+}
+
+impl HostTtls {
+    /// Parse a list of `host=seconds`, `*` being the TTL of the hosts not listed
+    pub fn parse(value: &str) -> Result<Self, String> {
+        let mut ttls = HostTtls {
+            default: Duration::ZERO,
+            hosts: HashMap::new(),
+        };
+        for item in value.split(',').map(str::trim).filter(|v| !v.is_empty()) {
+            let (host, seconds) = item
+                .split_once('=')
+                .ok_or_else(|| format!("missing '=' in {}", item))?;
+            let ttl = Duration::from_secs(
+                seconds
+                    .trim()
+                    .parse::<u64>()
+                    .map_err(|err| format!("invalid TTL for {}: {}", host, err))?,
+            );
+            match host.trim() {
+                "*" => ttls.default = ttl,
+                host => {
+                    ttls.hosts.insert(host.to_string(), ttl);
+                }
+            }
+        }
+        Ok(ttls)
+    }
+
+    pub fn ttl(&self, host: &str) -> Duration {
+        self.hosts.get(host).copied().unwrap_or(self.default)
+    }
+}
+
+/// What is known of a cached response, the body being stored next to it
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CacheEntry {
+    pub url: String,
+    pub stored_at: DateTime<Utc>,
+    pub content_type: Option<String>,
+    pub etag: Option<String>,
+    pub last_modified: Option<String>,
+}
+
+impl CacheEntry {
+    /// Rebuild the response served by the cache
+    pub fn to_response(&self, url: Url, body: Bytes) -> reqwest::Response {
+        let mut builder = hyper::Response::builder().status(StatusCode::OK).url(url);
+        if let Some(content_type) = &self.content_type {
+            builder = builder.header(CONTENT_TYPE, content_type);
+        }
+        builder.body(body).expect("cached response is valid").into()
+    }
+}
+
+/// Responses cached on disk by URL, one json file for the entry and one for the body.
+/// Only the successful responses are stored, and an expired entry is revalidated with
+/// its ETag/Last-Modified rather than being downloaded again.
+/// The entries not stored nor revalidated for max_age are evicted
+pub struct HttpCache {
+    dir: PathBuf,
+    ttls: HostTtls,
+    max_age: Duration,
+    writes: AtomicUsize,
+}
+
+impl HttpCache {
+    pub fn new(dir: impl Into<PathBuf>, ttls: HostTtls, max_age: Duration) -> Self {
+        Self {
+            dir: dir.into(),
+            ttls,
+            max_age,
+            writes: AtomicUsize::new(0),
+        }
+    }
+
+    /// The cache in HTTP_CACHE_DIR (default `http_cache`, empty to disable it) with the
+    /// TTLs of HTTP_CACHE_TTL and the max age of HTTP_CACHE_MAX_AGE
+    pub fn from_env() -> Option<Self> {
+        let dir = std::env::var("HTTP_CACHE_DIR").unwrap_or("http_cache".to_string());
+        if dir.is_empty() {
+            log::info!("http cache disabled");
+            return None;
+        }
+
+        let ttls = std::env::var("HTTP_CACHE_TTL")
+            .map_err(|v| v.to_string())
+            .and_then(|v| HostTtls::parse(&v))
+            .unwrap_or_else(|err| {
+                log::warn!(
+                    "Cannot initialize http cache TTLs from environment, fallback to default: {}",
+                    err
+                );
+                HostTtls::parse(DEFAULT_TTLS).unwrap()
+            });
+
+        let max_age = Duration::from_secs(env_or("HTTP_CACHE_MAX_AGE", DEFAULT_MAX_AGE));
+
+        log::debug!("Creating http cache in {} with TTLs {:?}", dir, ttls);
+        Some(Self::new(dir, ttls, max_age))
+    }
+
+    fn paths(&self, url: &Url) -> (PathBuf, PathBuf) {
+        let mut hasher = DefaultHasher::new();
+        url.as_str().hash(&mut hasher);
+        let name = format!("{:016x}", hasher.finish());
+        (
+            self.dir.join(format!("{}.json", name)),
+            self.dir.join(format!("{}.body", name)),
+        )
+    }
+
+    /// The cached entry and body of a URL, fresh or not
+    pub async fn lookup(&self, url: &Url) -> Option<(CacheEntry, Bytes)> {
+        let (entry_path, body_path) = self.paths(url);
+        let entry = tokio::fs::read(&entry_path).await.ok()?;
+        let entry = serde_json::from_slice::<CacheEntry>(&entry).ok()?;
+        // two urls can share the same hash
+        if entry.url != url.as_str() {
+            return None;
+        }
+        let body = tokio::fs::read(&body_path).await.ok()?;
+        Some((entry, Bytes::from(body)))
+    }
+
+    pub fn is_fresh(&self, entry: &CacheEntry, url: &Url) -> bool {
+        let ttl = self.ttls.ttl(url.host_str().unwrap_or_default());
+        let age = (Utc::now() - entry.stored_at).to_std().unwrap_or_default();
+        age < ttl
+    }
+
+    /// Cache a successful response, unless the server forbids it
+    pub async fn store(&self, url: &Url, headers: &HeaderMap, body: &Bytes) {
+        let header = |name| {
+            headers
+                .get(name)
+                .and_then(|v| v.to_str().ok())
+                .map(str::to_string)
+        };
+        if header(CACHE_CONTROL).is_some_and(|v| v.contains("no-store")) {
+            return;
+        }
+
+        let entry = CacheEntry {
+            url: url.to_string(),
+            stored_at: Utc::now(),
+            content_type: header(CONTENT_TYPE),
+            etag: header(ETAG),
+            last_modified: header(LAST_MODIFIED),
+        };
+        let (entry_path, body_path) = self.paths(url);
+        if let Err(err) = self.write(&body_path, body).await {
+            log::warn!("cannot cache the body of {}: {}", url, err);
+            return;
+        }
+        self.write_entry(&entry_path, &entry).await;
+
+        if self
+            .writes
+            .fetch_add(1, Ordering::Relaxed)
+            .is_multiple_of(SWEEP_EVERY)
+        {
+            match self.sweep().await {
+                Ok(removed) => log::debug!("{} old files removed from the http cache", removed),
+                Err(err) => log::warn!("cannot sweep the http cache: {}", err),
+            }
+        }
+    }
+
+    /// Remove the files older than max_age, a body being kept as long as its entry is
+    /// recent since a revalidation only rewrites the entry. Returns the number of files removed
+    pub async fn sweep(&self) -> Result<usize, std::io::Error> {
+        let is_old = |modified: SystemTime| modified.elapsed().unwrap_or_default() >= self.max_age;
+        let mut removed = 0;
+        let mut files = tokio::fs::read_dir(&self.dir).await?;
+        while let Some(file) = files.next_entry().await? {
+            let path = file.path();
+            if !is_old(file.metadata().await?.modified()?) {
+                continue;
+            }
+            if path.extension().is_some_and(|ext| ext == "body") {
+                let entry_modified = tokio::fs::metadata(path.with_extension("json"))
+                    .await
+                    .and_then(|metadata| metadata.modified());
+                if entry_modified.is_ok_and(|modified| !is_old(modified)) {
+                    continue;
+                }
+            }
+            match tokio::fs::remove_file(&path).await {
+                Ok(()) => removed += 1,
+                // removed by a concurrent sweep
+                Err(err) if err.kind() == std::io::ErrorKind::NotFound => {}
+                Err(err) => return Err(err),
+            }
+        }
+        Ok(removed)
+    }
+
+    /// Restart the TTL of an entry the server confirmed as still valid
+    pub async fn touch(&self, url: &Url, mut entry: CacheEntry) {
+        entry.stored_at = Utc::now();
+        let (entry_path, _) = self.paths(url);
+        self.write_entry(&entry_path, &entry).await;
+    }
+
+    async fn write_entry(&self, path: &Path, entry: &CacheEntry) {
+        let content = serde_json::to_vec(entry).expect("cache entry is serializable");
+        if let Err(err) = self.write(path, &content).await {
+            log::warn!("cannot cache the entry of {}: {}", entry.url, err);
+        }
+    }
+
+    /// Write through a temporary file, so that a concurrent lookup never reads half a file
+    async fn write(&self, path: &Path, content: &[u8]) -> Result<(), std::io::Error> {
+        tokio::fs::create_dir_all(&self.dir).await?;
+        let tmp_path = path.with_extension(format!("tmp{}", rand::random::<u32>()));
+        tokio::fs::write(&tmp_path, content).await?;
+        tokio::fs::rename(&tmp_path, path).await
+    }
+}
+
+lazy_static! {
+    pub(crate) static ref HTTP_CACHE: IntCounterVec = register_int_counter_vec!(
+        "http_cache",
+        "Number of http requests by cache result (hit, revalidated, miss, bypass)",
+        &["result"]
+    )
+    .unwrap();
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use bytes::Bytes;
+    use chrono::Utc;
+    use reqwest::header::{HeaderMap, HeaderValue, CACHE_CONTROL, CONTENT_TYPE, ETAG};
+    use reqwest::Url;
+
+    use super::{HostTtls, HttpCache};
+
+    fn cache_with_max_age(name: &str, ttls: &str, max_age: Duration) -> HttpCache {
+        let dir = std::env::temp_dir().join(format!("httpcache-{}-{}", name, std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        HttpCache::new(dir, HostTtls::parse(ttls).unwrap(), max_age)
+    }
+
+    fn cache(name: &str, ttls: &str) -> HttpCache {
+        cache_with_max_age(name, ttls, Duration::from_secs(3600))
+    }
+
+    #[test]
+    fn test_parse_ttls() {
+        let ttls = HostTtls::parse("*=3600, boardgamegeek.com=86400,www.okkazeo.com=0").unwrap();
+        assert_eq!(ttls.ttl("boardgamegeek.com"), Duration::from_secs(86400));
+        assert_eq!(ttls.ttl("www.okkazeo.com"), Duration::ZERO);
+        assert_eq!(ttls.ttl("www.philibert.net"), Duration::from_secs(3600));
+
+        assert_eq!(HostTtls::parse("").unwrap().ttl("any"), Duration::ZERO);
+        assert!(HostTtls::parse("boardgamegeek.com").is_err());
+        assert!(HostTtls::parse("*=an hour").is_err());
+    }
+
+    #[tokio::test]
+    async fn test_store_and_lookup() {
+        let cache = cache("store", "*=3600,www.okkazeo.com=0");
+        let url = Url::parse("https://www.philibert.net/fr/recherche?s=azul").unwrap();
+        assert!(cache.lookup(&url).await.is_none());
+
+        let mut headers = HeaderMap::new();
+        headers.insert(CONTENT_TYPE, HeaderValue::from_static("text/html"));
+        headers.insert(ETAG, HeaderValue::from_static("\"abc\""));
+        cache.store(&url, &headers, &Bytes::from("<html>")).await;
+
+        let (entry, body) = cache.lookup(&url).await.unwrap();
+        assert_eq!(body, Bytes::from("<html>"));
+        assert_eq!(entry.etag.as_deref(), Some("\"abc\""));
+        assert!(cache.is_fresh(&entry, &url));
+
+        let response = entry.to_response(url.clone(), body);
+        assert_eq!(response.url(), &url);
+        assert_eq!(response.text().await.unwrap(), "<html>");
+
+        let other = Url::parse("https://www.philibert.net/fr/recherche?s=skaal").unwrap();
+        assert!(cache.lookup(&other).await.is_none());
+    }
+
+    #[tokio::test]
+    async fn test_freshness() {
+        let cache = cache("freshness", "*=3600,www.okkazeo.com=0");
+        let url = Url::parse("https://www.okkazeo.com/annonces/view/1").unwrap();
+        cache
+            .store(&url, &HeaderMap::new(), &Bytes::from("<html>"))
+            .await;
+
+        let (mut entry, _) = cache.lookup(&url).await.unwrap();
+        assert!(!cache.is_fresh(&entry, &url));
+
+        let shop = Url::parse("https://www.philibert.net/fr/recherche?s=azul").unwrap();
+        entry.stored_at = Utc::now() - chrono::Duration::hours(2);
+        assert!(!cache.is_fresh(&entry, &shop));
+        cache.touch(&url, entry).await;
+        let (entry, _) = cache.lookup(&url).await.unwrap();
+        assert!(cache.is_fresh(&entry, &shop));
+    }
+
+    #[tokio::test]
+    async fn test_no_store() {
+        let cache = cache("no_store", "*=3600");
+        let url = Url::parse("https://boardgamegeek.com/xmlapi2/search?query=azul").unwrap();
+        let mut headers = HeaderMap::new();
+        headers.insert(CACHE_CONTROL, HeaderValue::from_static("private, no-store"));
+        cache.store(&url, &headers, &Bytes::from("<items/>")).await;
+
+        assert!(cache.lookup(&url).await.is_none());
+    }
+
+    #[tokio::test]
+    async fn test_sweep() {
+        let url = Url::parse("https://www.philibert.net/fr/recherche?s=azul").unwrap();
+
+        let cache = cache("sweep", "*=3600");
+        cache
+            .store(&url, &HeaderMap::new(), &Bytes::from("<html>"))
+            .await;
+        assert_eq!(cache.sweep().await.unwrap(), 0);
+        assert!(cache.lookup(&url).await.is_some());
+
+        // every file is too old without a max age
+        let cache = cache_with_max_age("sweep_all", "*=3600", Duration::ZERO);
+        cache
+            .store(&url, &HeaderMap::new(), &Bytes::from("<html>"))
+            .await;
+        assert!(cache.lookup(&url).await.is_none());
+        cache
+            .store(&url, &HeaderMap::new(), &Bytes::from("<html>"))
+            .await;
+        assert_eq!(cache.sweep().await.unwrap(), 2);
+        assert!(cache.lookup(&url).await.is_none());
+    }
+}
@@ -1,67 +1,684 @@
-use governor::state::keyed::DefaultKeyedStateStore;
-use governor::{clock, DefaultKeyedRateLimiter, Quota, RateLimiter};
+use bytes::Bytes;
+use chrono::{DateTime, Utc};
 use hyper::StatusCode;
-use nonzero_ext::nonzero;
-use reqwest::{Client, ClientBuilder, IntoUrl, Response};
+use lazy_static::lazy_static;
+use prometheus::{register_int_counter_vec, IntCounterVec};
+use reqwest::header::{
+    HeaderMap, HeaderValue, CONTENT_TYPE, IF_MODIFIED_SINCE, IF_NONE_MATCH, LOCATION, RETRY_AFTER,
+};
+use reqwest::{Client, ClientBuilder, IntoUrl, Proxy, Response, ResponseBuilderExt, Url};
 use scraper::Html;
-use std::num::NonZeroU32;
+use std::collections::HashMap;
+use std::fmt;
+use std::path::PathBuf;
+use std::str::FromStr;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
 
-/// DEFAULT_QUOTA is the default requests per minutes if not specified
-const DEFAULT_QUOTA: Quota = Quota::per_minute(nonzero!(30u32));
+use crate::httpcache::{CacheEntry, HttpCache, HTTP_CACHE};
+use crate::httpfixtures::{FixtureMode, HttpFixtures};
+use crate::ratelimit::{HostLimiters, RateLimitConfig};
+use crate::robots::{RobotsCache, RobotsTxt, ROBOTS_ERROR_TTL, ROBOTS_TTL};
 
-lazy_static::lazy_static! {
+/// delay before the first retry, doubled on each attempt
+const BASE_BACKOFF: Duration = Duration::from_secs(1);
+/// longest wait between two attempts, whatever the backoff or Retry-After
+const MAX_BACKOFF: Duration = Duration::from_secs(120);
+
+/// how we introduce ourselves to the websites, with a way to reach us
+const DEFAULT_USER_AGENT: &str = concat!(
+    "Aubonmeeple/",
+    env!("CARGO_PKG_VERSION"),
+    " (+https://aubonmeeple.fr)"
+);
+
+lazy_static! {
+    static ref CONFIG: ClientConfig = ClientConfig::from_env();
+    static ref POLICY: RetryPolicy = RetryPolicy::from_env();
     static ref CLIENT: Client = create_client();
-    static ref LIMITER_CLOCK: clock::DefaultClock = clock::DefaultClock::default();
-    static ref LIMITER: DefaultKeyedRateLimiter<String> = create_limiter();
+    static ref LIMITERS: HostLimiters = HostLimiters::new(RateLimitConfig::from_env());
+    static ref CACHE: Option<HttpCache> = HttpCache::from_env();
+    static ref FIXTURES: Option<HttpFixtures> = HttpFixtures::from_env();
+    static ref BREAKER: CircuitBreaker =
+        CircuitBreaker::new(POLICY.breaker_threshold, POLICY.breaker_cooldown);
+    static ref ROBOTS: RobotsCache = RobotsCache::default();
 }
 
-fn create_client() -> Client {
-    ClientBuilder::new()
-        .redirect(reqwest::redirect::Policy::none())
-        .build()
-        .expect("Failed to build reqwest::Client")
-}
-
-fn create_limiter() -> DefaultKeyedRateLimiter<String> {
-    let quota = std::env::var("RATELIMIT_PER_MINUTE")
-        .map_err(|v| v.to_string())
-        .and_then(|v| v.parse::<u32>().map_err(|v| v.to_string()))
-        .and_then(|v| {
-            NonZeroU32::new(v).ok_or_else(|| "Cannot convert to non-zero u32".to_string())
-        })
-        .map(Quota::per_minute)
-        .unwrap_or_else(|err| {
+/// Why a request failed, after its retries
+#[derive(Debug)]
+pub enum HttpError {
+    /// the request could not be sent or its response could not be read
+    Request(reqwest::Error),
+    /// the server kept answering with a 429 or a 5xx
+    Status { url: Url, status: StatusCode },
+    /// the host failed too many times in a row and is paused for a while
+    CircuitOpen { host: String },
+    /// replaying the http fixtures, but none was recorded for this url
+    MissingFixture { url: Url, path: PathBuf },
+    /// the robots.txt of the host forbids this url
+    Disallowed { url: Url },
+}
+
+impl fmt::Display for HttpError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            HttpError::Request(err) => write!(f, "{}", err),
+            HttpError::Status { url, status } => write!(f, "{} answered {}", url, status),
+            HttpError::CircuitOpen { host } => {
+                write!(f, "{} is paused after repeated failures", host)
+            }
+            HttpError::MissingFixture { url, path } => {
+                write!(f, "no fixture recorded for {} in {:?}", url, path)
+            }
+            HttpError::Disallowed { url } => write!(f, "{} is disallowed by robots.txt", url),
+        }
+    }
+}
+
+impl std::error::Error for HttpError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            HttpError::Request(err) => Some(err),
+            _ => None,
+        }
+    }
+}
+
+impl From<reqwest::Error> for HttpError {
+    fn from(err: reqwest::Error) -> Self {
+        HttpError::Request(err)
+    }
+}
+
+/// Proxies to go through by host, `*` being the proxy of the hosts not listed
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct HostProxies {
+    default: Option<Url>,
+    hosts: HashMap<String, Url>,
+}
+
+impl HostProxies {
+    /// Parse a list of `host=proxy url`, the proxies being http, https or socks5
+    pub fn parse(value: &str) -> Result<Self, String> {
+        let mut proxies = HostProxies::default();
+        for item in value.split(',').map(str::trim).filter(|v| !v.is_empty()) {
+            let (host, proxy) = item
+                .split_once('=')
+                .ok_or_else(|| format!("missing '=' in {}", item))?;
+            let proxy = Url::parse(proxy.trim())
+                .map_err(|err| format!("invalid proxy for {}: {}", host, err))?;
+            if !["http", "https", "socks5", "socks5h"].contains(&proxy.scheme()) {
+                return Err(format!("unsupported proxy scheme {}", proxy.scheme()));
+            }
+            match host.trim() {
+                "*" => proxies.default = Some(proxy),
+                host => {
+                    proxies.hosts.insert(host.to_string(), proxy);
+                }
+            }
+        }
+        Ok(proxies)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.default.is_none() && self.hosts.is_empty()
+    }
+
+    pub fn proxy(&self, host: &str) -> Option<&Url> {
+        self.hosts.get(host).or(self.default.as_ref())
+    }
+}
+
+/// How the shared client introduces itself and reaches the websites
+struct ClientConfig {
+    user_agent: String,
+    connect_timeout: Duration,
+    timeout: Duration,
+    proxies: HostProxies,
+    respect_robots: bool,
+}
+
+impl ClientConfig {
+    /// Read HTTP_USER_AGENT, HTTP_CONNECT_TIMEOUT (seconds, default 10), HTTP_TIMEOUT
+    /// (seconds to get the whole response, default 30), HTTP_PROXIES and HTTP_ROBOTS_TXT
+    /// (`ignore` to query the paths disallowed by the robots.txt of the websites)
+    fn from_env() -> Self {
+        let proxies = std::env::var("HTTP_PROXIES")
+            .ok()
+            .map(|v| {
+                HostProxies::parse(&v).unwrap_or_else(|err| {
+                    log::warn!("Cannot initialize proxies from environment: {}", err);
+                    HostProxies::default()
+                })
+            })
+            .unwrap_or_default();
+
+        Self {
+            user_agent: std::env::var("HTTP_USER_AGENT")
+                .ok()
+                .filter(|v| !v.is_empty())
+                .unwrap_or(DEFAULT_USER_AGENT.to_string()),
+            connect_timeout: Duration::from_secs(env_or("HTTP_CONNECT_TIMEOUT", 10)),
+            timeout: Duration::from_secs(env_or("HTTP_TIMEOUT", 30)),
+            proxies,
+            respect_robots: std::env::var("HTTP_ROBOTS_TXT").as_deref() != Ok("ignore"),
+        }
+    }
+
+    /// The name robots.txt groups refer to us with
+    fn product_token(&self) -> &str {
+        self.user_agent.split(['/', ' ']).next().unwrap_or_default()
+    }
+}
+
+/// How the failing requests are retried and the failing hosts paused
+struct RetryPolicy {
+    max_attempts: u32,
+    breaker_threshold: u32,
+    breaker_cooldown: Duration,
+}
+
+impl RetryPolicy {
+    /// Read HTTP_MAX_ATTEMPTS (default 3), HTTP_BREAKER_THRESHOLD (failed requests in a row, default 5) and
+    /// HTTP_BREAKER_COOLDOWN (seconds, default 300)
+    fn from_env() -> Self {
+        Self {
+            max_attempts: env_or("HTTP_MAX_ATTEMPTS", 3u32).max(1),
+            breaker_threshold: env_or("HTTP_BREAKER_THRESHOLD", 5u32).max(1),
+            breaker_cooldown: Duration::from_secs(env_or("HTTP_BREAKER_COOLDOWN", 300)),
+        }
+    }
+}
+
+/// The value of an environment variable, the default when it is not set or invalid
+pub fn env_or<T: FromStr>(name: &str, default: T) -> T {
+    match std::env::var(name).map(|v| v.parse::<T>()) {
+        Ok(Ok(v)) => v,
+        Ok(Err(_)) => {
+            log::warn!(
+                "Cannot parse {} from environment, fallback to default",
+                name
+            );
+            default
+        }
+        Err(_) => default,
+    }
+}
+
+#[derive(Debug, Default)]
+struct HostHealth {
+    consecutive_failures: u32,
+    open_until: Option<Instant>,
+}
+
+/// Pause the hosts whose requests keep failing, so that a shop being down does not slow
+/// down every announce. Once the cooldown is over the host is tried again, a single
+/// failure pausing it again until a request succeeds
+pub struct CircuitBreaker {
+    threshold: u32,
+    cooldown: Duration,
+    hosts: Mutex<HashMap<String, HostHealth>>,
+}
+
+impl CircuitBreaker {
+    pub fn new(threshold: u32, cooldown: Duration) -> Self {
+        Self {
+            threshold,
+            cooldown,
+            hosts: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Whether a request can be sent to the host
+    pub fn allow(&self, host: &str) -> bool {
+        let hosts = self.hosts.lock().unwrap();
+        match hosts.get(host).and_then(|health| health.open_until) {
+            Some(open_until) => Instant::now() >= open_until,
+            None => true,
+        }
+    }
+
+    pub fn record_success(&self, host: &str) {
+        self.hosts.lock().unwrap().remove(host);
+    }
+
+    pub fn record_failure(&self, host: &str) {
+        let mut hosts = self.hosts.lock().unwrap();
+        let health = hosts.entry(host.to_string()).or_default();
+        health.consecutive_failures += 1;
+        if health.consecutive_failures >= self.threshold {
             log::warn!(
-                "Cannot initialize quota from environment, fallback to default: {}",
-                err
+                "pausing {} for {:?} after {} failed requests",
+                host,
+                self.cooldown,
+                health.consecutive_failures
             );
-            DEFAULT_QUOTA
-        })
-        .allow_burst(nonzero!(1u32));
+            health.open_until = Some(Instant::now() + self.cooldown);
+        }
+    }
+}
+
+fn create_client() -> Client {
+    let mut builder = ClientBuilder::new()
+        .redirect(reqwest::redirect::Policy::none())
+        .user_agent(&CONFIG.user_agent)
+        .connect_timeout(CONFIG.connect_timeout)
+        .timeout(CONFIG.timeout);
+    if !CONFIG.proxies.is_empty() {
+        let proxies = CONFIG.proxies.clone();
+        builder = builder.proxy(Proxy::custom(move |url| {
+            proxies.proxy(url.host_str().unwrap_or_default()).cloned()
+        }));
+    }
 
-    log::debug!("Creating rate limiter with quota {:?}", quota);
-    let state = DefaultKeyedStateStore::default();
-    RateLimiter::new(quota, state, &LIMITER_CLOCK)
+    builder.build().expect("Failed to build reqwest::Client")
 }
 
-/// Execute a request using the shared http client
-pub async fn get<U: IntoUrl>(url: U) -> Result<Response, reqwest::Error> {
-    let url_r = url.into_url()?;
+/// Whether the robots.txt of the host allows to fetch the url. The robots.txt is kept
+/// for a day, and everything is disallowed for a while when the server fails to serve it.
+/// Nothing is cached when it could not be asked at all (circuit open, network error), the
+/// url being disallowed until the next try
+async fn robots_allow(url: &Url) -> bool {
+    let origin = url.origin().ascii_serialization();
+    let robots = match ROBOTS.get(&origin) {
+        Some(robots) => robots,
+        None => {
+            let robots_url = url.join("/robots.txt").expect("robots.txt url is valid");
+            let (robots, ttl) = match send_to_network(robots_url, None).await {
+                Ok(response) if response.status().is_success() => match response.text().await {
+                    Ok(content) => (
+                        RobotsTxt::parse(&content, CONFIG.product_token()),
+                        ROBOTS_TTL,
+                    ),
+                    Err(err) => {
+                        log::warn!("cannot read the robots.txt of {}: {}", origin, err);
+                        return false;
+                    }
+                },
+                Ok(response) => robots_for_status(response.status()),
+                // retryable statuses still failing after the last attempt
+                Err(HttpError::Status { status, .. }) => robots_for_status(status),
+                Err(err) => {
+                    log::warn!("cannot get the robots.txt of {}: {}", origin, err);
+                    return false;
+                }
+            };
+            ROBOTS.insert(&origin, robots, ttl)
+        }
+    };
 
-    let ratelimit_key = url_r.host_str().unwrap().to_string();
-    log::debug!("get_doc {}", url_r);
-    LIMITER.until_key_ready(&ratelimit_key).await;
+    let mut path = url.path().to_string();
+    if let Some(query) = url.query() {
+        path.push('?');
+        path.push_str(query);
+    }
+    robots.is_allowed(&path)
+}
+
+/// The robots.txt of a host answering an error status, and how long to keep it: no
+/// restriction when there is no robots.txt (or elsewhere), everything disallowed for a
+/// while when the server is overloaded or broken
+fn robots_for_status(status: StatusCode) -> (RobotsTxt, Duration) {
+    if (status.is_client_error() && status != StatusCode::TOO_MANY_REQUESTS)
+        || status.is_redirection()
+    {
+        (RobotsTxt::allow_all(), ROBOTS_TTL)
+    } else {
+        log::warn!("robots.txt answered {}, disallowing the host", status);
+        (RobotsTxt::disallow_all(), ROBOTS_ERROR_TTL)
+    }
+}
 
-    CLIENT.get(url_r).send().await
+/// The responses worth retrying: the server is overloaded or temporarily broken
+fn is_retryable_status(status: StatusCode) -> bool {
+    status == StatusCode::TOO_MANY_REQUESTS
+        || (status.is_server_error() && status != StatusCode::NOT_IMPLEMENTED)
+}
+
+fn is_transient_error(err: &reqwest::Error) -> bool {
+    err.is_timeout() || err.is_connect()
+}
+
+/// The delay asked by the server, in seconds or as an http date
+fn retry_after(headers: &HeaderMap) -> Option<Duration> {
+    let value = headers.get(RETRY_AFTER)?.to_str().ok()?.trim();
+    if let Ok(seconds) = value.parse::<u64>() {
+        return Some(Duration::from_secs(seconds));
+    }
+    let date = DateTime::parse_from_rfc2822(value).ok()?;
+    (date.with_timezone(&Utc) - Utc::now()).to_std().ok()
+}
+
+/// Exponential backoff with jitter before the given retry (1 for the first one), so that
+/// the tasks failing together do not all retry at the same time
+fn backoff(retry: u32) -> Duration {
+    let exponential = BASE_BACKOFF
+        .saturating_mul(2u32.saturating_pow(retry.saturating_sub(1)))
+        .min(MAX_BACKOFF);
+    exponential / 2 + exponential.mul_f64(rand::random::<f64>() / 2.0)
+}
+
+/// Rebuild a response whose body has already been read
+fn response_from_parts(url: Url, status: StatusCode, headers: HeaderMap, body: Bytes) -> Response {
+    let mut builder = hyper::Response::builder().status(status).url(url);
+    *builder.headers_mut().unwrap() = headers;
+    builder.body(body).expect("response is valid").into()
+}
+
+/// Send a request allowed by the robots.txt of its host, or serve its recorded response
+/// when replaying the http fixtures. When recording, the responses are saved as they
+/// are received
+async fn send(url: Url, cached: Option<&CacheEntry>) -> Result<Response, HttpError> {
+    let replay = FIXTURES
+        .as_ref()
+        .is_some_and(|fixtures| fixtures.mode() == FixtureMode::Replay);
+    if !replay && CONFIG.respect_robots && !robots_allow(&url).await {
+        HTTP_REQUESTS
+            .with_label_values(&[url.host_str().unwrap_or_default(), "disallowed"])
+            .inc();
+        return Err(HttpError::Disallowed { url });
+    }
+
+    let Some(fixtures) = FIXTURES.as_ref() else {
+        return send_to_network(url, cached).await;
+    };
+
+    if fixtures.mode() == FixtureMode::Replay {
+        let Some((fixture, body)) = fixtures.replay(&url).await else {
+            let path = fixtures.path(&url);
+            return Err(HttpError::MissingFixture { url, path });
+        };
+        let mut headers = HeaderMap::new();
+        for (name, value) in [
+            (CONTENT_TYPE, &fixture.content_type),
+            (LOCATION, &fixture.location),
+        ] {
+            if let Some(value) = value.as_deref().and_then(|v| HeaderValue::from_str(v).ok()) {
+                headers.insert(name, value);
+            }
+        }
+        return Ok(response_from_parts(url, fixture.status(), headers, body));
+    }
+
+    let response = send_to_network(url.clone(), cached).await?;
+    let status = response.status();
+    let headers = response.headers().clone();
+    let body = response.bytes().await?;
+    if let Err(err) = fixtures.record(&url, status, &headers, &body).await {
+        log::warn!("cannot record the fixture of {}: {}", url, err);
+    }
+    Ok(response_from_parts(url, status, headers, body))
+}
+
+/// Send a request within the limits of its host, conditional when a cached response can be revalidated.
+/// Timeouts, connection errors, 429 and 5xx are retried with backoff, honoring
+/// Retry-After, and count as a failure of the host for the circuit breaker
+async fn send_to_network(url: Url, cached: Option<&CacheEntry>) -> Result<Response, HttpError> {
+    let host = url.host_str().unwrap_or_default().to_string();
+    if !BREAKER.allow(&host) {
+        HTTP_REQUESTS
+            .with_label_values(&[&host, "circuit_open"])
+            .inc();
+        return Err(HttpError::CircuitOpen { host });
+    }
+
+    let mut attempt = 1;
+    loop {
+        let permit = LIMITERS.acquire(&host).await;
+        log::debug!("get {} (attempt {})", url, attempt);
+
+        let mut request = CLIENT.get(url.clone());
+        if let Some(entry) = cached {
+            if let Some(etag) = &entry.etag {
+                request = request.header(IF_NONE_MATCH, etag);
+            }
+            if let Some(last_modified) = &entry.last_modified {
+                request = request.header(IF_MODIFIED_SINCE, last_modified);
+            }
+        }
+        let result = request.send().await;
+        drop(permit);
+
+        // None when the result is final, else the delay asked by the server if any
+        let retry = match &result {
+            Ok(response) if is_retryable_status(response.status()) => {
+                Some(retry_after(response.headers()))
+            }
+            Err(err) if is_transient_error(err) => Some(None),
+            _ => None,
+        };
+        let Some(retry_after) = retry else {
+            return match result {
+                Ok(response) => {
+                    HTTP_REQUESTS.with_label_values(&[&host, "success"]).inc();
+                    BREAKER.record_success(&host);
+                    Ok(response)
+                }
+                Err(err) => {
+                    HTTP_REQUESTS.with_label_values(&[&host, "error"]).inc();
+                    Err(err.into())
+                }
+            };
+        };
+
+        if attempt >= POLICY.max_attempts {
+            HTTP_REQUESTS.with_label_values(&[&host, "failure"]).inc();
+            BREAKER.record_failure(&host);
+            return match result {
+                Ok(response) => Err(HttpError::Status {
+                    url,
+                    status: response.status(),
+                }),
+                Err(err) => Err(err.into()),
+            };
+        }
+
+        let delay = retry_after
+            .unwrap_or_else(|| backoff(attempt))
+            .min(MAX_BACKOFF);
+        match &result {
+            Ok(response) => log::warn!(
+                "{} answered {}, retrying in {:?}",
+                url,
+                response.status(),
+                delay
+            ),
+            Err(err) => log::warn!("{} failed: {}, retrying in {:?}", url, err, delay),
+        }
+        HTTP_REQUESTS.with_label_values(&[&host, "retry"]).inc();
+        tokio::time::sleep(delay).await;
+        attempt += 1;
+    }
+}
+
+/// Execute a request using the shared http client, the response being served from the
+/// http cache while it is fresh
+pub async fn get<U: IntoUrl>(url: U) -> Result<Response, HttpError> {
+    let url = url.into_url()?;
+    // the fixtures must see every request
+    let Some(cache) = CACHE.as_ref().filter(|_| FIXTURES.is_none()) else {
+        return send(url, None).await;
+    };
+
+    let cached = cache.lookup(&url).await;
+    if let Some((entry, body)) = &cached {
+        if cache.is_fresh(entry, &url) {
+            HTTP_CACHE.with_label_values(&["hit"]).inc();
+            return Ok(entry.to_response(url, body.clone()));
+        }
+    }
+
+    let response = send(url.clone(), cached.as_ref().map(|(entry, _)| entry)).await?;
+    if let (StatusCode::NOT_MODIFIED, Some((entry, body))) = (response.status(), cached) {
+        HTTP_CACHE.with_label_values(&["revalidated"]).inc();
+        let response = entry.to_response(url.clone(), body);
+        cache.touch(&url, entry).await;
+        return Ok(response);
+    }
+
+    HTTP_CACHE.with_label_values(&["miss"]).inc();
+    if response.status() != StatusCode::OK {
+        return Ok(response);
+    }
+    let headers = response.headers().clone();
+    let body = response.bytes().await?;
+    cache.store(&url, &headers, &body).await;
+
+    Ok(response_from_parts(url, StatusCode::OK, headers, body))
+}
+
+/// Execute a request bypassing the http cache, for the pages that must be up to date
+/// (like checking an announce is still available) or that are fetched only once
+pub async fn get_uncached<U: IntoUrl>(url: U) -> Result<Response, HttpError> {
+    HTTP_CACHE.with_label_values(&["bypass"]).inc();
+    send(url.into_url()?, None).await
 }
 
 /// Fetch an HTML document from a URL
-/// The requests are rate-limited by host
-pub async fn get_doc<U: IntoUrl>(url: U) -> Result<(Html, StatusCode), reqwest::Error> {
-    let response = get(url).await?;
+/// The requests are rate-limited by host and go through the http cache
+pub async fn get_doc<U: IntoUrl>(url: U) -> Result<(Html, StatusCode), HttpError> {
+    parse_doc(get(url).await?).await
+}
+
+/// Fetch an HTML document from a URL, bypassing the http cache
+pub async fn get_doc_uncached<U: IntoUrl>(url: U) -> Result<(Html, StatusCode), HttpError> {
+    parse_doc(get_uncached(url).await?).await
+}
+
+async fn parse_doc(response: Response) -> Result<(Html, StatusCode), HttpError> {
     let http_code = response.status();
 
     let content = response.text().await?;
     let document = Html::parse_document(&content);
     Ok((document, http_code))
 }
+
+lazy_static! {
+    static ref HTTP_REQUESTS: IntCounterVec = register_int_counter_vec!(
+        "http_requests",
+        "Number of http requests by host and outcome (success, retry, failure, error, circuit_open, disallowed)",
+        &["host", "outcome"]
+    )
+    .unwrap();
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use hyper::StatusCode;
+    use reqwest::header::{HeaderMap, HeaderValue, RETRY_AFTER};
+
+    use super::{
+        backoff, is_retryable_status, retry_after, robots_for_status, CircuitBreaker, HostProxies,
+    };
+    use crate::robots::{ROBOTS_ERROR_TTL, ROBOTS_TTL};
+
+    #[test]
+    fn test_is_retryable_status() {
+        assert!(is_retryable_status(StatusCode::TOO_MANY_REQUESTS));
+        assert!(is_retryable_status(StatusCode::BAD_GATEWAY));
+        assert!(is_retryable_status(StatusCode::SERVICE_UNAVAILABLE));
+        assert!(!is_retryable_status(StatusCode::NOT_IMPLEMENTED));
+        assert!(!is_retryable_status(StatusCode::OK));
+        assert!(!is_retryable_status(StatusCode::FOUND));
+        assert!(!is_retryable_status(StatusCode::NOT_FOUND));
+    }
+
+    #[test]
+    fn test_robots_for_status() {
+        for status in [
+            StatusCode::NOT_FOUND,
+            StatusCode::FORBIDDEN,
+            StatusCode::FOUND,
+        ] {
+            let (robots, ttl) = robots_for_status(status);
+            assert!(robots.is_allowed("/annonces/view/1234"));
+            assert_eq!(ttl, ROBOTS_TTL);
+        }
+        for status in [
+            StatusCode::TOO_MANY_REQUESTS,
+            StatusCode::SERVICE_UNAVAILABLE,
+            StatusCode::NOT_IMPLEMENTED,
+        ] {
+            let (robots, ttl) = robots_for_status(status);
+            assert!(!robots.is_allowed("/annonces/view/1234"));
+            assert_eq!(ttl, ROBOTS_ERROR_TTL);
+        }
+    }
+
+    #[test]
+    fn test_retry_after() {
+        let mut headers = HeaderMap::new();
+        assert_eq!(retry_after(&headers), None);
+
+        headers.insert(RETRY_AFTER, HeaderValue::from_static("120"));
+        assert_eq!(retry_after(&headers), Some(Duration::from_secs(120)));
+
+        let date = (chrono::Utc::now() + chrono::Duration::seconds(60)).to_rfc2822();
+        headers.insert(RETRY_AFTER, HeaderValue::from_str(&date).unwrap());
+        let delay = retry_after(&headers).unwrap();
+        assert!(delay > Duration::from_secs(55) && delay <= Duration::from_secs(60));
+
+        headers.insert(
+            RETRY_AFTER,
+            HeaderValue::from_static("Wed, 21 Oct 2015 07:28:00 GMT"),
+        );
+        assert_eq!(retry_after(&headers), None);
+    }
+
+    #[test]
+    fn test_backoff() {
+        for _ in 0..20 {
+            let first = backoff(1);
+            assert!(first >= Duration::from_millis(500) && first <= Duration::from_secs(1));
+            let third = backoff(3);
+            assert!(third >= Duration::from_secs(2) && third <= Duration::from_secs(4));
+            assert!(backoff(30) <= Duration::from_secs(120));
+        }
+    }
+
+    #[test]
+    fn test_circuit_breaker() {
+        let breaker = CircuitBreaker::new(2, Duration::from_secs(60));
+        assert!(breaker.allow("www.philibert.net"));
+
+        breaker.record_failure("www.philibert.net");
+        assert!(breaker.allow("www.philibert.net"));
+        breaker.record_failure("www.philibert.net");
+        assert!(!breaker.allow("www.philibert.net"));
+        assert!(breaker.allow("boardgamegeek.com"));
+
+        breaker.record_success("www.philibert.net");
+        assert!(breaker.allow("www.philibert.net"));
+    }
+
+    #[test]
+    fn test_circuit_breaker_cooldown() {
+        let breaker = CircuitBreaker::new(1, Duration::ZERO);
+        breaker.record_failure("www.philibert.net");
+        assert!(breaker.allow("www.philibert.net"));
+    }
+
+    #[test]
+    fn test_parse_proxies() {
+        let proxies = HostProxies::parse(
+            "*=http://proxy.local:3128, boardgamegeek.com=socks5h://127.0.0.1:1080",
+        )
+        .unwrap();
+        assert_eq!(
+            proxies.proxy("boardgamegeek.com").unwrap().as_str(),
+            "socks5h://127.0.0.1:1080"
+        );
+        assert_eq!(
+            proxies.proxy("www.okkazeo.com").unwrap().as_str(),
+            "http://proxy.local:3128/"
+        );
+
+        let proxies = HostProxies::parse("www.philibertnet.com=http://proxy.local:3128").unwrap();
+        assert!(proxies.proxy("www.okkazeo.com").is_none());
+        assert!(HostProxies::parse("").unwrap().is_empty());
+        assert!(HostProxies::parse("*=ftp://proxy.local").is_err());
+        assert!(HostProxies::parse("http://proxy.local:3128").is_err());
+    }
+}
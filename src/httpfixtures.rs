@@ -0,0 +1,215 @@
+use std::collections::hash_map::DefaultHasher;
+use std::hash::{Hash, Hasher};
+use std::path::{Path, PathBuf};
+
+use bytes::Bytes;
+use reqwest::header::{HeaderMap, CONTENT_TYPE, LOCATION};
+use reqwest::{StatusCode, Url};
+use serde::{Deserialize, Serialize};
+
+/// longest file name kept as is, longer ones are cut and suffixed by a hash of the url
+const MAX_NAME_LEN: usize = 100;
+
+/// `path` followed by `suffix`, the fixture names keeping the dots of the urls
+fn with_suffix(path: &Path, suffix: &str) -> PathBuf {
+    let mut path = path.as_os_str().to_owned();
+    path.push(suffix);
+    path.into()
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FixtureMode {
+    /// save every response received, to replay it later
+    Record,
+    /// serve the saved responses instead of querying the network
+    Replay,
+}
+
+/// What is needed to rebuild a recorded response, its body being stored next to it
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Fixture {
+    pub url: String,
+    pub status: u16,
+    pub content_type: Option<String>,
+    pub location: Option<String>,
+}
+
+impl Fixture {
+    pub fn status(&self) -> StatusCode {
+        StatusCode::from_u16(self.status).unwrap_or(StatusCode::OK)
+    }
+}
+
+/// Http responses recorded to files, so that the scrapers can be tested end to end
+/// without network. A response of https://host/path?query is stored in
+/// `<dir>/<host>/<path_query>.json` with its body in `<path_query>.body`
+pub struct HttpFixtures {
+    mode: FixtureMode,
+    dir: PathBuf,
+}
+
+impl HttpFixtures {
+    pub fn new(mode: FixtureMode, dir: impl Into<PathBuf>) -> Self {
+        Self {
+            mode,
+            dir: dir.into(),
+        }
+    }
+
+    /// The fixtures of HTTP_FIXTURES (`record` or `replay`, disabled otherwise), in
+    /// HTTP_FIXTURES_DIR (default `tests/fixtures`)
+    pub fn from_env() -> Option<Self> {
+        let mode = match std::env::var("HTTP_FIXTURES").as_deref() {
+            Ok("record") => FixtureMode::Record,
+            Ok("replay") => FixtureMode::Replay,
+            _ => return None,
+        };
+        let dir = std::env::var("HTTP_FIXTURES_DIR").unwrap_or("tests/fixtures".to_string());
+
+        log::info!("http fixtures in {} ({:?})", dir, mode);
+        Some(Self::new(mode, dir))
+    }
+
+    pub fn mode(&self) -> FixtureMode {
+        self.mode
+    }
+
+    /// The path of the fixture of a URL, without extension
+    pub fn path(&self, url: &Url) -> PathBuf {
+        let mut target = url.path().to_string();
+        if let Some(query) = url.query() {
+            target.push('?');
+            target.push_str(query);
+        }
+        let mut name = target
+            .chars()
+            .map(|c| {
+                if c.is_ascii_alphanumeric() || c == '-' || c == '.' {
+                    c
+                } else {
+                    '_'
+                }
+            })
+            .collect::<String>()
+            .trim_matches('_')
+            .to_string();
+        if name.is_empty() {
+            name = "index".to_string();
+        }
+        if name.len() > MAX_NAME_LEN {
+            let mut hasher = DefaultHasher::new();
+            url.as_str().hash(&mut hasher);
+            name = format!("{}_{:016x}", &name[..MAX_NAME_LEN - 17], hasher.finish());
+        }
+
+        self.dir.join(url.host_str().unwrap_or_default()).join(name)
+    }
+
+    /// Save a response, overwriting the previous recording of the URL
+    pub async fn record(
+        &self,
+        url: &Url,
+        status: StatusCode,
+        headers: &HeaderMap,
+        body: &Bytes,
+    ) -> Result<(), std::io::Error> {
+        let header = |name| {
+            headers
+                .get(name)
+                .and_then(|v| v.to_str().ok())
+                .map(str::to_string)
+        };
+        let fixture = Fixture {
+            url: url.to_string(),
+            status: status.as_u16(),
+            content_type: header(CONTENT_TYPE),
+            location: header(LOCATION),
+        };
+
+        let path = self.path(url);
+        if let Some(parent) = path.parent() {
+            tokio::fs::create_dir_all(parent).await?;
+        }
+        let content = serde_json::to_vec_pretty(&fixture).expect("fixture is serializable");
+        tokio::fs::write(with_suffix(&path, ".json"), content).await?;
+        tokio::fs::write(with_suffix(&path, ".body"), body).await?;
+        log::debug!("recorded {} in {:?}", url, path);
+        Ok(())
+    }
+
+    /// The recorded response of a URL, if any
+    pub async fn replay(&self, url: &Url) -> Option<(Fixture, Bytes)> {
+        let path = self.path(url);
+        let fixture = tokio::fs::read(with_suffix(&path, ".json")).await.ok()?;
+        let fixture = serde_json::from_slice::<Fixture>(&fixture).ok()?;
+        let body = tokio::fs::read(with_suffix(&path, ".body"))
+            .await
+            .unwrap_or_default();
+        log::debug!("replaying {} from {:?}", url, path);
+        Some((fixture, Bytes::from(body)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+
+    use bytes::Bytes;
+    use reqwest::header::{HeaderMap, HeaderValue, CONTENT_TYPE, LOCATION};
+    use reqwest::{StatusCode, Url};
+
+    use super::{FixtureMode, HttpFixtures};
+
+    #[test]
+    fn test_path() {
+        let fixtures = HttpFixtures::new(FixtureMode::Replay, "tests/fixtures");
+        let path = |url: &str| fixtures.path(&Url::parse(url).unwrap());
+
+        assert_eq!(
+            path("https://www.okkazeo.com/annonces/view/1234"),
+            PathBuf::from("tests/fixtures/www.okkazeo.com/annonces_view_1234")
+        );
+        assert_eq!(
+            path("https://www.ludocortex.fr/jolisearch?s=Les Colons"),
+            PathBuf::from("tests/fixtures/www.ludocortex.fr/jolisearch_s_Les_20Colons")
+        );
+        assert_eq!(
+            path("https://www.okkazeo.com/"),
+            PathBuf::from("tests/fixtures/www.okkazeo.com/index")
+        );
+
+        let long = path(&format!(
+            "https://boardgamegeek.com/search?q={}",
+            "a".repeat(200)
+        ));
+        let name = long.file_name().unwrap().to_str().unwrap();
+        assert_eq!(name.len(), 100);
+        assert!(name.starts_with("search_q_aaa"));
+    }
+
+    #[tokio::test]
+    async fn test_record_and_replay() {
+        let dir = std::env::temp_dir().join(format!("httpfixtures-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        let recorder = HttpFixtures::new(FixtureMode::Record, &dir);
+        let url = Url::parse("https://www.okkazeo.com/annonces/view.php?id=1234").unwrap();
+
+        let mut headers = HeaderMap::new();
+        headers.insert(CONTENT_TYPE, HeaderValue::from_static("text/html"));
+        headers.insert(LOCATION, HeaderValue::from_static("/annonces/index"));
+        recorder
+            .record(&url, StatusCode::FOUND, &headers, &Bytes::from("moved"))
+            .await
+            .unwrap();
+
+        let player = HttpFixtures::new(FixtureMode::Replay, &dir);
+        let (fixture, body) = player.replay(&url).await.unwrap();
+        assert_eq!(fixture.status(), StatusCode::FOUND);
+        assert_eq!(fixture.content_type.as_deref(), Some("text/html"));
+        assert_eq!(fixture.location.as_deref(), Some("/annonces/index"));
+        assert_eq!(body, Bytes::from("moved"));
+
+        let other = Url::parse("https://www.okkazeo.com/annonces/view.php?id=4321").unwrap();
+        assert!(player.replay(&other).await.is_none());
+    }
+}
@@ -0,0 +1,710 @@
+use std::fmt;
+use std::str::FromStr;
+use std::time::Duration;
+
+use chrono::{DateTime, Utc};
+use deadpool_postgres::PoolError;
+use feed_rs::model::Entry;
+use lazy_static::lazy_static;
+use prometheus::{register_int_counter_vec, register_int_gauge_vec, IntCounterVec, IntGaugeVec};
+use serde::{Deserialize, Serialize};
+use tokio::task::JoinSet;
+use tokio_postgres::{Client, Error};
+
+use crate::db::{
+    delete_from_all_table_with_id, get_db_client, select_game_with_id_from_db,
+    select_stale_references_ids_from_db, update_references_date, upsert_announce_into_db, DbPool,
+    DB_IO,
+};
+use crate::error::ScrapeError;
+use crate::game::{get_game_infos, Game};
+use crate::httpclient::env_or;
+use crate::website::okkazeo::game_still_available;
+
+/// how long a claimed job is reserved to its worker. A job still running past it is
+/// considered abandoned (its worker crashed) and claimed again
+pub const JOB_LEASE: Duration = Duration::from_secs(15 * 60);
+/// how long an announce whose job was dead-lettered is not queued again for the same
+/// kind of job, so that a job failing for good is not recreated on every run
+pub const DEAD_JOB_COOLDOWN: Duration = Duration::from_secs(24 * 3600);
+/// delay before the first retry of a failed job, doubled at each attempt
+const RETRY_BASE_DELAY: Duration = Duration::from_secs(60);
+const RETRY_MAX_DELAY: Duration = Duration::from_secs(6 * 3600);
+/// delay before querying bgg again for an announce whose reviews were not found
+const REVIEWS_RETRY_DELAY: Duration = Duration::from_secs(3600);
+
+/// The scraping work that can be queued on an announce
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum JobKind {
+    /// scrap a new announce and the prices of the game, and store it
+    EnrichAnnounce,
+    /// query the shops again for the prices of a stored announce
+    RefreshReferences,
+    /// check that a stored announce is still for sale, removing it otherwise
+    CheckAvailability,
+    /// query bgg again for the reviews of a stored announce
+    FetchReviews,
+}
+
+impl JobKind {
+    pub const ALL: [JobKind; 4] = [
+        JobKind::EnrichAnnounce,
+        JobKind::RefreshReferences,
+        JobKind::CheckAvailability,
+        JobKind::FetchReviews,
+    ];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            JobKind::EnrichAnnounce => "enrich_announce",
+            JobKind::RefreshReferences => "refresh_references",
+            JobKind::CheckAvailability => "check_availability",
+            JobKind::FetchReviews => "fetch_reviews",
+        }
+    }
+
+    /// Priority of the jobs of this kind unless given otherwise, the new announces of
+    /// the feed coming first
+    pub fn default_priority(&self) -> i16 {
+        match self {
+            JobKind::EnrichAnnounce => 30,
+            JobKind::RefreshReferences => 20,
+            JobKind::FetchReviews => 10,
+            JobKind::CheckAvailability => 0,
+        }
+    }
+}
+
+impl FromStr for JobKind {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        JobKind::ALL
+            .into_iter()
+            .find(|kind| kind.as_str() == s)
+            .ok_or_else(|| format!("unknown job kind {}", s))
+    }
+}
+
+impl fmt::Display for JobKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.as_str())
+    }
+}
+
+/// What a job needs to know besides its announce, stored as json
+#[derive(Debug, Default, Clone, PartialEq, Serialize, Deserialize)]
+pub struct JobPayload {
+    /// modification date of the announce in the feed
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub updated: Option<DateTime<Utc>>,
+}
+
+/// A job to be queued
+#[derive(Debug, Clone)]
+pub struct NewJob {
+    pub kind: JobKind,
+    pub oa_id: u32,
+    pub priority: i16,
+    pub delay: Duration,
+    pub payload: JobPayload,
+}
+
+impl NewJob {
+    pub fn new(kind: JobKind, oa_id: u32) -> Self {
+        NewJob {
+            kind,
+            oa_id,
+            priority: kind.default_priority(),
+            delay: Duration::ZERO,
+            payload: JobPayload::default(),
+        }
+    }
+
+    pub fn priority(mut self, priority: i16) -> Self {
+        self.priority = priority;
+        self
+    }
+
+    pub fn delay(mut self, delay: Duration) -> Self {
+        self.delay = delay;
+        self
+    }
+
+    pub fn updated(mut self, updated: Option<DateTime<Utc>>) -> Self {
+        self.payload.updated = updated;
+        self
+    }
+}
+
+/// A job claimed by a worker
+#[derive(Debug, Clone)]
+pub struct Job {
+    pub id: i64,
+    pub kind: JobKind,
+    pub oa_id: u32,
+    pub payload: JobPayload,
+    /// number of times the job was claimed, this one included
+    pub attempts: i32,
+}
+
+/// What a job did
+#[derive(Debug)]
+pub enum JobOutput {
+    /// the announce was stored, new or updated
+    Stored(Box<Game>),
+    /// the announce was removed, not being for sale anymore
+    Removed(u32),
+    /// nothing to do, the announce being gone
+    Unchanged,
+    /// the job failed, it is retried later or dead-lettered
+    Failed,
+}
+
+/// Why a job failed
+#[derive(Debug)]
+pub enum JobFailure {
+    /// the same job may succeed later
+    Retry(String),
+    /// retrying is pointless, the job is dead-lettered right away
+    Dead(String),
+}
+
+impl fmt::Display for JobFailure {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            JobFailure::Retry(message) | JobFailure::Dead(message) => write!(f, "{}", message),
+        }
+    }
+}
+
+impl From<ScrapeError> for JobFailure {
+    fn from(err: ScrapeError) -> Self {
+        if err.is_transient() {
+            JobFailure::Retry(err.to_string())
+        } else {
+            JobFailure::Dead(err.to_string())
+        }
+    }
+}
+
+impl From<Error> for JobFailure {
+    fn from(err: Error) -> Self {
+        JobFailure::Retry(err.to_string())
+    }
+}
+
+impl From<PoolError> for JobFailure {
+    fn from(err: PoolError) -> Self {
+        JobFailure::Retry(err.to_string())
+    }
+}
+
+/// How the workers consume the queue
+#[derive(Debug, Clone)]
+pub struct JobConfig {
+    /// number of jobs claimed and run at once by a worker
+    pub concurrency: usize,
+    /// number of attempts before a job is dead-lettered
+    pub max_attempts: i32,
+    /// pause of a worker finding nothing to do
+    pub poll_interval: Duration,
+    /// only the jobs up to this priority are claimed, none for all of them
+    pub max_priority: Option<i16>,
+}
+
+impl JobConfig {
+    /// Read JOB_CONCURRENCY (default 4), JOB_MAX_ATTEMPTS (default 5) and
+    /// JOB_POLL_INTERVAL (seconds, default 10)
+    pub fn from_env() -> Self {
+        Self {
+            concurrency: env_or("JOB_CONCURRENCY", 4usize).max(1),
+            max_attempts: env_or("JOB_MAX_ATTEMPTS", 5i32).max(1),
+            poll_interval: Duration::from_secs(env_or("JOB_POLL_INTERVAL", 10)),
+            max_priority: None,
+        }
+    }
+}
+
+/// How the shop prices of the stored announces are kept up to date
+#[derive(Debug, Clone)]
+pub struct RefreshConfig {
+    /// age from which the shop prices of an announce are queried again
+    pub max_age: Duration,
+    /// max number of refreshes queued at each round, 0 disabling them
+    pub batch: usize,
+    /// pause between two rounds
+    pub interval: Duration,
+}
+
+impl RefreshConfig {
+    /// Read REFERENCES_MAX_AGE (hours, default 24), REFERENCES_REFRESH_BATCH (default 100)
+    /// and REFERENCES_REFRESH_INTERVAL (seconds, default 600)
+    pub fn from_env() -> Self {
+        Self {
+            max_age: Duration::from_secs(env_or("REFERENCES_MAX_AGE", 24u64) * 3600),
+            batch: env_or("REFERENCES_REFRESH_BATCH", 100),
+            interval: Duration::from_secs(env_or("REFERENCES_REFRESH_INTERVAL", 600)),
+        }
+    }
+}
+
+/// Priority of the refresh of an announce, the more it is cheaper than new, the sooner.
+/// Still below the enrichment of the new announces
+pub fn refresh_priority(deal_percentage: Option<i32>) -> i16 {
+    let base = JobKind::RefreshReferences.default_priority();
+    match deal_percentage {
+        Some(percentage) => base + (-percentage / 10).clamp(-5, 5) as i16,
+        None => base - 5,
+    }
+}
+
+/// Queue the refresh of the announces whose shop prices are older than the configured
+/// max age, the best deals first. Returns the number of refreshes queued
+pub async fn schedule_references_refresh(
+    db_client: &Client,
+    config: &RefreshConfig,
+) -> Result<usize, Error> {
+    let older_than = Utc::now()
+        - chrono::Duration::from_std(config.max_age).unwrap_or(chrono::Duration::days(1));
+    let stale = select_stale_references_ids_from_db(
+        db_client,
+        older_than,
+        DEAD_JOB_COOLDOWN,
+        config.batch as i64,
+    )
+    .await?;
+
+    let mut queued = 0;
+    for (id, deal_percentage) in stale {
+        let job = NewJob::new(JobKind::RefreshReferences, id as u32)
+            .priority(refresh_priority(deal_percentage));
+        if enqueue_job(db_client, &job).await? {
+            queued += 1;
+        }
+    }
+
+    Ok(queued)
+}
+
+/// Delay before retrying a job that failed at its given attempt
+pub fn retry_delay(attempts: i32) -> Duration {
+    let exponent = attempts.saturating_sub(1).clamp(0, 16) as u32;
+    RETRY_BASE_DELAY
+        .saturating_mul(2u32.pow(exponent))
+        .min(RETRY_MAX_DELAY)
+}
+
+/// Queue a job, unless the announce already has a live job of the same kind, whose
+/// priority is then raised to the one of the new job if it is still pending, or one that
+/// died less than DEAD_JOB_COOLDOWN ago. Returns whether a job was queued or raised
+pub async fn enqueue_job(db_client: &Client, job: &NewJob) -> Result<bool, Error> {
+    let payload = serde_json::to_string(&job.payload).unwrap_or("{}".to_string());
+    let res = db_client
+        .execute(
+            r#"INSERT INTO job (job_kind, job_oa_id, job_payload, job_priority, job_run_at)
+            SELECT $1, $2, $3::text::jsonb, $4, now() + make_interval(secs => $5)
+            WHERE NOT EXISTS (
+                SELECT 1 FROM job WHERE job_kind = $1 AND job_oa_id = $2
+                AND job_status = 'dead' AND job_dead_date > now() - make_interval(secs => $6)
+            )
+            ON CONFLICT (job_kind, job_oa_id) WHERE job_status <> 'dead' DO UPDATE SET
+            job_priority = EXCLUDED.job_priority
+            WHERE job.job_status = 'pending' AND job.job_priority < EXCLUDED.job_priority"#,
+            &[
+                &job.kind.as_str(),
+                &(job.oa_id as i32),
+                &payload,
+                &job.priority,
+                &job.delay.as_secs_f64(),
+                &DEAD_JOB_COOLDOWN.as_secs_f64(),
+            ],
+        )
+        .await?;
+    DB_IO.with_label_values(&["upsert", "job"]).inc();
+
+    // the dead job the new one replaces, if any, is not needed anymore
+    if res > 0 {
+        db_client
+            .execute(
+                "DELETE FROM job WHERE job_kind = $1 AND job_oa_id = $2 AND job_status = 'dead'",
+                &[&job.kind.as_str(), &(job.oa_id as i32)],
+            )
+            .await?;
+        DB_IO.with_label_values(&["delete", "job"]).inc();
+    }
+
+    Ok(res > 0)
+}
+
+/// Claim up to `limit` jobs of the given kinds that are due, up to `max_priority` if any,
+/// the highest priority and oldest first, skipping the ones being claimed by another
+/// worker. The running jobs whose lease expired are claimed again
+pub async fn claim_jobs(
+    db_client: &Client,
+    kinds: &[JobKind],
+    max_priority: Option<i16>,
+    limit: usize,
+) -> Result<Vec<Job>, Error> {
+    let kinds: Vec<&str> = kinds.iter().map(JobKind::as_str).collect();
+    let rows = db_client
+        .query(
+            r#"UPDATE job SET
+            job_status = 'running',
+            job_attempts = job_attempts + 1,
+            job_locked_until = now() + make_interval(secs => $3)
+            WHERE job_id IN (
+                SELECT job_id FROM job
+                WHERE job_kind = ANY($1) AND job_run_at <= now()
+                AND ($4::smallint IS NULL OR job_priority <= $4)
+                AND (job_status = 'pending'
+                    OR (job_status = 'running' AND job_locked_until < now()))
+                ORDER BY job_priority DESC, job_run_at
+                LIMIT $2
+                FOR UPDATE SKIP LOCKED
+            )
+            RETURNING job_id, job_kind, job_oa_id, job_payload::text, job_attempts"#,
+            &[
+                &kinds,
+                &(limit as i64),
+                &JOB_LEASE.as_secs_f64(),
+                &max_priority,
+            ],
+        )
+        .await?;
+    DB_IO.with_label_values(&["update", "job"]).inc();
+
+    let mut jobs = Vec::new();
+    for row in rows {
+        let kind: String = row.try_get(1)?;
+        let Ok(kind) = kind.parse::<JobKind>() else {
+            log::error!("skipping job of unknown kind {}", kind);
+            continue;
+        };
+        let payload: String = row.try_get(3)?;
+        let oa_id: i32 = row.try_get(2)?;
+        jobs.push(Job {
+            id: row.try_get(0)?,
+            kind,
+            oa_id: oa_id as u32,
+            payload: serde_json::from_str(&payload).unwrap_or_default(),
+            attempts: row.try_get(4)?,
+        });
+    }
+
+    Ok(jobs)
+}
+
+/// Remove a job that is done
+pub async fn complete_job(db_client: &Client, id: i64) -> Result<(), Error> {
+    db_client
+        .execute("DELETE FROM job WHERE job_id = $1", &[&id])
+        .await?;
+    DB_IO.with_label_values(&["delete", "job"]).inc();
+
+    Ok(())
+}
+
+/// Schedule a failed job for a retry with backoff, or dead-letter it when it cannot
+/// succeed or ran out of attempts. Returns whether it is dead
+pub async fn fail_job(
+    db_client: &Client,
+    job: &Job,
+    failure: &JobFailure,
+    max_attempts: i32,
+) -> Result<bool, Error> {
+    let dead = matches!(failure, JobFailure::Dead(_)) || job.attempts >= max_attempts;
+    let error = failure.to_string();
+    if dead {
+        db_client
+            .execute(
+                r#"UPDATE job SET job_status = 'dead', job_locked_until = NULL,
+                job_dead_date = now(), job_last_error = $2 WHERE job_id = $1"#,
+                &[&job.id, &error],
+            )
+            .await?;
+    } else {
+        db_client
+            .execute(
+                r#"UPDATE job SET job_status = 'pending', job_locked_until = NULL,
+                job_last_error = $2, job_run_at = now() + make_interval(secs => $3)
+                WHERE job_id = $1"#,
+                &[&job.id, &error, &retry_delay(job.attempts).as_secs_f64()],
+            )
+            .await?;
+    }
+    DB_IO.with_label_values(&["update", "job"]).inc();
+
+    Ok(dead)
+}
+
+/// Update the gauge of the number of jobs by kind and status
+async fn update_job_counts(db_client: &Client) -> Result<(), Error> {
+    let rows = db_client
+        .query(
+            "SELECT job_kind, job_status, count(*) FROM job GROUP BY job_kind, job_status",
+            &[],
+        )
+        .await?;
+    DB_IO.with_label_values(&["select", "job"]).inc();
+
+    for kind in JobKind::ALL {
+        for status in ["pending", "running", "dead"] {
+            JOBS.with_label_values(&[kind.as_str(), status]).set(0);
+        }
+    }
+    for row in rows {
+        let kind: String = row.try_get(0)?;
+        let status: String = row.try_get(1)?;
+        let count: i64 = row.try_get(2)?;
+        JOBS.with_label_values(&[&kind, &status]).set(count);
+    }
+
+    Ok(())
+}
+
+/// Claim a batch of jobs of the given kinds and run them concurrently. Returns what
+/// each claimed job did, nothing when none was due
+pub async fn process_jobs(
+    pool: &DbPool,
+    kinds: &[JobKind],
+    config: &JobConfig,
+) -> Result<Vec<JobOutput>, PoolError> {
+    let jobs = {
+        let db_client = get_db_client(pool).await?;
+        if let Err(e) = update_job_counts(&db_client).await {
+            log::error!("error db, cannot count jobs : {}", e);
+        }
+        claim_jobs(&db_client, kinds, config.max_priority, config.concurrency).await?
+    };
+
+    let mut tasks = JoinSet::new();
+    for job in jobs {
+        let pool = pool.clone();
+        tasks.spawn(async move {
+            let result = run_job(&pool, &job).await;
+            (job, result)
+        });
+    }
+
+    let mut outputs = Vec::new();
+    while let Some(res) = tasks.join_next().await {
+        // a job whose task panicked stays running until its lease expires
+        let (job, result) = match res {
+            Ok(res) => res,
+            Err(e) => {
+                log::error!("error joining job task : {}", e);
+                continue;
+            }
+        };
+        // likewise when its outcome cannot be saved
+        let db_client = match get_db_client(pool).await {
+            Ok(db_client) => db_client,
+            Err(e) => {
+                log::error!("error db, cannot save outcome of job {} : {}", job.id, e);
+                continue;
+            }
+        };
+        let output = match result {
+            Ok(output) => {
+                if let Err(e) = complete_job(&db_client, job.id).await {
+                    log::error!("error db, cannot complete job {} : {}", job.id, e);
+                }
+                JOBS_PROCESSED
+                    .with_label_values(&[job.kind.as_str(), "done"])
+                    .inc();
+                output
+            }
+            Err(failure) => {
+                match fail_job(&db_client, &job, &failure, config.max_attempts).await {
+                    Ok(dead) => {
+                        let outcome = if dead { "dead" } else { "retry" };
+                        log::warn!(
+                            "{} job of announce {} failed at attempt {} ({}) : {}",
+                            job.kind,
+                            job.oa_id,
+                            job.attempts,
+                            outcome,
+                            failure
+                        );
+                        JOBS_PROCESSED
+                            .with_label_values(&[job.kind.as_str(), outcome])
+                            .inc();
+                    }
+                    Err(e) => log::error!("error db, cannot fail job {} : {}", job.id, e),
+                }
+                JobOutput::Failed
+            }
+        };
+        outputs.push(output);
+    }
+
+    Ok(outputs)
+}
+
+async fn run_job(pool: &DbPool, job: &Job) -> Result<JobOutput, JobFailure> {
+    log::debug!("running {} job of announce {}", job.kind, job.oa_id);
+    match job.kind {
+        JobKind::EnrichAnnounce => enrich_announce(pool, job).await,
+        JobKind::RefreshReferences => refresh_references(pool, job.oa_id).await,
+        JobKind::CheckAvailability => check_availability(pool, job.oa_id).await,
+        JobKind::FetchReviews => fetch_reviews(pool, job.oa_id).await,
+    }
+}
+
+/// The connection is only taken once the scraping is done, not to hold it while waiting
+/// for the websites
+async fn enrich_announce(pool: &DbPool, job: &Job) -> Result<JobOutput, JobFailure> {
+    let entry = job.payload.updated.map(|updated| Entry {
+        updated: Some(updated),
+        ..Default::default()
+    });
+    let game = match get_game_infos(entry.as_ref(), job.oa_id).await {
+        Ok(game) => game,
+        Err(ScrapeError::NotFound { .. }) => {
+            log::debug!("announce {} gone before being enriched", job.oa_id);
+            return Ok(JobOutput::Unchanged);
+        }
+        Err(e) => {
+            e.record("okkazeo");
+            return Err(e.into());
+        }
+    };
+
+    let mut db_client = get_db_client(pool).await?;
+    upsert_announce_into_db(&mut db_client, &game).await?;
+    update_references_date(&db_client, job.oa_id as i32).await?;
+    if game.review.reviews.is_empty() {
+        let reviews = NewJob::new(JobKind::FetchReviews, job.oa_id).delay(REVIEWS_RETRY_DELAY);
+        enqueue_job(&db_client, &reviews).await?;
+    }
+
+    Ok(JobOutput::Stored(game))
+}
+
+async fn refresh_references(pool: &DbPool, id: u32) -> Result<JobOutput, JobFailure> {
+    let game = select_game_with_id_from_db(&*get_db_client(pool).await?, id).await;
+    let Some(mut game) = game else {
+        return Ok(JobOutput::Unchanged);
+    };
+
+    game.refresh_references().await;
+    let mut db_client = get_db_client(pool).await?;
+    upsert_announce_into_db(&mut db_client, &game).await?;
+    update_references_date(&db_client, id as i32).await?;
+
+    Ok(JobOutput::Stored(Box::new(game)))
+}
+
+async fn fetch_reviews(pool: &DbPool, id: u32) -> Result<JobOutput, JobFailure> {
+    let game = select_game_with_id_from_db(&*get_db_client(pool).await?, id).await;
+    let Some(mut game) = game else {
+        return Ok(JobOutput::Unchanged);
+    };
+
+    game.get_reviews().await;
+    let mut db_client = get_db_client(pool).await?;
+    upsert_announce_into_db(&mut db_client, &game).await?;
+
+    Ok(JobOutput::Stored(Box::new(game)))
+}
+
+async fn check_availability(pool: &DbPool, id: u32) -> Result<JobOutput, JobFailure> {
+    let available = game_still_available(id)
+        .await
+        .inspect_err(|e| e.record("okkazeo"))?;
+    if available {
+        return Ok(JobOutput::Unchanged);
+    }
+
+    log::debug!("removing games with id {}", id);
+    let mut db_client = get_db_client(pool).await?;
+    delete_from_all_table_with_id(&mut db_client, id as i32).await?;
+
+    Ok(JobOutput::Removed(id))
+}
+
+lazy_static! {
+    static ref JOBS: IntGaugeVec = register_int_gauge_vec!(
+        "jobs",
+        "Number of queued jobs by kind and status (pending, running, dead)",
+        &["kind", "status"]
+    )
+    .unwrap();
+    static ref JOBS_PROCESSED: IntCounterVec = register_int_counter_vec!(
+        "jobs_processed",
+        "Number of jobs run by kind and outcome (done, retry, dead)",
+        &["kind", "outcome"]
+    )
+    .unwrap();
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::{refresh_priority, retry_delay, JobFailure, JobKind, JobPayload, NewJob};
+    use crate::error::ScrapeError;
+
+    #[test]
+    fn test_kind() {
+        for kind in JobKind::ALL {
+            assert_eq!(kind.as_str().parse::<JobKind>(), Ok(kind));
+        }
+        assert!("enrich".parse::<JobKind>().is_err());
+        assert!(
+            JobKind::EnrichAnnounce.default_priority()
+                > JobKind::CheckAvailability.default_priority()
+        );
+
+        let job = NewJob::new(JobKind::RefreshReferences, 1234).priority(42);
+        assert_eq!(job.priority, 42);
+        assert_eq!(job.delay, Duration::ZERO);
+    }
+
+    #[test]
+    fn test_retry_delay() {
+        assert_eq!(retry_delay(1), Duration::from_secs(60));
+        assert_eq!(retry_delay(2), Duration::from_secs(120));
+        assert_eq!(retry_delay(4), Duration::from_secs(480));
+        assert_eq!(retry_delay(0), Duration::from_secs(60));
+        assert_eq!(retry_delay(50), Duration::from_secs(6 * 3600));
+    }
+
+    #[test]
+    fn test_refresh_priority() {
+        assert_eq!(refresh_priority(Some(-25)), 22);
+        assert_eq!(refresh_priority(Some(-80)), 25);
+        assert_eq!(refresh_priority(Some(0)), 20);
+        assert_eq!(refresh_priority(Some(15)), 19);
+        assert_eq!(refresh_priority(None), 15);
+        assert!(refresh_priority(Some(-100)) < JobKind::EnrichAnnounce.default_priority());
+    }
+
+    #[test]
+    fn test_failure_from_scrape_error() {
+        let failure = JobFailure::from(ScrapeError::RateLimited {
+            host: "www.okkazeo.com".to_string(),
+        });
+        assert!(matches!(failure, JobFailure::Retry(_)));
+
+        let failure = JobFailure::from(ScrapeError::selector_miss("okkazeo", ".desc_jeu .prix"));
+        assert!(matches!(failure, JobFailure::Dead(message) if message.contains(".prix")));
+    }
+
+    #[test]
+    fn test_payload() {
+        assert_eq!(serde_json::to_string(&JobPayload::default()).unwrap(), "{}");
+
+        let payload: JobPayload =
+            serde_json::from_str(r#"{"updated":"2024-01-02T10:00:00Z"}"#).unwrap();
+        assert_eq!(
+            payload.updated.unwrap().to_rfc3339(),
+            "2024-01-02T10:00:00+00:00"
+        );
+        assert_eq!(
+            serde_json::from_str::<JobPayload>("{}").unwrap(),
+            JobPayload::default()
+        );
+    }
+}
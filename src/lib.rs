@@ -1,6 +1,18 @@
 pub mod db;
+pub mod error;
+pub mod filter_query;
 pub mod frontlib;
 pub mod game;
+pub mod health;
+pub mod httpcache;
 pub mod httpclient;
+pub mod httpfixtures;
+pub mod jobs;
 pub mod metrics;
+pub mod migrations;
+pub mod notifier;
+pub mod ratelimit;
+pub mod robots;
+pub mod storage;
 pub mod website;
+pub mod wishlist;
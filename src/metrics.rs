@@ -1,6 +1,14 @@
+use axum::http::StatusCode;
+use axum::response::{Html, IntoResponse, Response};
 use axum::{routing::get, Router};
 use lazy_static::lazy_static;
 use prometheus::{register_int_counter, Encoder, IntCounter, TextEncoder};
+use tera::{Context, Tera};
+
+use crate::health;
+
+/// embedded as the backend and the crawler are deployed without the templates directory
+const SCRAPER_HEALTH_TEMPLATE: &str = include_str!("../templates/scraper_health.tera");
 
 async fn metrics() -> String {
     AXUM_METRICS_GET.inc();
@@ -13,8 +21,28 @@ async fn metrics() -> String {
     String::from_utf8(buffer).expect("Failed to convert bytes to string")
 }
 
+/// The health of the scrapers of this process, answering 503 when one of them is unhealthy
+async fn scraper_health() -> Response {
+    let sources = health::report();
+    let unhealthy = sources.iter().any(|source| source.is_unhealthy());
+
+    let mut context = Context::new();
+    context.insert("sources", &sources);
+    context.insert("unhealthy", &unhealthy);
+    match Tera::one_off(SCRAPER_HEALTH_TEMPLATE, &context, true) {
+        Ok(page) if unhealthy => (StatusCode::SERVICE_UNAVAILABLE, Html(page)).into_response(),
+        Ok(page) => Html(page).into_response(),
+        Err(e) => {
+            log::error!("cannot render scraper health : {:?}", e);
+            StatusCode::INTERNAL_SERVER_ERROR.into_response()
+        }
+    }
+}
+
 pub async fn run_metrics(bind_addr: String) {
-    let app = Router::new().route("/metrics", get(metrics));
+    let app = Router::new()
+        .route("/metrics", get(metrics))
+        .route("/health/scrapers", get(scraper_health));
 
     log::info!("[METRICS] starting metrics server on {}", bind_addr);
     let listener = tokio::net::TcpListener::bind(bind_addr).await.unwrap();
@@ -29,3 +57,28 @@ lazy_static! {
     )
     .unwrap();
 }
+
+#[cfg(test)]
+mod tests {
+    use tera::{Context, Tera};
+
+    use super::SCRAPER_HEALTH_TEMPLATE;
+    use crate::health::{ScrapeOutcome, ScraperHealth};
+
+    #[test]
+    fn test_scraper_health_page() {
+        let health = ScraperHealth::default();
+        for _ in 0..3 {
+            health.record_at("ludifolie", ScrapeOutcome::Matched, 1_760_000_000);
+        }
+        health.record_at("ludifolie", ScrapeOutcome::Broken, 1_760_000_000);
+
+        let mut context = Context::new();
+        context.insert("sources", &health.report_at(1_760_000_000));
+        context.insert("unhealthy", &false);
+        let page = Tera::one_off(SCRAPER_HEALTH_TEMPLATE, &context, true).unwrap();
+        assert!(page.contains("<td>ludifolie</td>"));
+        assert!(page.contains("75 %"));
+        assert!(page.contains("2025-10-09 08:00"));
+    }
+}
@@ -0,0 +1,206 @@
+use tokio_postgres::Client;
+
+use crate::db::DB_IO;
+
+/// key of the postgres advisory lock held while migrating
+const MIGRATION_LOCK_ID: i64 = 0x6d69_6772_6174;
+
+/// A versioned schema change, applied once and recorded in the schema_migrations table
+pub struct Migration {
+    pub version: i32,
+    pub name: &'static str,
+    pub sql: &'static str,
+}
+
+macro_rules! migration {
+    ($version:expr, $name:expr) => {
+        Migration {
+            version: $version,
+            name: $name,
+            sql: include_str!(concat!("../migrations/", $name, ".sql")),
+        }
+    };
+}
+
+/// Every migration, in the order they must be applied. Never edit an applied migration,
+/// add a new one at the end instead
+pub static MIGRATIONS: &[Migration] = &[
+    migration!(1, "0001_initial_schema"),
+    migration!(2, "0002_price_history"),
+    migration!(3, "0003_seller_reputation"),
+    migration!(4, "0004_saved_search"),
+    migration!(5, "0005_notification_sent"),
+    migration!(6, "0006_wishlist"),
+    migration!(7, "0007_wishlist_bgg_import"),
+    migration!(8, "0008_announce_upsert_keys"),
+    migration!(9, "0009_name_search"),
+    migration!(10, "0010_listable_game"),
+    migration!(11, "0011_job_queue"),
+    migration!(12, "0012_references_date"),
+    migration!(13, "0013_feed_state"),
+    migration!(14, "0014_unknown_creation_date"),
+    migration!(15, "0015_job_dead_date"),
+];
+
+/// What to do at startup when the database is not up to date, read from DB_MIGRATE
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MigrationMode {
+    /// apply the pending migrations (default)
+    Apply,
+    /// refuse to start if some migrations are pending
+    Check,
+}
+
+impl MigrationMode {
+    pub fn from_env() -> Self {
+        match std::env::var("DB_MIGRATE").as_deref() {
+            Ok("check") => MigrationMode::Check,
+            _ => MigrationMode::Apply,
+        }
+    }
+}
+
+async fn create_history_table(db_client: &Client) -> Result<(), tokio_postgres::Error> {
+    db_client
+        .batch_execute(
+            r#"CREATE TABLE IF NOT EXISTS "schema_migrations" (
+                "version" integer PRIMARY KEY,
+                "name" text NOT NULL,
+                "applied_on" timestamptz DEFAULT now()
+            )"#,
+        )
+        .await
+}
+
+/// Versions recorded in the history, none if the history table does not exist yet
+pub async fn select_applied_migrations(
+    db_client: &Client,
+) -> Result<Vec<i32>, tokio_postgres::Error> {
+    let exists: bool = db_client
+        .query_one("SELECT to_regclass('schema_migrations') IS NOT NULL", &[])
+        .await?
+        .get(0);
+    if !exists {
+        return Ok(vec![]);
+    }
+
+    let rows = db_client
+        .query(
+            "SELECT version FROM schema_migrations ORDER BY version",
+            &[],
+        )
+        .await?;
+    DB_IO
+        .with_label_values(&["select", "schema_migrations"])
+        .inc();
+
+    Ok(rows.iter().map(|row| row.get(0)).collect())
+}
+
+/// Migrations not yet recorded in the history. Fails if the database has a version
+/// unknown to this binary, meaning it is older than the schema
+pub fn pending_migrations(applied: &[i32]) -> Result<Vec<&'static Migration>, anyhow::Error> {
+    let latest = MIGRATIONS.last().map_or(0, |m| m.version);
+    if let Some(unknown) = applied.iter().find(|version| **version > latest) {
+        return Err(anyhow::anyhow!(
+            "database schema version {} is newer than this binary ({})",
+            unknown,
+            latest
+        ));
+    }
+
+    Ok(MIGRATIONS
+        .iter()
+        .filter(|m| !applied.contains(&m.version))
+        .collect())
+}
+
+async fn apply_pending_migrations(db_client: &Client) -> Result<usize, anyhow::Error> {
+    // the lock serializes binaries starting at the same time, the second one
+    // sees the migrations applied by the first one once it gets the lock
+    db_client
+        .execute("SELECT pg_advisory_xact_lock($1)", &[&MIGRATION_LOCK_ID])
+        .await?;
+    create_history_table(db_client).await?;
+    let pending = pending_migrations(&select_applied_migrations(db_client).await?)?;
+
+    for migration in &pending {
+        log::info!("applying migration {}", migration.name);
+        db_client.batch_execute(migration.sql).await?;
+        db_client
+            .execute(
+                "INSERT INTO schema_migrations (version, name) VALUES ($1, $2)",
+                &[&migration.version, &migration.name],
+            )
+            .await?;
+        DB_IO
+            .with_label_values(&["insert", "schema_migrations"])
+            .inc();
+    }
+
+    Ok(pending.len())
+}
+
+/// Bring the database schema up to date, or only check it is with MigrationMode::Check.
+/// All pending migrations are applied in a single transaction
+pub async fn migrate_db(db_client: &Client, mode: MigrationMode) -> Result<(), anyhow::Error> {
+    if mode == MigrationMode::Check {
+        let pending = pending_migrations(&select_applied_migrations(db_client).await?)?;
+        if !pending.is_empty() {
+            return Err(anyhow::anyhow!(
+                "database schema is not up to date, pending migrations : {}",
+                pending
+                    .iter()
+                    .map(|m| m.name)
+                    .collect::<Vec<&str>>()
+                    .join(", ")
+            ));
+        }
+        log::info!("database schema is up to date");
+        return Ok(());
+    }
+
+    db_client.batch_execute("BEGIN").await?;
+    match apply_pending_migrations(db_client).await {
+        Ok(nb_applied) => {
+            db_client.batch_execute("COMMIT").await?;
+            log::info!(
+                "database schema is up to date, {} migrations applied",
+                nb_applied
+            );
+            Ok(())
+        }
+        Err(e) => {
+            db_client.batch_execute("ROLLBACK").await?;
+            Err(e)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{pending_migrations, MIGRATIONS};
+
+    #[test]
+    fn test_versions() {
+        for (i, migration) in MIGRATIONS.iter().enumerate() {
+            assert_eq!(migration.version, i as i32 + 1);
+            assert!(migration
+                .name
+                .starts_with(&format!("{:04}_", migration.version)));
+            assert!(!migration.sql.trim().is_empty());
+        }
+    }
+
+    #[test]
+    fn test_pending() {
+        assert_eq!(pending_migrations(&[]).unwrap().len(), MIGRATIONS.len());
+
+        let pending = pending_migrations(&[1, 2, 3]).unwrap();
+        assert_eq!(pending[0].version, 4);
+
+        let all: Vec<i32> = MIGRATIONS.iter().map(|m| m.version).collect();
+        assert!(pending_migrations(&all).unwrap().is_empty());
+        assert!(pending_migrations(&[MIGRATIONS.len() as i32 + 1]).is_err());
+    }
+}
@@ -0,0 +1,364 @@
+pub mod push;
+pub mod smtp;
+pub mod webhook;
+
+use std::collections::HashMap;
+use std::error::Error;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use async_trait::async_trait;
+use lazy_static::lazy_static;
+use prometheus::{register_int_counter_vec, IntCounterVec};
+use reqwest::{Client, ClientBuilder, RequestBuilder};
+use serde::Serialize;
+
+use crate::db::{
+    check_if_notification_sent, get_db_client, insert_into_notification_sent_table, DbPool,
+};
+use crate::game::Game;
+
+use self::push::{Gotify, Ntfy};
+use self::smtp::{Smtp, SmtpConfig};
+use self::webhook::{Discord, Slack, Webhook};
+
+const DEFAULT_MAX_ATTEMPTS: u32 = 3;
+const DEFAULT_RETRY_DELAY: Duration = Duration::from_secs(2);
+
+lazy_static! {
+    static ref CLIENT: Client = ClientBuilder::new()
+        .timeout(Duration::from_secs(10))
+        .build()
+        .expect("Failed to build reqwest::Client");
+}
+
+/// A deal alert, built from an announce matched by a saved search or a wishlist item
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct Notification {
+    pub id: u32,
+    pub name: String,
+    pub price: f32,
+    pub deal_percentage: i32,
+    pub seller: String,
+    pub city: Option<String>,
+    pub url: String,
+    pub permalink: Option<String>,
+    pub reason: String,
+}
+
+impl Notification {
+    pub fn from_game(game: &Game, reason: &str) -> Self {
+        let announce = &game.okkazeo_announce;
+        Notification {
+            id: announce.id,
+            name: announce.name.clone(),
+            price: announce.price,
+            deal_percentage: game.deal.deal_percentage,
+            seller: announce.seller.name.clone(),
+            city: announce.city.clone(),
+            url: announce.url.clone(),
+            permalink: std::env::var("FRONTEND_PUBLIC_URL")
+                .ok()
+                .map(|base| format!("{}/game/{}", base.trim_end_matches('/'), announce.id)),
+            reason: reason.to_string(),
+        }
+    }
+
+    pub fn title(&self) -> String {
+        if self.deal_percentage < 0 {
+            format!(
+                "{} - {:.2}€ ({}%)",
+                self.name, self.price, self.deal_percentage
+            )
+        } else {
+            format!("{} - {:.2}€", self.name, self.price)
+        }
+    }
+
+    pub fn message(&self) -> String {
+        let mut message = format!("{} : vendu par {}", self.reason, self.seller);
+        if let Some(city) = &self.city {
+            message.push_str(&format!(" ({})", city));
+        }
+        message
+    }
+
+    /// Link to the announce, on the frontend if it is publicly reachable
+    pub fn link(&self) -> &str {
+        self.permalink.as_deref().unwrap_or(&self.url)
+    }
+}
+
+/// A channel deal alerts can be delivered to
+#[async_trait]
+pub trait Notifier: Send + Sync {
+    /// Name of the channel, used for de-duplication and metrics
+    fn channel(&self) -> &str;
+
+    async fn send(&self, notification: &Notification) -> Result<(), Box<dyn Error + Send + Sync>>;
+}
+
+/// Send a request built by an http notifier, failing on non 2xx responses
+async fn send_request(request: RequestBuilder) -> Result<(), Box<dyn Error + Send + Sync>> {
+    request.send().await?.error_for_status()?;
+    Ok(())
+}
+
+fn env_var(name: &str) -> Option<String> {
+    std::env::var(name).ok().filter(|v| !v.is_empty())
+}
+
+/// Record of the notifications delivered, per channel and announce
+#[async_trait]
+pub trait SentNotifications: Send + Sync {
+    /// Whether the announce was already notified on the channel at this price or lower
+    async fn is_sent(
+        &self,
+        channel: &str,
+        id: i32,
+        price: f32,
+    ) -> Result<bool, Box<dyn Error + Send + Sync>>;
+
+    async fn set_sent(
+        &self,
+        channel: &str,
+        id: i32,
+        price: f32,
+    ) -> Result<(), Box<dyn Error + Send + Sync>>;
+}
+
+#[async_trait]
+impl SentNotifications for DbPool {
+    async fn is_sent(
+        &self,
+        channel: &str,
+        id: i32,
+        price: f32,
+    ) -> Result<bool, Box<dyn Error + Send + Sync>> {
+        let db_client = get_db_client(self).await?;
+        Ok(check_if_notification_sent(&db_client, channel, id, price).await?)
+    }
+
+    async fn set_sent(
+        &self,
+        channel: &str,
+        id: i32,
+        price: f32,
+    ) -> Result<(), Box<dyn Error + Send + Sync>> {
+        let db_client = get_db_client(self).await?;
+        Ok(insert_into_notification_sent_table(&db_client, channel, id, price).await?)
+    }
+}
+
+/// Delivers notifications to every configured channel, with retries
+/// and de-duplication per announce
+pub struct Dispatcher {
+    notifiers: Vec<Arc<dyn Notifier>>,
+    sent: Arc<dyn SentNotifications>,
+    /// lowest price of the deliveries in progress, per channel and announce
+    pending: Arc<Mutex<HashMap<(String, u32), f32>>>,
+    max_attempts: u32,
+    retry_delay: Duration,
+}
+
+impl Dispatcher {
+    pub fn new(
+        notifiers: Vec<Box<dyn Notifier>>,
+        sent: Arc<dyn SentNotifications>,
+        max_attempts: u32,
+        retry_delay: Duration,
+    ) -> Self {
+        Dispatcher {
+            notifiers: notifiers.into_iter().map(Arc::from).collect(),
+            sent,
+            pending: Arc::new(Mutex::new(HashMap::new())),
+            max_attempts: max_attempts.max(1),
+            retry_delay,
+        }
+    }
+
+    /// Build the dispatcher from the NOTIFY_* environment variables,
+    /// a channel is enabled as soon as its url (or smtp host) is set
+    pub fn from_env(sent: Arc<dyn SentNotifications>) -> Self {
+        let mut notifiers: Vec<Box<dyn Notifier>> = Vec::new();
+
+        if let Some(config) = SmtpConfig::from_env() {
+            match Smtp::new(config) {
+                Ok(smtp) => notifiers.push(Box::new(smtp)),
+                Err(e) => log::error!("cannot configure smtp notifier : {}", e),
+            }
+        }
+        if let Some(url) = env_var("NOTIFY_WEBHOOK_URL") {
+            notifiers.push(Box::new(Webhook::new(&url)));
+        }
+        if let Some(url) = env_var("NOTIFY_DISCORD_WEBHOOK_URL") {
+            notifiers.push(Box::new(Discord::new(&url)));
+        }
+        if let Some(url) = env_var("NOTIFY_SLACK_WEBHOOK_URL") {
+            notifiers.push(Box::new(Slack::new(&url)));
+        }
+        if let (Some(url), Some(topic)) = (env_var("NOTIFY_NTFY_URL"), env_var("NOTIFY_NTFY_TOPIC"))
+        {
+            notifiers.push(Box::new(Ntfy::new(
+                &url,
+                &topic,
+                env_var("NOTIFY_NTFY_TOKEN"),
+            )));
+        }
+        if let (Some(url), Some(token)) =
+            (env_var("NOTIFY_GOTIFY_URL"), env_var("NOTIFY_GOTIFY_TOKEN"))
+        {
+            notifiers.push(Box::new(Gotify::new(&url, &token)));
+        }
+
+        let max_attempts = env_var("NOTIFY_MAX_ATTEMPTS")
+            .and_then(|v| v.parse::<u32>().ok())
+            .unwrap_or(DEFAULT_MAX_ATTEMPTS);
+
+        log::info!(
+            "notification channels : {:?}",
+            notifiers.iter().map(|n| n.channel()).collect::<Vec<&str>>()
+        );
+        Dispatcher::new(notifiers, sent, max_attempts, DEFAULT_RETRY_DELAY)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.notifiers.is_empty()
+    }
+
+    /// Send a notification through a channel, retrying with an exponential backoff
+    pub async fn send_with_retry(
+        &self,
+        notifier: &dyn Notifier,
+        notification: &Notification,
+    ) -> Result<(), Box<dyn Error + Send + Sync>> {
+        send_with_retry(notifier, notification, self.max_attempts, self.retry_delay).await
+    }
+
+    /// Send a notification on every channel it has not been sent to yet, in the background
+    /// so that the retries do not hold the caller. An announce is notified again on a
+    /// channel only if its price went down
+    pub fn notify(&self, notification: &Notification) {
+        for notifier in &self.notifiers {
+            let key = (notifier.channel().to_string(), notification.id);
+            {
+                let mut pending = self.pending.lock().unwrap();
+                if pending
+                    .get(&key)
+                    .is_some_and(|price| *price <= notification.price)
+                {
+                    log::debug!(
+                        "announce {} already being notified on {}",
+                        notification.id,
+                        key.0
+                    );
+                    continue;
+                }
+                pending.insert(key.clone(), notification.price);
+            }
+
+            let notifier = notifier.clone();
+            let sent = self.sent.clone();
+            let pending = self.pending.clone();
+            let notification = notification.clone();
+            let (max_attempts, retry_delay) = (self.max_attempts, self.retry_delay);
+            tokio::spawn(async move {
+                deliver(
+                    notifier.as_ref(),
+                    sent.as_ref(),
+                    &notification,
+                    max_attempts,
+                    retry_delay,
+                )
+                .await;
+                let mut pending = pending.lock().unwrap();
+                if pending.get(&key) == Some(&notification.price) {
+                    pending.remove(&key);
+                }
+            });
+        }
+    }
+}
+
+/// Send a notification through a channel unless it was already sent, and record it
+async fn deliver(
+    notifier: &dyn Notifier,
+    sent: &dyn SentNotifications,
+    notification: &Notification,
+    max_attempts: u32,
+    retry_delay: Duration,
+) {
+    let channel = notifier.channel();
+    let id = notification.id as i32;
+    match sent.is_sent(channel, id, notification.price).await {
+        Ok(true) => {
+            log::debug!("announce {} already notified on {}", id, channel);
+            return;
+        }
+        Ok(false) => {}
+        Err(e) => {
+            log::error!("error db, cannot check notification {} : {}", id, e);
+            return;
+        }
+    }
+
+    if let Err(e) = send_with_retry(notifier, notification, max_attempts, retry_delay).await {
+        log::error!("cannot send notification {} on {} : {}", id, channel, e);
+        return;
+    }
+
+    if let Err(e) = sent.set_sent(channel, id, notification.price).await {
+        log::error!("error db, cannot record notification {} : {}", id, e);
+    }
+}
+
+async fn send_with_retry(
+    notifier: &dyn Notifier,
+    notification: &Notification,
+    max_attempts: u32,
+    retry_delay: Duration,
+) -> Result<(), Box<dyn Error + Send + Sync>> {
+    let mut delay = retry_delay;
+    let mut attempt = 1;
+    loop {
+        match notifier.send(notification).await {
+            Ok(()) => {
+                NOTIFICATIONS
+                    .with_label_values(&[notifier.channel(), "success"])
+                    .inc();
+                return Ok(());
+            }
+            Err(e) if attempt < max_attempts => {
+                log::warn!(
+                    "notification {} on {} failed (attempt {}/{}) : {}",
+                    notification.id,
+                    notifier.channel(),
+                    attempt,
+                    max_attempts,
+                    e
+                );
+                NOTIFICATIONS
+                    .with_label_values(&[notifier.channel(), "retry"])
+                    .inc();
+                tokio::time::sleep(delay).await;
+                delay *= 2;
+                attempt += 1;
+            }
+            Err(e) => {
+                NOTIFICATIONS
+                    .with_label_values(&[notifier.channel(), "fail"])
+                    .inc();
+                return Err(e);
+            }
+        }
+    }
+}
+
+lazy_static! {
+    static ref NOTIFICATIONS: IntCounterVec = register_int_counter_vec!(
+        "notifications",
+        "Number of notifications sent per channel",
+        &["channel", "result"]
+    )
+    .unwrap();
+}
@@ -0,0 +1,87 @@
+use std::error::Error;
+
+use async_trait::async_trait;
+use serde_json::json;
+
+use super::{send_request, Notification, Notifier, CLIENT};
+
+/// ntfy server, the notification is published in JSON on the server root
+pub struct Ntfy {
+    url: String,
+    topic: String,
+    token: Option<String>,
+}
+
+impl Ntfy {
+    pub fn new(url: &str, topic: &str, token: Option<String>) -> Self {
+        Ntfy {
+            url: url.trim_end_matches('/').to_string(),
+            topic: topic.to_string(),
+            token,
+        }
+    }
+}
+
+#[async_trait]
+impl Notifier for Ntfy {
+    fn channel(&self) -> &str {
+        "ntfy"
+    }
+
+    async fn send(&self, notification: &Notification) -> Result<(), Box<dyn Error + Send + Sync>> {
+        let payload = json!({
+            "topic": self.topic,
+            "title": notification.title(),
+            "message": notification.message(),
+            "click": notification.link(),
+            "tags": ["game_die"],
+        });
+
+        let mut request = CLIENT.post(&self.url).json(&payload);
+        if let Some(token) = &self.token {
+            request = request.bearer_auth(token);
+        }
+        send_request(request).await
+    }
+}
+
+/// Gotify server, authenticated with an application token
+pub struct Gotify {
+    url: String,
+    token: String,
+}
+
+impl Gotify {
+    pub fn new(url: &str, token: &str) -> Self {
+        Gotify {
+            url: url.trim_end_matches('/').to_string(),
+            token: token.to_string(),
+        }
+    }
+}
+
+#[async_trait]
+impl Notifier for Gotify {
+    fn channel(&self) -> &str {
+        "gotify"
+    }
+
+    async fn send(&self, notification: &Notification) -> Result<(), Box<dyn Error + Send + Sync>> {
+        let payload = json!({
+            "title": notification.title(),
+            "message": notification.message(),
+            "priority": 5,
+            "extras": {
+                "client::notification": { "click": { "url": notification.link() } }
+            }
+        });
+
+        send_request(
+            CLIENT
+                .post(format!("{}/message", self.url))
+                .header("X-Gotify-Key", &self.token)
+                .json(&payload),
+        )
+        .await
+    }
+}
@@ -0,0 +1,110 @@
+use std::error::Error;
+
+use async_trait::async_trait;
+use lettre::message::header::ContentType;
+use lettre::message::Mailbox;
+use lettre::transport::smtp::authentication::Credentials;
+use lettre::{AsyncSmtpTransport, AsyncTransport, Message, Tokio1Executor};
+
+use super::{env_var, Notification, Notifier};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SmtpSecurity {
+    /// plain connection, for a local relay
+    None,
+    StartTls,
+    Tls,
+}
+
+#[derive(Debug, Clone)]
+pub struct SmtpConfig {
+    pub host: String,
+    pub port: u16,
+    pub security: SmtpSecurity,
+    pub credentials: Option<(String, String)>,
+    pub from: String,
+    pub to: String,
+}
+
+impl SmtpConfig {
+    pub fn from_env() -> Option<Self> {
+        let host = env_var("NOTIFY_SMTP_HOST")?;
+        let security = match env_var("NOTIFY_SMTP_SECURITY").as_deref() {
+            Some("none") => SmtpSecurity::None,
+            Some("tls") => SmtpSecurity::Tls,
+            _ => SmtpSecurity::StartTls,
+        };
+        let default_port = match security {
+            SmtpSecurity::None => 25,
+            SmtpSecurity::StartTls => 587,
+            SmtpSecurity::Tls => 465,
+        };
+
+        Some(SmtpConfig {
+            host,
+            port: env_var("NOTIFY_SMTP_PORT")
+                .and_then(|v| v.parse::<u16>().ok())
+                .unwrap_or(default_port),
+            security,
+            credentials: env_var("NOTIFY_SMTP_USER")
+                .map(|user| (user, env_var("NOTIFY_SMTP_PASSWORD").unwrap_or_default())),
+            from: env_var("NOTIFY_SMTP_FROM").unwrap_or("aubonmeeple@localhost".to_string()),
+            to: env_var("NOTIFY_SMTP_TO")?,
+        })
+    }
+}
+
+/// Mail notifications through an smtp relay
+pub struct Smtp {
+    transport: AsyncSmtpTransport<Tokio1Executor>,
+    from: Mailbox,
+    to: Mailbox,
+}
+
+impl Smtp {
+    pub fn new(config: SmtpConfig) -> Result<Self, Box<dyn Error + Send + Sync>> {
+        let mut builder = match config.security {
+            SmtpSecurity::None => {
+                AsyncSmtpTransport::<Tokio1Executor>::builder_dangerous(&config.host)
+            }
+            SmtpSecurity::StartTls => {
+                AsyncSmtpTransport::<Tokio1Executor>::starttls_relay(&config.host)?
+            }
+            SmtpSecurity::Tls => AsyncSmtpTransport::<Tokio1Executor>::relay(&config.host)?,
+        }
+        .port(config.port);
+
+        if let Some((user, password)) = config.credentials {
+            builder = builder.credentials(Credentials::new(user, password));
+        }
+
+        Ok(Smtp {
+            transport: builder.build(),
+            from: config.from.parse()?,
+            to: config.to.parse()?,
+        })
+    }
+}
+
+#[async_trait]
+impl Notifier for Smtp {
+    fn channel(&self) -> &str {
+        "smtp"
+    }
+
+    async fn send(&self, notification: &Notification) -> Result<(), Box<dyn Error + Send + Sync>> {
+        let email = Message::builder()
+            .from(self.from.clone())
+            .to(self.to.clone())
+            .subject(notification.title())
+            .header(ContentType::TEXT_PLAIN)
+            .body(format!(
+                "{}\n\n{}\n",
+                notification.message(),
+                notification.link()
+            ))?;
+
+        self.transport.send(email).await?;
+        Ok(())
+    }
+}
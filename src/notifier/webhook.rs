@@ -0,0 +1,94 @@
+use std::error::Error;
+
+use async_trait::async_trait;
+use serde_json::json;
+
+use super::{send_request, Notification, Notifier, CLIENT};
+
+/// Generic webhook, the notification is posted as is in JSON
+pub struct Webhook {
+    url: String,
+}
+
+impl Webhook {
+    pub fn new(url: &str) -> Self {
+        Webhook {
+            url: url.to_string(),
+        }
+    }
+}
+
+#[async_trait]
+impl Notifier for Webhook {
+    fn channel(&self) -> &str {
+        "webhook"
+    }
+
+    async fn send(&self, notification: &Notification) -> Result<(), Box<dyn Error + Send + Sync>> {
+        send_request(CLIENT.post(&self.url).json(notification)).await
+    }
+}
+
+/// Discord channel webhook
+pub struct Discord {
+    url: String,
+}
+
+impl Discord {
+    pub fn new(url: &str) -> Self {
+        Discord {
+            url: url.to_string(),
+        }
+    }
+}
+
+#[async_trait]
+impl Notifier for Discord {
+    fn channel(&self) -> &str {
+        "discord"
+    }
+
+    async fn send(&self, notification: &Notification) -> Result<(), Box<dyn Error + Send + Sync>> {
+        let payload = json!({
+            "username": "aubonmeeple",
+            "embeds": [{
+                "title": notification.title(),
+                "description": notification.message(),
+                "url": notification.link(),
+            }]
+        });
+        send_request(CLIENT.post(&self.url).json(&payload)).await
+    }
+}
+
+/// Slack incoming webhook
+pub struct Slack {
+    url: String,
+}
+
+impl Slack {
+    pub fn new(url: &str) -> Self {
+        Slack {
+            url: url.to_string(),
+        }
+    }
+}
+
+#[async_trait]
+impl Notifier for Slack {
+    fn channel(&self) -> &str {
+        "slack"
+    }
+
+    async fn send(&self, notification: &Notification) -> Result<(), Box<dyn Error + Send + Sync>> {
+        let payload = json!({
+            "text": format!(
+                "*<{}|{}>*\n{}",
+                notification.link(),
+                notification.title(),
+                notification.message()
+            )
+        });
+        send_request(CLIENT.post(&self.url).json(&payload)).await
+    }
+}
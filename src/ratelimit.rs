@@ -0,0 +1,278 @@
+use std::collections::HashMap;
+use std::num::{NonZeroU32, NonZeroUsize};
+use std::sync::{Arc, Mutex};
+use std::time::Instant;
+
+use governor::{DefaultDirectRateLimiter, Quota, RateLimiter};
+use lazy_static::lazy_static;
+use nonzero_ext::nonzero;
+use prometheus::{register_histogram_vec, HistogramVec};
+use serde::Deserialize;
+use tokio::sync::{OwnedSemaphorePermit, Semaphore};
+
+/// requests per minute of the hosts without a limit, if RATELIMIT_PER_MINUTE is not set
+const DEFAULT_REQUESTS_PER_MINUTE: NonZeroU32 = nonzero!(30u32);
+
+fn default_burst() -> NonZeroU32 {
+    nonzero!(1u32)
+}
+
+/// How hard a host can be queried
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct HostLimit {
+    pub requests_per_minute: NonZeroU32,
+    /// requests that can be sent at once after a quiet period
+    #[serde(default = "default_burst")]
+    pub burst: NonZeroU32,
+    /// requests waiting for their response at the same time, unlimited if not set
+    #[serde(default)]
+    pub max_concurrency: Option<NonZeroUsize>,
+}
+
+impl HostLimit {
+    fn quota(&self) -> Quota {
+        Quota::per_minute(self.requests_per_minute).allow_burst(self.burst)
+    }
+}
+
+/// The limits by host, read from the toml file pointed by RATELIMIT_CONFIG:
+/// ```toml
+/// [default]
+/// requests_per_minute = 30
+///
+/// [hosts."boardgamegeek.com"]
+/// requests_per_minute = 20
+/// burst = 2
+/// max_concurrency = 1
+/// ```
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct RateLimitConfig {
+    /// limit of the hosts not listed
+    #[serde(default = "RateLimitConfig::env_default")]
+    pub default: HostLimit,
+    #[serde(default)]
+    pub hosts: HashMap<String, HostLimit>,
+}
+
+impl Default for RateLimitConfig {
+    fn default() -> Self {
+        Self {
+            default: Self::env_default(),
+            hosts: HashMap::new(),
+        }
+    }
+}
+
+impl RateLimitConfig {
+    pub fn parse(content: &str) -> Result<Self, toml::de::Error> {
+        toml::from_str(content)
+    }
+
+    /// The default limit when none is configured: RATELIMIT_PER_MINUTE requests per
+    /// minute, one at a time
+    fn env_default() -> HostLimit {
+        let requests_per_minute = std::env::var("RATELIMIT_PER_MINUTE")
+            .map_err(|v| v.to_string())
+            .and_then(|v| v.parse::<NonZeroU32>().map_err(|v| v.to_string()))
+            .unwrap_or_else(|err| {
+                log::warn!(
+                    "Cannot initialize quota from environment, fallback to default: {}",
+                    err
+                );
+                DEFAULT_REQUESTS_PER_MINUTE
+            });
+
+        HostLimit {
+            requests_per_minute,
+            burst: default_burst(),
+            max_concurrency: None,
+        }
+    }
+
+    /// Read the file of RATELIMIT_CONFIG, every host sharing the default limit if it
+    /// is not set or cannot be read
+    pub fn from_env() -> Self {
+        let Ok(path) = std::env::var("RATELIMIT_CONFIG") else {
+            return Self::default();
+        };
+
+        std::fs::read_to_string(&path)
+            .map_err(|err| err.to_string())
+            .and_then(|content| Self::parse(&content).map_err(|err| err.to_string()))
+            .unwrap_or_else(|err| {
+                log::warn!(
+                    "Cannot read rate limits from {}, fallback to default: {}",
+                    path,
+                    err
+                );
+                Self::default()
+            })
+    }
+
+    pub fn limit(&self, host: &str) -> &HostLimit {
+        self.hosts.get(host).unwrap_or(&self.default)
+    }
+}
+
+struct HostLimiter {
+    rate: DefaultDirectRateLimiter,
+    concurrency: Option<Arc<Semaphore>>,
+}
+
+/// Rate and concurrency limiters of every host, created on the first request to a host
+pub struct HostLimiters {
+    config: RateLimitConfig,
+    hosts: Mutex<HashMap<String, Arc<HostLimiter>>>,
+}
+
+impl HostLimiters {
+    pub fn new(config: RateLimitConfig) -> Self {
+        log::debug!("Creating rate limiters with {:?}", config);
+        Self {
+            config,
+            hosts: Mutex::new(HashMap::new()),
+        }
+    }
+
+    fn limiter(&self, host: &str) -> Arc<HostLimiter> {
+        let mut hosts = self.hosts.lock().unwrap();
+        hosts
+            .entry(host.to_string())
+            .or_insert_with(|| {
+                let limit = self.config.limit(host);
+                Arc::new(HostLimiter {
+                    rate: RateLimiter::direct(limit.quota()),
+                    concurrency: limit
+                        .max_concurrency
+                        .map(|max| Arc::new(Semaphore::new(max.get()))),
+                })
+            })
+            .clone()
+    }
+
+    /// Wait until a request can be sent to the host. The returned permit holds a
+    /// concurrency slot of the host until it is dropped
+    pub async fn acquire(&self, host: &str) -> Option<OwnedSemaphorePermit> {
+        let limiter = self.limiter(host);
+        let start = Instant::now();
+
+        let permit = match &limiter.concurrency {
+            Some(semaphore) => Some(
+                semaphore
+                    .clone()
+                    .acquire_owned()
+                    .await
+                    .expect("host semaphore is never closed"),
+            ),
+            None => None,
+        };
+        limiter.rate.until_ready().await;
+
+        HTTP_LIMITER_WAIT
+            .with_label_values(&[host])
+            .observe(start.elapsed().as_secs_f64());
+        permit
+    }
+}
+
+lazy_static! {
+    static ref HTTP_LIMITER_WAIT: HistogramVec = register_histogram_vec!(
+        "http_limiter_wait_seconds",
+        "Time spent waiting for the rate and concurrency limits of a host",
+        &["host"],
+        vec![0.01, 0.1, 0.5, 1.0, 2.0, 5.0, 10.0, 30.0, 60.0, 120.0]
+    )
+    .unwrap();
+}
+
+#[cfg(test)]
+mod tests {
+    use std::num::{NonZeroU32, NonZeroUsize};
+    use std::time::Duration;
+
+    use super::{HostLimiters, RateLimitConfig};
+
+    const CONFIG: &str = r#"
+        [default]
+        requests_per_minute = 30
+
+        [hosts."www.okkazeo.com"]
+        requests_per_minute = 120
+        burst = 5
+
+        [hosts."boardgamegeek.com"]
+        requests_per_minute = 20
+        max_concurrency = 1
+    "#;
+
+    #[test]
+    fn test_parse() {
+        let config = RateLimitConfig::parse(CONFIG).unwrap();
+
+        let okkazeo = config.limit("www.okkazeo.com");
+        assert_eq!(okkazeo.requests_per_minute, NonZeroU32::new(120).unwrap());
+        assert_eq!(okkazeo.burst, NonZeroU32::new(5).unwrap());
+        assert_eq!(okkazeo.max_concurrency, None);
+
+        let bgg = config.limit("boardgamegeek.com");
+        assert_eq!(bgg.burst, NonZeroU32::new(1).unwrap());
+        assert_eq!(bgg.max_concurrency, NonZeroUsize::new(1));
+
+        let philibert = config.limit("www.philibert.net");
+        assert_eq!(philibert.requests_per_minute, NonZeroU32::new(30).unwrap());
+
+        assert!(RateLimitConfig::parse("[default]\nrequests_per_minute = 0").is_err());
+        assert!(RateLimitConfig::parse("[default]\nrequest_per_minute = 10").is_err());
+    }
+
+    #[tokio::test]
+    async fn test_burst() {
+        let limiters = HostLimiters::new(RateLimitConfig::parse(CONFIG).unwrap());
+
+        for _ in 0..5 {
+            tokio::time::timeout(
+                Duration::from_millis(50),
+                limiters.acquire("www.okkazeo.com"),
+            )
+            .await
+            .unwrap();
+        }
+        assert!(tokio::time::timeout(
+            Duration::from_millis(50),
+            limiters.acquire("www.okkazeo.com")
+        )
+        .await
+        .is_err());
+    }
+
+    #[tokio::test]
+    async fn test_concurrency() {
+        let mut config = RateLimitConfig::parse(CONFIG).unwrap();
+        config
+            .hosts
+            .get_mut("boardgamegeek.com")
+            .unwrap()
+            .requests_per_minute = NonZeroU32::new(6000).unwrap();
+        let limiters = HostLimiters::new(config);
+
+        let permit = limiters.acquire("boardgamegeek.com").await;
+        assert!(permit.is_some());
+        tokio::time::sleep(Duration::from_millis(20)).await;
+        assert!(tokio::time::timeout(
+            Duration::from_millis(50),
+            limiters.acquire("boardgamegeek.com")
+        )
+        .await
+        .is_err());
+
+        drop(permit);
+        tokio::time::timeout(
+            Duration::from_millis(50),
+            limiters.acquire("boardgamegeek.com"),
+        )
+        .await
+        .unwrap();
+    }
+}
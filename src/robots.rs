@@ -0,0 +1,230 @@
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use regex::Regex;
+
+/// how long a robots.txt is trusted before being fetched again
+pub const ROBOTS_TTL: Duration = Duration::from_secs(24 * 3600);
+/// how long a host is considered as forbidding everything when its server fails to serve
+/// its robots.txt (5xx), before trying again
+pub const ROBOTS_ERROR_TTL: Duration = Duration::from_secs(600);
+
+#[derive(Debug, Clone)]
+struct Rule {
+    allow: bool,
+    /// length of the path pattern, the longest matching rule wins
+    len: usize,
+    pattern: Regex,
+}
+
+/// The rules of a robots.txt (RFC 9309) applying to our user agent
+#[derive(Debug, Clone, Default)]
+pub struct RobotsTxt {
+    rules: Vec<Rule>,
+}
+
+impl RobotsTxt {
+    pub fn allow_all() -> Self {
+        Self::default()
+    }
+
+    pub fn disallow_all() -> Self {
+        Self {
+            rules: vec![Self::rule(false, "/").unwrap()],
+        }
+    }
+
+    fn rule(allow: bool, path: &str) -> Option<Rule> {
+        let (path, anchored) = match path.strip_suffix('$') {
+            Some(path) => (path, true),
+            None => (path, false),
+        };
+        let pattern = format!(
+            "^{}{}",
+            path.split('*')
+                .map(regex::escape)
+                .collect::<Vec<_>>()
+                .join(".*"),
+            if anchored { "$" } else { "" }
+        );
+
+        Some(Rule {
+            allow,
+            len: path.len(),
+            pattern: Regex::new(&pattern).ok()?,
+        })
+    }
+
+    /// Keep the rules of the groups naming our product token (the user agent up to its
+    /// first `/`), or of the `*` groups if none does
+    pub fn parse(content: &str, product: &str) -> Self {
+        let product = product.to_lowercase();
+        // user agents and rules of each group, a group ending when a user-agent line
+        // follows its rules
+        let mut groups: Vec<(Vec<String>, Vec<Rule>)> = Vec::new();
+        let mut in_rules = false;
+        for line in content.lines() {
+            let line = line.split('#').next().unwrap_or_default().trim();
+            let Some((key, value)) = line.split_once(':') else {
+                continue;
+            };
+            let value = value.trim();
+            match key.trim().to_lowercase().as_str() {
+                "user-agent" => {
+                    if in_rules || groups.is_empty() {
+                        groups.push((Vec::new(), Vec::new()));
+                        in_rules = false;
+                    }
+                    if let Some((agents, _)) = groups.last_mut() {
+                        agents.push(value.to_lowercase());
+                    }
+                }
+                key @ ("allow" | "disallow") => {
+                    in_rules = true;
+                    // an empty disallow allows everything
+                    if value.is_empty() {
+                        continue;
+                    }
+                    if let (Some((_, rules)), Some(rule)) =
+                        (groups.last_mut(), Self::rule(key == "allow", value))
+                    {
+                        rules.push(rule);
+                    }
+                }
+                _ => {}
+            }
+        }
+
+        let named = |agent: &str| {
+            groups
+                .iter()
+                .filter(|(agents, _)| agents.iter().any(|a| a == agent))
+                .flat_map(|(_, rules)| rules.iter().cloned())
+                .collect::<Vec<_>>()
+        };
+        let rules = if groups.iter().any(|(agents, _)| agents.contains(&product)) {
+            named(&product)
+        } else {
+            named("*")
+        };
+        Self { rules }
+    }
+
+    /// Whether a path (with its query) can be fetched: the longest matching rule decides,
+    /// an allow winning over a disallow of the same length
+    pub fn is_allowed(&self, path: &str) -> bool {
+        if path == "/robots.txt" {
+            return true;
+        }
+
+        self.rules
+            .iter()
+            .filter(|rule| rule.pattern.is_match(path))
+            .max_by_key(|rule| (rule.len, rule.allow))
+            .is_none_or(|rule| rule.allow)
+    }
+}
+
+/// The robots.txt of the hosts we query, by origin (`https://host:port`)
+#[derive(Default)]
+pub struct RobotsCache {
+    entries: Mutex<HashMap<String, (Instant, Arc<RobotsTxt>)>>,
+}
+
+impl RobotsCache {
+    pub fn get(&self, origin: &str) -> Option<Arc<RobotsTxt>> {
+        let entries = self.entries.lock().unwrap();
+        entries
+            .get(origin)
+            .filter(|(expires, _)| Instant::now() < *expires)
+            .map(|(_, robots)| robots.clone())
+    }
+
+    pub fn insert(&self, origin: &str, robots: RobotsTxt, ttl: Duration) -> Arc<RobotsTxt> {
+        let robots = Arc::new(robots);
+        self.entries
+            .lock()
+            .unwrap()
+            .insert(origin.to_string(), (Instant::now() + ttl, robots.clone()));
+        robots
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::{RobotsCache, RobotsTxt};
+
+    const ROBOTS: &str = "
+        # okkazeo
+        User-agent: *
+        Disallow: /membres/
+        Allow: /membres/viewProfil/
+        Disallow: /*?sort=
+        Disallow: /*.pdf$
+
+        User-agent: BadBot
+        User-agent: OtherBot
+        Disallow: /
+    ";
+
+    #[test]
+    fn test_generic_group() {
+        let robots = RobotsTxt::parse(ROBOTS, "aubonmeeple");
+
+        assert!(robots.is_allowed("/annonces/view/1234"));
+        assert!(!robots.is_allowed("/membres/stock/4242"));
+        assert!(robots.is_allowed("/membres/viewProfil/4242"));
+        assert!(!robots.is_allowed("/jeux/arrivages?sort=date"));
+        assert!(robots.is_allowed("/jeux/arrivages?page=2"));
+        assert!(!robots.is_allowed("/regles/catan.pdf"));
+        assert!(robots.is_allowed("/regles/catan.pdf.html"));
+    }
+
+    #[test]
+    fn test_specific_group() {
+        for product in ["BadBot", "otherbot"] {
+            let robots = RobotsTxt::parse(ROBOTS, product);
+            assert!(!robots.is_allowed("/annonces/view/1234"));
+            assert!(robots.is_allowed("/robots.txt"));
+        }
+
+        let robots = RobotsTxt::parse(
+            "User-agent: aubonmeeple\nDisallow:\n\nUser-agent: *\nDisallow: /",
+            "Aubonmeeple",
+        );
+        assert!(robots.is_allowed("/annonces/view/1234"));
+    }
+
+    #[test]
+    fn test_allow_and_disallow_all() {
+        assert!(RobotsTxt::allow_all().is_allowed("/annonces"));
+        assert!(RobotsTxt::parse("", "aubonmeeple").is_allowed("/annonces"));
+        assert!(!RobotsTxt::disallow_all().is_allowed("/annonces"));
+        assert!(!RobotsTxt::disallow_all().is_allowed("/"));
+    }
+
+    #[test]
+    fn test_cache() {
+        let cache = RobotsCache::default();
+        assert!(cache.get("https://www.okkazeo.com").is_none());
+
+        cache.insert(
+            "https://www.okkazeo.com",
+            RobotsTxt::disallow_all(),
+            Duration::from_secs(60),
+        );
+        cache.insert(
+            "https://boardgamegeek.com",
+            RobotsTxt::allow_all(),
+            Duration::ZERO,
+        );
+        assert!(!cache
+            .get("https://www.okkazeo.com")
+            .unwrap()
+            .is_allowed("/annonces"));
+        assert!(cache.get("https://boardgamegeek.com").is_none());
+    }
+}
@@ -0,0 +1,69 @@
+pub mod postgres;
+pub mod sqlite;
+
+use std::error::Error;
+use std::sync::Arc;
+
+use async_trait::async_trait;
+
+use crate::db::{create_db_pool, get_db_client, DbPool};
+use crate::frontlib::server::State;
+use crate::frontlib::Filters;
+use crate::game::{Game, Games};
+use crate::migrations::{migrate_db, MigrationMode};
+use crate::website::okkazeo::FeedState;
+
+use self::postgres::PostgresStorage;
+use self::sqlite::SqliteStorage;
+
+pub type StorageError = Box<dyn Error + Send + Sync>;
+
+/// Where the announces are stored and listed from, Postgres in production or SQLite
+/// for local development and tests
+#[async_trait]
+pub trait Storage: Send + Sync {
+    /// Insert a new announce or update a known one, with its seller, deal, shipping,
+    /// references and reviews
+    async fn upsert_game(&self, game: &Game) -> Result<(), StorageError>;
+
+    async fn select_game(&self, id: u32) -> Result<Option<Game>, StorageError>;
+
+    /// A page of the announces matching the filters, in the requested order
+    async fn select_games(&self, state: &State) -> Result<Games, StorageError>;
+
+    async fn count_games(&self, filters: &Filters) -> Result<i64, StorageError>;
+
+    /// Distinct announce names close to the beginning of a search
+    async fn select_name_suggestions(
+        &self,
+        query: &str,
+        limit: i64,
+    ) -> Result<Vec<String>, StorageError>;
+
+    async fn delete_game(&self, id: u32) -> Result<(), StorageError>;
+
+    /// Make the announces upserted or deleted since the last refresh visible in the listing
+    async fn refresh_listing(&self) -> Result<(), StorageError>;
+
+    /// The most recent entry handled of a feed, if it was ever polled
+    async fn select_feed_state(&self, feed: &str) -> Result<Option<FeedState>, StorageError>;
+
+    async fn update_feed_state(&self, feed: &str, state: &FeedState) -> Result<(), StorageError>;
+}
+
+/// Open the storage configured by DB_URL. A `sqlite:` url (`sqlite:dev.db`,
+/// `sqlite::memory:`) opens a SQLite database, anything else is a Postgres connection
+/// string, migrated as per DB_MIGRATE. The Postgres pool is returned along for the
+/// features only available on Postgres (saved searches, wishlist, sellers, notifications)
+pub async fn open_storage() -> Result<(Arc<dyn Storage>, Option<DbPool>), anyhow::Error> {
+    let db_url = std::env::var("DB_URL").map_err(|_| anyhow::anyhow!("DB_URL is not defined"))?;
+    if let Some(path) = db_url.strip_prefix("sqlite:") {
+        log::info!("using SQLite storage {}", path);
+        return Ok((Arc::new(SqliteStorage::open(path)?), None));
+    }
+
+    let pool = create_db_pool()?;
+    let db_client = get_db_client(&pool).await?;
+    migrate_db(&db_client, MigrationMode::from_env()).await?;
+    Ok((Arc::new(PostgresStorage::new(pool.clone())), Some(pool)))
+}
@@ -0,0 +1,77 @@
+use async_trait::async_trait;
+
+use crate::db::{
+    delete_from_all_table_with_id, get_db_client, refresh_listable_game_view,
+    select_count_filtered_games_from_db, select_feed_state_from_db, select_game_with_id_from_db,
+    select_games_from_db, select_name_suggestions_from_db, upsert_announce_into_db,
+    upsert_into_feed_state_table, DbPool,
+};
+use crate::frontlib::server::State;
+use crate::frontlib::Filters;
+use crate::game::{Game, Games};
+use crate::website::okkazeo::FeedState;
+
+use super::{Storage, StorageError};
+
+/// Storage on the Postgres database, a connection being taken from the pool for each call
+pub struct PostgresStorage {
+    pool: DbPool,
+}
+
+impl PostgresStorage {
+    pub fn new(pool: DbPool) -> Self {
+        PostgresStorage { pool }
+    }
+}
+
+#[async_trait]
+impl Storage for PostgresStorage {
+    async fn upsert_game(&self, game: &Game) -> Result<(), StorageError> {
+        let mut db_client = get_db_client(&self.pool).await?;
+        Ok(upsert_announce_into_db(&mut db_client, game).await?)
+    }
+
+    async fn select_game(&self, id: u32) -> Result<Option<Game>, StorageError> {
+        let db_client = get_db_client(&self.pool).await?;
+        Ok(select_game_with_id_from_db(&db_client, id).await)
+    }
+
+    async fn select_games(&self, state: &State) -> Result<Games, StorageError> {
+        let db_client = get_db_client(&self.pool).await?;
+        Ok(select_games_from_db(&db_client, state).await?)
+    }
+
+    async fn count_games(&self, filters: &Filters) -> Result<i64, StorageError> {
+        let db_client = get_db_client(&self.pool).await?;
+        Ok(select_count_filtered_games_from_db(&db_client, filters.clone()).await?)
+    }
+
+    async fn select_name_suggestions(
+        &self,
+        query: &str,
+        limit: i64,
+    ) -> Result<Vec<String>, StorageError> {
+        let db_client = get_db_client(&self.pool).await?;
+        Ok(select_name_suggestions_from_db(&db_client, query, limit).await?)
+    }
+
+    async fn delete_game(&self, id: u32) -> Result<(), StorageError> {
+        let mut db_client = get_db_client(&self.pool).await?;
+        Ok(delete_from_all_table_with_id(&mut db_client, id as i32).await?)
+    }
+
+    async fn refresh_listing(&self) -> Result<(), StorageError> {
+        let db_client = get_db_client(&self.pool).await?;
+        Ok(refresh_listable_game_view(&db_client).await?)
+    }
+
+    async fn select_feed_state(&self, feed: &str) -> Result<Option<FeedState>, StorageError> {
+        let db_client = get_db_client(&self.pool).await?;
+        Ok(select_feed_state_from_db(&db_client, feed).await?)
+    }
+
+    async fn update_feed_state(&self, feed: &str, state: &FeedState) -> Result<(), StorageError> {
+        let db_client = get_db_client(&self.pool).await?;
+        Ok(upsert_into_feed_state_table(&db_client, feed, state).await?)
+    }
+}
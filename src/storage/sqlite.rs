@@ -0,0 +1,553 @@
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+
+use async_trait::async_trait;
+use rusqlite::functions::FunctionFlags;
+use rusqlite::types::Value;
+use rusqlite::{params, params_from_iter, Connection, OptionalExtension, Row};
+use unidecode::unidecode;
+
+use crate::frontlib::server::State;
+use crate::frontlib::Filters;
+use crate::game::{Deal, Game, Games, OkkazeoAnnounce, Reference, Review, Reviewer, Seller};
+use crate::website::okkazeo::FeedState;
+
+use super::{Storage, StorageError};
+
+/// The tables of the Postgres schema needed by the listing, with the same column names
+const SCHEMA: &str = r#"
+PRAGMA foreign_keys = ON;
+
+CREATE TABLE IF NOT EXISTS seller (
+  seller_id INTEGER PRIMARY KEY,
+  seller_name TEXT,
+  seller_url TEXT,
+  seller_nb_announces INTEGER,
+  seller_is_pro INTEGER,
+  seller_rating REAL,
+  seller_nb_ratings INTEGER
+);
+
+CREATE TABLE IF NOT EXISTS okkazeo_announce (
+  oa_id INTEGER PRIMARY KEY,
+  oa_last_modification_date TEXT NOT NULL,
+  oa_name TEXT NOT NULL,
+  oa_image TEXT NOT NULL,
+  oa_price REAL NOT NULL,
+  oa_url TEXT NOT NULL,
+  oa_extension TEXT,
+  oa_seller INTEGER REFERENCES seller(seller_id),
+  oa_barcode INTEGER,
+  oa_city TEXT
+);
+
+CREATE TABLE IF NOT EXISTS deal (
+  deal_oa_id INTEGER PRIMARY KEY REFERENCES okkazeo_announce(oa_id) ON DELETE CASCADE,
+  deal_price INTEGER,
+  deal_percentage INTEGER
+);
+
+CREATE TABLE IF NOT EXISTS reference (
+  ref_oa_id INTEGER REFERENCES okkazeo_announce(oa_id) ON DELETE CASCADE,
+  ref_name TEXT,
+  ref_price REAL,
+  ref_url TEXT,
+  PRIMARY KEY (ref_oa_id, ref_name)
+);
+
+CREATE TABLE IF NOT EXISTS reviewer (
+  reviewer_oa_id INTEGER REFERENCES okkazeo_announce(oa_id) ON DELETE CASCADE,
+  reviewer_name TEXT,
+  reviewer_url TEXT,
+  reviewer_note REAL,
+  reviewer_number INTEGER,
+  PRIMARY KEY (reviewer_oa_id, reviewer_name)
+);
+
+CREATE TABLE IF NOT EXISTS shipping (
+  ship_oa_id INTEGER REFERENCES okkazeo_announce(oa_id) ON DELETE CASCADE,
+  ship_shipper TEXT,
+  ship_price REAL,
+  PRIMARY KEY (ship_oa_id, ship_shipper)
+);
+
+CREATE TABLE IF NOT EXISTS feed_state (
+  fs_feed TEXT PRIMARY KEY,
+  fs_last_id INTEGER NOT NULL,
+  fs_last_date TEXT NOT NULL
+);
+"#;
+
+const SELECT_GAMES: &str = "SELECT *
+    FROM okkazeo_announce oa
+    JOIN deal d ON d.deal_oa_id = oa.oa_id
+    JOIN seller s ON s.seller_id = oa.oa_seller";
+
+/// Storage on a SQLite database, for local development and tests. The name search is
+/// a plain substring match, and the listing is always up to date
+pub struct SqliteStorage {
+    conn: Arc<Mutex<Connection>>,
+}
+
+impl SqliteStorage {
+    /// Open (or create) the database at path, `:memory:` opening an in-memory one
+    pub fn open(path: &str) -> Result<Self, rusqlite::Error> {
+        let conn = if path == ":memory:" {
+            Connection::open_in_memory()?
+        } else {
+            Connection::open(path)?
+        };
+        // there is no unaccent extension in SQLite
+        conn.create_scalar_function(
+            "unaccent",
+            1,
+            FunctionFlags::SQLITE_UTF8 | FunctionFlags::SQLITE_DETERMINISTIC,
+            |ctx| Ok(ctx.get::<Option<String>>(0)?.map(|s| unidecode(&s))),
+        )?;
+        conn.execute_batch(SCHEMA)?;
+
+        Ok(SqliteStorage {
+            conn: Arc::new(Mutex::new(conn)),
+        })
+    }
+
+    /// Run queries on the connection, out of the async runtime
+    async fn call<T, F>(&self, f: F) -> Result<T, StorageError>
+    where
+        T: Send + 'static,
+        F: FnOnce(&mut Connection) -> Result<T, rusqlite::Error> + Send + 'static,
+    {
+        let conn = self.conn.clone();
+        let res = tokio::task::spawn_blocking(move || f(&mut conn.lock().unwrap())).await?;
+        Ok(res?)
+    }
+}
+
+fn upsert_game(conn: &mut Connection, game: &Game) -> Result<(), rusqlite::Error> {
+    let announce = &game.okkazeo_announce;
+    let seller = &announce.seller;
+    let id = announce.id;
+    let transaction = conn.transaction()?;
+
+    // an unknown rating (the profile could not be fetched) keeps the stored one
+    let rating = seller.known_rating();
+    transaction.execute(
+        "INSERT INTO seller (seller_id, seller_name, seller_url, seller_nb_announces, seller_is_pro, seller_rating, seller_nb_ratings)
+            VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)
+            ON CONFLICT (seller_id) DO UPDATE SET
+            seller_nb_announces = excluded.seller_nb_announces,
+            seller_rating = COALESCE(excluded.seller_rating, seller_rating),
+            seller_nb_ratings = COALESCE(excluded.seller_nb_ratings, seller_nb_ratings)",
+        params![
+            seller.id,
+            seller.name,
+            seller.url,
+            seller.nb_announces,
+            seller.is_pro,
+            rating.map(|(rating, _)| rating),
+            rating.map(|(_, nb_ratings)| nb_ratings)
+        ],
+    )?;
+    transaction.execute(
+        "INSERT INTO okkazeo_announce (oa_id, oa_last_modification_date, oa_name, oa_image, oa_price, oa_url, oa_extension, oa_seller, oa_barcode, oa_city)
+            VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10)
+            ON CONFLICT (oa_id) DO UPDATE SET
+            oa_last_modification_date = excluded.oa_last_modification_date,
+            oa_name = excluded.oa_name,
+            oa_image = excluded.oa_image,
+            oa_price = excluded.oa_price,
+            oa_url = excluded.oa_url,
+            oa_extension = excluded.oa_extension,
+            oa_seller = excluded.oa_seller,
+            oa_barcode = excluded.oa_barcode,
+            oa_city = excluded.oa_city",
+        params![
+            id,
+            announce.last_modification_date,
+            announce.name,
+            announce.image,
+            announce.price,
+            announce.url,
+            announce.extension,
+            seller.id,
+            announce.barcode.unwrap_or_default() as i64,
+            announce.city.as_deref().unwrap_or_default()
+        ],
+    )?;
+    transaction.execute(
+        "INSERT INTO deal (deal_oa_id, deal_price, deal_percentage) VALUES (?1, ?2, ?3)
+            ON CONFLICT (deal_oa_id) DO UPDATE SET
+            deal_price = excluded.deal_price,
+            deal_percentage = excluded.deal_percentage",
+        params![id, game.deal.deal_price, game.deal.deal_percentage],
+    )?;
+
+    transaction.execute("DELETE FROM shipping WHERE ship_oa_id = ?1", [id])?;
+    for (shipper, price) in announce.shipping.iter() {
+        transaction.execute(
+            "INSERT INTO shipping (ship_oa_id, ship_shipper, ship_price) VALUES (?1, ?2, ?3)",
+            params![id, shipper, price],
+        )?;
+    }
+    transaction.execute("DELETE FROM reference WHERE ref_oa_id = ?1", [id])?;
+    for reference in game.references.values() {
+        transaction.execute(
+            "INSERT INTO reference (ref_oa_id, ref_name, ref_price, ref_url) VALUES (?1, ?2, ?3, ?4)",
+            params![id, reference.name, reference.price, reference.url],
+        )?;
+    }
+    transaction.execute("DELETE FROM reviewer WHERE reviewer_oa_id = ?1", [id])?;
+    for reviewer in game.review.reviews.values() {
+        transaction.execute(
+            "INSERT INTO reviewer (reviewer_oa_id, reviewer_name, reviewer_url, reviewer_note, reviewer_number)
+                VALUES (?1, ?2, ?3, ?4, ?5)",
+            params![id, reviewer.name, reviewer.url, reviewer.note, reviewer.number],
+        )?;
+    }
+
+    transaction.commit()
+}
+
+/// Build a game from an announce row joined with its deal and seller, without its
+/// shipping, references and reviews
+fn game_from_row(row: &Row) -> Result<Game, rusqlite::Error> {
+    let barcode: Option<i64> = row.get("oa_barcode")?;
+
+    Ok(Game {
+        okkazeo_announce: OkkazeoAnnounce {
+            id: row.get("oa_id")?,
+            name: row.get("oa_name")?,
+            image: row.get("oa_image")?,
+            price: row.get("oa_price")?,
+            url: row.get("oa_url")?,
+            extension: row
+                .get::<_, Option<String>>("oa_extension")?
+                .unwrap_or_default(),
+            shipping: HashMap::new(),
+            seller: Seller {
+                id: row.get("seller_id")?,
+                name: row.get("seller_name")?,
+                url: row.get("seller_url")?,
+                nb_announces: row.get::<_, i64>("seller_nb_announces")? as u32,
+                is_pro: row.get("seller_is_pro")?,
+                rating: row
+                    .get::<_, Option<f32>>("seller_rating")?
+                    .unwrap_or_default(),
+                nb_ratings: row
+                    .get::<_, Option<u32>>("seller_nb_ratings")?
+                    .unwrap_or_default(),
+            },
+            barcode: barcode.map(|barcode| barcode as u64),
+            city: row.get("oa_city")?,
+            last_modification_date: row.get("oa_last_modification_date")?,
+        },
+        references: HashMap::new(),
+        review: Review::default(),
+        deal: Deal {
+            deal_price: row.get("deal_price")?,
+            deal_percentage: row.get("deal_percentage")?,
+        },
+    })
+}
+
+/// Fill a game with its shipping, references and reviews
+fn complete_game(conn: &Connection, game: &mut Game) -> Result<(), rusqlite::Error> {
+    let id = game.okkazeo_announce.id;
+
+    let mut stmt =
+        conn.prepare_cached("SELECT ship_shipper, ship_price FROM shipping WHERE ship_oa_id = ?1")?;
+    game.okkazeo_announce.shipping = stmt
+        .query_map([id], |row| Ok((row.get(0)?, row.get(1)?)))?
+        .collect::<Result<_, _>>()?;
+
+    let mut stmt = conn.prepare_cached(
+        "SELECT ref_name, ref_price, ref_url FROM reference WHERE ref_oa_id = ?1",
+    )?;
+    game.references = stmt
+        .query_map([id], |row| {
+            let name: String = row.get(0)?;
+            Ok((
+                name.clone(),
+                Reference {
+                    name,
+                    price: row.get(1)?,
+                    url: row.get(2)?,
+                },
+            ))
+        })?
+        .collect::<Result<_, _>>()?;
+
+    let mut stmt = conn.prepare_cached(
+        "SELECT reviewer_name, reviewer_url, reviewer_note, reviewer_number
+            FROM reviewer WHERE reviewer_oa_id = ?1",
+    )?;
+    game.review.reviews = stmt
+        .query_map([id], |row| {
+            let name: String = row.get(0)?;
+            Ok((
+                name.clone(),
+                Reviewer {
+                    name,
+                    url: row.get(1)?,
+                    note: row.get(2)?,
+                    number: row.get(3)?,
+                },
+            ))
+        })?
+        .collect::<Result<_, _>>()?;
+    game.review.compute_average_note();
+
+    Ok(())
+}
+
+fn select_games(
+    conn: &Connection,
+    req: &str,
+    params: Vec<Value>,
+) -> Result<Games, rusqlite::Error> {
+    let mut stmt = conn.prepare(req)?;
+    let mut games = stmt
+        .query_map(params_from_iter(params), game_from_row)?
+        .collect::<Result<Vec<Game>, _>>()?;
+    for game in games.iter_mut() {
+        complete_game(conn, game)?;
+    }
+
+    Ok(Games {
+        games: games.into_iter().map(Box::new).collect(),
+    })
+}
+
+/// WHERE clause selecting the announces matching the filters, with its parameters.
+/// Same filters as the Postgres `FilterQuery`, on the tables instead of the view
+fn filters_sql(filters: &Filters) -> (String, Vec<Value>) {
+    let mut conditions = Vec::new();
+    let mut params = Vec::new();
+
+    if let Some(name) = filters
+        .name
+        .as_deref()
+        .filter(|name| !name.trim().is_empty())
+    {
+        conditions.push("unaccent(oa.oa_name) LIKE unaccent(?)".to_string());
+        params.push(Value::from(format!("%{}%", name)));
+    }
+
+    // if the city filter is a number, it is a postcode. Okkazeo format for city is : "city (postcode)"
+    let match_start = if filters
+        .city
+        .as_ref()
+        .is_some_and(|city| city.parse::<i32>().is_ok())
+    {
+        "("
+    } else {
+        ""
+    };
+    conditions.push("unaccent(oa.oa_city) LIKE unaccent(?)".to_string());
+    params.push(Value::from(format!(
+        "%{}{}%",
+        match_start,
+        filters.city.as_deref().unwrap_or_default()
+    )));
+    conditions.push("unaccent(s.seller_name) LIKE unaccent(?)".to_string());
+    params.push(Value::from(format!(
+        "%{}%",
+        filters.vendor.as_deref().unwrap_or_default()
+    )));
+    conditions.push("oa.oa_price > ? AND oa.oa_price < ?".to_string());
+    params.push(Value::from(filters.min_price.unwrap_or_default() as f64));
+    params.push(Value::from(filters.max_price.unwrap_or(10000) as f64));
+
+    if filters.pro.is_some() {
+        conditions.push("NOT s.seller_is_pro".to_string());
+    }
+
+    conditions.push(
+        "((? AND oa.oa_extension = 'Jeu') OR
+            (? AND oa.oa_extension = 'Extension') OR
+            (? AND oa.oa_extension = 'Jeu + Extension') OR
+            (? AND oa.oa_extension NOT IN ('Jeu', 'Jeu + Extension', 'Extension')))"
+            .to_string(),
+    );
+    params.push(Value::from(filters.type_game));
+    params.push(Value::from(filters.type_ext));
+    params.push(Value::from(filters.type_game_ext));
+    params.push(Value::from(filters.type_misc));
+
+    if let Some(date) = &filters.date {
+        // dates are stored as "YYYY-MM-DD HH:MM:SS", so that they compare as text
+        conditions.push("oa.oa_last_modification_date >= ?".to_string());
+        params.push(Value::from(date.clone()));
+    }
+
+    if filters.delivery.is_some() {
+        conditions.push(
+            "EXISTS (SELECT 1 FROM shipping WHERE ship_oa_id = oa.oa_id AND ship_shipper != 'hand_delivery')"
+                .to_string(),
+        );
+    }
+
+    if let Some(rating) = filters.seller_rating {
+        conditions.push("s.seller_rating >= ?".to_string());
+        params.push(Value::from(rating as f64));
+    }
+
+    if let Some(note) = filters.note {
+        // announces without any review count as 0
+        conditions.push(
+            "(SELECT COALESCE(SUM(CASE WHEN reviewer_number > 0 THEN reviewer_note * reviewer_number ELSE 0 END)
+                / SUM(CASE WHEN reviewer_number > 0 THEN reviewer_number ELSE 1 END), 0)
+                FROM reviewer WHERE reviewer_oa_id = oa.oa_id) >= ?"
+                .to_string(),
+        );
+        params.push(Value::from(note as f64));
+    }
+
+    (format!("WHERE {}", conditions.join(" AND ")), params)
+}
+
+#[async_trait]
+impl Storage for SqliteStorage {
+    async fn upsert_game(&self, game: &Game) -> Result<(), StorageError> {
+        let game = game.clone();
+        self.call(move |conn| upsert_game(conn, &game)).await
+    }
+
+    async fn select_game(&self, id: u32) -> Result<Option<Game>, StorageError> {
+        self.call(move |conn| {
+            let mut game = conn
+                .query_row(
+                    &format!("{} WHERE oa.oa_id = ?1", SELECT_GAMES),
+                    [id],
+                    game_from_row,
+                )
+                .optional()?;
+            if let Some(game) = game.as_mut() {
+                complete_game(conn, game)?;
+            }
+            Ok(game)
+        })
+        .await
+    }
+
+    async fn select_games(&self, state: &State) -> Result<Games, StorageError> {
+        let state = state.clone();
+        self.call(move |conn| {
+            let (where_sql, mut params) = filters_sql(&state.filters);
+            let order_by = match state.sort.sort.as_str() {
+                "price" => "d.deal_price ASC",
+                "percent" => "d.deal_percentage ASC",
+                _ => "oa.oa_last_modification_date DESC",
+            };
+            // names starting with the searched one first
+            let order_by = match state.filters.name.as_deref() {
+                Some(name) if !name.trim().is_empty() => {
+                    params.push(Value::from(format!("{}%", name)));
+                    format!("unaccent(oa.oa_name) LIKE unaccent(?) DESC, {}", order_by)
+                }
+                _ => order_by.to_string(),
+            };
+            params.push(Value::from(state.pagination.per_page as i64));
+            params.push(Value::from(
+                (state.pagination.page * state.pagination.per_page) as i64,
+            ));
+
+            select_games(
+                conn,
+                &format!(
+                    "{} {} ORDER BY {} LIMIT ? OFFSET ?",
+                    SELECT_GAMES, where_sql, order_by
+                ),
+                params,
+            )
+        })
+        .await
+    }
+
+    async fn count_games(&self, filters: &Filters) -> Result<i64, StorageError> {
+        let (where_sql, params) = filters_sql(filters);
+        self.call(move |conn| {
+            conn.query_row(
+                &format!(
+                    "SELECT COUNT(*) FROM okkazeo_announce oa
+                        JOIN deal d ON d.deal_oa_id = oa.oa_id
+                        JOIN seller s ON s.seller_id = oa.oa_seller {}",
+                    where_sql
+                ),
+                params_from_iter(params),
+                |row| row.get(0),
+            )
+        })
+        .await
+    }
+
+    async fn select_name_suggestions(
+        &self,
+        query: &str,
+        limit: i64,
+    ) -> Result<Vec<String>, StorageError> {
+        let query = query.to_string();
+        self.call(move |conn| {
+            let mut stmt = conn.prepare(
+                "SELECT DISTINCT oa_name FROM okkazeo_announce
+                    WHERE unaccent(oa_name) LIKE unaccent(?1)
+                    ORDER BY unaccent(oa_name) LIKE unaccent(?2) DESC, oa_name
+                    LIMIT ?3",
+            )?;
+            let names = stmt
+                .query_map(
+                    params![format!("%{}%", query), format!("{}%", query), limit],
+                    |row| row.get(0),
+                )?
+                .collect();
+            names
+        })
+        .await
+    }
+
+    async fn delete_game(&self, id: u32) -> Result<(), StorageError> {
+        self.call(move |conn| {
+            conn.execute("DELETE FROM okkazeo_announce WHERE oa_id = ?1", [id])
+                .map(|_| ())
+        })
+        .await
+    }
+
+    async fn refresh_listing(&self) -> Result<(), StorageError> {
+        // the listing reads the tables directly
+        Ok(())
+    }
+
+    async fn select_feed_state(&self, feed: &str) -> Result<Option<FeedState>, StorageError> {
+        let feed = feed.to_string();
+        self.call(move |conn| {
+            conn.query_row(
+                "SELECT fs_last_id, fs_last_date FROM feed_state WHERE fs_feed = ?1",
+                [feed],
+                |row| {
+                    Ok(FeedState {
+                        last_id: row.get("fs_last_id")?,
+                        last_date: row.get("fs_last_date")?,
+                    })
+                },
+            )
+            .optional()
+        })
+        .await
+    }
+
+    async fn update_feed_state(&self, feed: &str, state: &FeedState) -> Result<(), StorageError> {
+        let (feed, state) = (feed.to_string(), state.clone());
+        self.call(move |conn| {
+            conn.execute(
+                "INSERT INTO feed_state (fs_feed, fs_last_id, fs_last_date) VALUES (?1, ?2, ?3)
+                    ON CONFLICT (fs_feed) DO UPDATE SET
+                    fs_last_id = excluded.fs_last_id,
+                    fs_last_date = excluded.fs_last_date",
+                params![feed, state.last_id, state.last_date],
+            )?;
+            Ok(())
+        })
+        .await
+    }
+}
@@ -1,10 +1,14 @@
 use scraper::{Html, Selector};
 
-use crate::{httpclient, website::helper::are_names_similar};
+use crate::{
+    error::ScrapeError,
+    httpclient,
+    website::helper::{are_names_similar, expect_results_list, ProductsCheck},
+};
 
 pub async fn get_agorajeux_price_and_url_by_name(
     name: &str,
-) -> Result<Option<(f32, String)>, anyhow::Error> {
+) -> Result<Option<(f32, String)>, ScrapeError> {
     let name_clean = normalize_agorajeux_name(name);
     let search = format!(
         "https://www.agorajeux.com/fr/recherche?controller=search&s={}",
@@ -17,53 +21,67 @@ pub async fn get_agorajeux_price_and_url_by_name(
     );
 
     let (doc, _) = httpclient::get_doc(&search).await?;
-    Ok(parse_agorajeux_document(name, &doc))
+    parse_agorajeux_document(name, &doc)
 }
 
 fn normalize_agorajeux_name(name: &str) -> String {
     name.replace('&', " ")
 }
 
-fn parse_agorajeux_document(name: &str, document: &Html) -> Option<(f32, String)> {
+fn parse_agorajeux_document(
+    name: &str,
+    document: &Html,
+) -> Result<Option<(f32, String)>, ScrapeError> {
+    let href_css = "a.thumbnail.product-thumbnail";
+    let price_css = ".product-price-and-shipping .price";
+    let product_name_css = "span.h3.product-title a";
     let product_selector = Selector::parse(".js-product-miniature").unwrap();
-    let href_selector = Selector::parse("a.thumbnail.product-thumbnail").unwrap();
-    let price_selector = Selector::parse(".product-price-and-shipping .price").unwrap();
-    let product_name_selector = Selector::parse("span.h3.product-title a").unwrap();
+    let href_selector = Selector::parse(href_css).unwrap();
+    let price_selector = Selector::parse(price_css).unwrap();
+    let product_name_selector = Selector::parse(product_name_css).unwrap();
 
     log::trace!("parsing agorajeux document for {}", name);
+    expect_results_list("agorajeux", document, "#products")
+        .inspect_err(|_| AGORAJEUX_STAT.with_label_values(&["broken"]).inc())?;
+
+    let mut check = ProductsCheck::default();
     for product in document.select(&product_selector) {
-        let href_element = product.select(&href_selector).next();
-        if let Some(href) = href_element {
-            let href_attr = href.value().attr("href").unwrap_or_default();
-            log::trace!("href : {}", href_attr);
-
-            let price_element = product.select(&price_selector).next();
-            if let Some(price) = price_element {
-                let price_text = price.text().collect::<String>();
-                let mut price = price_text.trim().replace(',', ".");
-                // this is ugly but I dont know why others technics dont work to remove the last 2 chars here
-                price.pop();
-                price.pop();
-                log::trace!("price : {}", price);
-                let price = price.parse::<f32>().unwrap_or(0.0);
-
-                let product_name_element = product.select(&product_name_selector).next();
-                if let Some(product_name) = product_name_element {
-                    let processed_name = product_name.text().collect::<String>();
-                    if are_names_similar(processed_name.as_str(), name) {
-                        AGORAJEUX_STAT.with_label_values(&["success"]).inc();
-                        return Some((price, href_attr.to_string()));
-                    }
-                }
-            } else {
-                log::trace!("fail to select price");
-            }
-        } else {
-            log::trace!("fail to select href");
+        let Some(href) = product.select(&href_selector).next() else {
+            check.missing(href_css);
+            continue;
+        };
+        let href_attr = href.value().attr("href").unwrap_or_default();
+        log::trace!("href : {}", href_attr);
+
+        let Some(price) = product.select(&price_selector).next() else {
+            check.missing(price_css);
+            continue;
+        };
+        let price_text = price.text().collect::<String>();
+        let mut price = price_text.trim().replace(',', ".");
+        // this is ugly but I dont know why others technics dont work to remove the last 2 chars here
+        price.pop();
+        price.pop();
+        log::trace!("price : {}", price);
+        let price = price.parse::<f32>().unwrap_or(0.0);
+
+        let Some(product_name) = product.select(&product_name_selector).next() else {
+            check.missing(product_name_css);
+            continue;
+        };
+        check.complete();
+        let processed_name = product_name.text().collect::<String>();
+        if are_names_similar(processed_name.as_str(), name) {
+            AGORAJEUX_STAT.with_label_values(&["success"]).inc();
+            return Ok(Some((price, href_attr.to_string())));
         }
     }
+    check
+        .check("agorajeux")
+        .inspect_err(|_| AGORAJEUX_STAT.with_label_values(&["broken"]).inc())?;
+
     AGORAJEUX_STAT.with_label_values(&["fail"]).inc();
-    None
+    Ok(None)
 }
 
 use lazy_static::lazy_static;
@@ -71,7 +89,7 @@ use prometheus::{register_int_counter_vec, IntCounterVec};
 lazy_static! {
     static ref AGORAJEUX_STAT: IntCounterVec = register_int_counter_vec!(
         "agorajeux_stat",
-        "Stat about parsing/fetch success/fail for this website",
+        "Stat about parsing/fetch success/fail/broken for this website",
         &["result"]
     )
     .unwrap();
@@ -80,6 +98,7 @@ lazy_static! {
 #[cfg(test)]
 mod tests {
     use super::{normalize_agorajeux_name, parse_agorajeux_document};
+    use crate::error::ScrapeError;
     use log::Level;
     use std::{env, fs};
 
@@ -130,7 +149,8 @@ mod tests {
             let doc =
                 fs::read_to_string(test.document).expect("Should have been able to read the file");
             let document = scraper::Html::parse_document(&doc);
-            if let Some((price, href)) = parse_agorajeux_document(&(name_clean), &document) {
+            if let Some((price, href)) = parse_agorajeux_document(&(name_clean), &document).unwrap()
+            {
                 assert_eq!(price, test.price);
                 assert_eq!(href, test.href);
             } else {
@@ -138,4 +158,28 @@ mod tests {
             }
         }
     }
+
+    #[test]
+    fn test_broken_layout() {
+        let doc = fs::read_to_string("tests/agorajeux/test1.html")
+            .expect("Should have been able to read the file");
+        let document = scraper::Html::parse_document(&doc);
+        assert_eq!(
+            parse_agorajeux_document("Les Aventuriers du Rail", &document).unwrap(),
+            None
+        );
+
+        // the prices moved
+        let document = scraper::Html::parse_document(
+            &doc.replace("product-price-and-shipping", "product-prices"),
+        );
+        assert!(matches!(
+            parse_agorajeux_document("Break In Tour Eiffel", &document),
+            Err(ScrapeError::SelectorMiss { selector, .. }) if selector == ".product-price-and-shipping .price"
+        ));
+
+        // not a search page anymore
+        let document = scraper::Html::parse_document("<html><body>Access denied</body></html>");
+        assert!(parse_agorajeux_document("Break In Tour Eiffel", &document).is_err());
+    }
 }
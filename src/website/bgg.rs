@@ -1,12 +1,18 @@
+use regex::Regex;
 use scraper::{Html, Selector};
 
 use crate::{
+    error::ScrapeError,
     game::Reviewer,
     httpclient,
-    website::helper::{are_names_similar, clean_name},
+    website::helper::{are_names_similar, clean_name, expect_results_list},
+    wishlist::WishlistItem,
 };
 
-pub async fn get_bgg_note(name: &str) -> Result<Option<Reviewer>, anyhow::Error> {
+/// number of times we ask for a collection while bgg is still preparing it
+const COLLECTION_MAX_ATTEMPTS: u32 = 5;
+
+pub async fn get_bgg_note(name: &str) -> Result<Option<Reviewer>, ScrapeError> {
     let name = clean_name(name);
     let search = format!(
         "https://boardgamegeek.com/geeksearch.php?action=search&objecttype=boardgame&q={}",
@@ -14,20 +20,38 @@ pub async fn get_bgg_note(name: &str) -> Result<Option<Reviewer>, anyhow::Error>
     );
     log::debug!("getting bgg note: {}\n", &name);
     let (doc, _) = httpclient::get_doc(&search).await?;
-    Ok(parse_bgg_document(&name, search, &doc))
+    parse_bgg_document(&name, search, &doc)
 }
 
-fn parse_bgg_document(name: &str, search: String, document: &Html) -> Option<Reviewer> {
+fn parse_bgg_document(
+    name: &str,
+    search: String,
+    document: &Html,
+) -> Result<Option<Reviewer>, ScrapeError> {
     let primary_selector = Selector::parse("a.primary").unwrap();
 
     // Sélecteur pour les éléments avec la classe 'collection_bggrating'
-    let bggrating_selector = Selector::parse("td.collection_bggrating").unwrap();
+    let bggrating_css = "td.collection_bggrating";
+    let bggrating_selector = Selector::parse(bggrating_css).unwrap();
+
+    expect_results_list("bgg", document, "#collectionitems, table.collection_table")
+        .inspect_err(|_| BGG_STAT.with_label_values(&["broken"]).inc())?;
 
-    let selected_name = if let Some(primary) = document.select(&primary_selector).next() {
-        primary.text().collect::<Vec<_>>().join("")
+    let (selected_name, game_url) = if let Some(primary) = document.select(&primary_selector).next()
+    {
+        (
+            primary.text().collect::<Vec<_>>().join(""),
+            primary.value().attr("href").map(|href| {
+                if href.starts_with('/') {
+                    format!("https://boardgamegeek.com{}", href)
+                } else {
+                    href.to_string()
+                }
+            }),
+        )
     } else {
         BGG_STAT.with_label_values(&["fail"]).inc();
-        return None;
+        return Ok(None);
     };
     log::trace!("selected_name: {} vs name {}", selected_name, name);
 
@@ -40,20 +64,141 @@ fn parse_bgg_document(name: &str, search: String, document: &Html) -> Option<Rev
     }
     log::trace!("bggrating_values: {:#?}", bggrating_values);
 
-    if bggrating_values.len() == 2 && are_names_similar(name, &selected_name) {
+    if bggrating_values.len() != 2 {
+        BGG_STAT.with_label_values(&["broken"]).inc();
+        return Err(ScrapeError::selector_miss("bgg", bggrating_css));
+    }
+    if are_names_similar(name, &selected_name) {
         let rating = bggrating_values[0].clone().parse::<f32>().unwrap_or(0.0);
         let review_cnt = bggrating_values[1].clone().parse::<u32>().unwrap_or(0);
         BGG_STAT.with_label_values(&["success"]).inc();
-        return Some(Reviewer {
+        return Ok(Some(Reviewer {
             name: "bgg".to_string(),
             note: rating,
             number: review_cnt,
-            url: search,
-        });
+            // link to the game page when available, it carries the bgg id
+            url: game_url.unwrap_or(search),
+        }));
     }
 
     BGG_STAT.with_label_values(&["fail"]).inc();
-    None
+    Ok(None)
+}
+
+/// Extract the bgg id of a game from its page url (https://boardgamegeek.com/boardgame/<id>/<name>)
+pub fn bgg_id_from_url(url: &str) -> Option<u32> {
+    let re = Regex::new(r"/boardgame(?:expansion)?/(\d+)").unwrap();
+    re.captures(url)
+        .and_then(|caps| caps.get(1))
+        .and_then(|id| id.as_str().parse::<u32>().ok())
+}
+
+/// Fetch the wishlist and "want to buy" games of a bgg user through the xml api.
+/// bgg answers 202 while the export is being prepared, so we retry a few times
+pub async fn get_bgg_collection(username: &str) -> Result<Vec<WishlistItem>, ScrapeError> {
+    let url = bgg_collection_url(username);
+
+    for attempt in 1..=COLLECTION_MAX_ATTEMPTS {
+        log::debug!(
+            "getting bgg collection of {} (attempt {})",
+            username,
+            attempt
+        );
+        let res = httpclient::get_uncached(&url).await?;
+        if res.status() == reqwest::StatusCode::ACCEPTED {
+            tokio::time::sleep(std::time::Duration::from_secs(5 * attempt as u64)).await;
+            continue;
+        }
+        return parse_bgg_collection(&res.text().await?);
+    }
+
+    log::warn!("bgg collection of {} is still not ready", username);
+    Err(ScrapeError::Status {
+        url,
+        status: reqwest::StatusCode::ACCEPTED,
+    })
+}
+
+/// bgg wishlist priority of the games the user does not want
+const DONT_BUY_PRIORITY: u32 = 5;
+
+fn bgg_collection_url(username: &str) -> String {
+    reqwest::Url::parse_with_params(
+        "https://boardgamegeek.com/xmlapi2/collection",
+        &[
+            ("username", username),
+            ("subtype", "boardgame"),
+            ("brief", "1"),
+        ],
+    )
+    .expect("bgg collection url is valid")
+    .to_string()
+}
+
+/// Parse a bgg collection export (offline file or xmlapi2 response),
+/// keeping the games on the wishlist or flagged "want to buy".
+/// The wishlist priority 5 ("don't buy this") is left out
+pub fn parse_bgg_collection(xml: &str) -> Result<Vec<WishlistItem>, ScrapeError> {
+    let doc = roxmltree::Document::parse(xml)
+        .map_err(|err| ScrapeError::parse("bgg", format!("invalid collection : {}", err)))?;
+    let root = doc.root_element();
+    if root.tag_name().name() != "items" {
+        let message = root
+            .descendants()
+            .filter(|node| node.is_text())
+            .filter_map(|node| node.text())
+            .collect::<Vec<_>>()
+            .join(" ");
+        return Err(ScrapeError::parse(
+            "bgg",
+            format!("not a bgg collection : {}", message.trim()),
+        ));
+    }
+
+    let mut items = Vec::new();
+    for item in root.children().filter(|n| n.has_tag_name("item")) {
+        let status = match item.children().find(|n| n.has_tag_name("status")) {
+            Some(status) => status,
+            None => continue,
+        };
+        let wishlist = status.attribute("wishlist") == Some("1");
+        let want_to_buy = status.attribute("wanttobuy") == Some("1");
+        if !wishlist && !want_to_buy {
+            continue;
+        }
+
+        let priority = if wishlist {
+            status
+                .attribute("wishlistpriority")
+                .and_then(|p| p.parse::<u32>().ok())
+        } else {
+            None
+        };
+        if priority == Some(DONT_BUY_PRIORITY) {
+            continue;
+        }
+
+        let name = match item
+            .children()
+            .find(|n| n.has_tag_name("name"))
+            .and_then(|n| n.text())
+        {
+            Some(name) => name.trim().to_string(),
+            None => continue,
+        };
+
+        items.push(WishlistItem {
+            name,
+            bgg_id: item
+                .attribute("objectid")
+                .and_then(|id| id.parse::<u32>().ok()),
+            priority,
+            want_to_buy,
+            ..Default::default()
+        });
+    }
+
+    Ok(items)
 }
 
 use lazy_static::lazy_static;
@@ -61,7 +206,7 @@ use prometheus::{register_int_counter_vec, IntCounterVec};
 lazy_static! {
     static ref BGG_STAT: IntCounterVec = register_int_counter_vec!(
         "bgg_stat",
-        "Stat about parsing/fetch success/fail for this website",
+        "Stat about parsing/fetch success/fail/broken for this website",
         &["result"]
     )
     .unwrap();
@@ -72,12 +217,16 @@ mod tests {
     use log::Level;
     use std::{env, fs};
 
-    use crate::website::{bgg::parse_bgg_document, helper::clean_name};
+    use crate::website::{
+        bgg::{bgg_collection_url, bgg_id_from_url, parse_bgg_collection, parse_bgg_document},
+        helper::clean_name,
+    };
 
     struct Test {
         name: String,
         note: f32,
         review_cnt: u32,
+        bgg_id: Option<u32>,
         document: String,
     }
 
@@ -95,36 +244,42 @@ mod tests {
                 name: String::from("Lucky Bastard"),
                 note: 5.0,
                 review_cnt: 1,
+                bgg_id: Some(386454),
                 document: String::from("tests/bgg/test1.html"),
             },
             Test {
                 name: String::from("Cartaventura : Versailles"),
                 note: 6.79,
                 review_cnt: 8,
+                bgg_id: Some(382064),
                 document: String::from("tests/bgg/test2.html"),
             },
             Test {
                 name: String::from("Michel Strogoff VF"),
                 note: 6.72,
                 review_cnt: 621,
+                bgg_id: Some(224894),
                 document: String::from("tests/bgg/test3.html"),
             },
             Test {
                 name: String::from("Tiny Epic Western Base"),
                 note: 6.64,
                 review_cnt: 4179,
+                bgg_id: Some(180852),
                 document: String::from("tests/bgg/test4.html"),
             },
             Test {
                 name: String::from("Strife: Shadows & Steam"),
                 note: 6.53,
                 review_cnt: 138,
+                bgg_id: Some(177513),
                 document: String::from("tests/bgg/test5.html"),
             },
             Test {
                 name: String::from("Runebound"),
                 note: 6.22,
                 review_cnt: 1577,
+                bgg_id: Some(9829),
                 document: String::from("tests/bgg/test6.html"),
             },
         ];
@@ -133,9 +288,47 @@ mod tests {
             let html_doc =
                 fs::read_to_string(test.document).expect("Should have been able to read the file");
             let document = scraper::Html::parse_document(&html_doc);
-            let review = parse_bgg_document(&name, String::new(), &document).unwrap();
+            let review = parse_bgg_document(&name, String::new(), &document)
+                .unwrap()
+                .unwrap();
             assert_eq!(review.note, test.note);
             assert_eq!(review.number, test.review_cnt);
+            assert_eq!(bgg_id_from_url(&review.url), test.bgg_id);
         }
     }
+
+    #[test]
+    fn test_collection_url() {
+        assert_eq!(
+            bgg_collection_url("john doe&co"),
+            "https://boardgamegeek.com/xmlapi2/collection?username=john+doe%26co&subtype=boardgame&brief=1"
+        );
+    }
+
+    #[test]
+    fn test_collection() {
+        let xml = fs::read_to_string("tests/bgg/collection1.xml")
+            .expect("Should have been able to read the file");
+        let items = parse_bgg_collection(&xml).unwrap();
+        assert_eq!(items.len(), 3);
+
+        assert_eq!(items[0].name, "Runebound");
+        assert_eq!(items[0].bgg_id, Some(9829));
+        assert_eq!(items[0].priority, Some(2));
+        assert!(items[0].want_to_buy);
+
+        assert_eq!(items[1].name, "Michael Strogoff");
+        assert_eq!(items[1].priority, Some(4));
+        assert!(!items[1].want_to_buy);
+
+        assert_eq!(items[2].bgg_id, Some(180852));
+        assert_eq!(items[2].priority, None);
+        assert!(items[2].want_to_buy);
+
+        assert!(items.iter().all(|item| item.name != "UNO"));
+
+        let xml = fs::read_to_string("tests/bgg/collection2.xml")
+            .expect("Should have been able to read the file");
+        assert!(parse_bgg_collection(&xml).is_err());
+    }
 }
@@ -1,8 +1,11 @@
 use std::collections::HashSet;
 
 use regex::Regex;
+use scraper::{Html, Selector};
 use unidecode::unidecode;
 
+use crate::error::ScrapeError;
+
 static TOKENS_UNWANTED: [&str; 23] = [
     "vf",
     "vo",
@@ -80,9 +83,80 @@ pub fn are_names_similar(name1: &str, name2: &str) -> bool {
     true
 }
 
+/// Check that a search page has its results list, empty or not. Without it, the page is
+/// not the one expected (layout changed, captcha...) rather than a search without results
+pub fn expect_results_list(
+    website: &'static str,
+    document: &Html,
+    selector: &str,
+) -> Result<(), ScrapeError> {
+    let results_selector = Selector::parse(selector).unwrap();
+    if document.select(&results_selector).next().is_none() {
+        return Err(ScrapeError::selector_miss(website, selector));
+    }
+    Ok(())
+}
+
+/// The products of a search page that miss an element, to tell a layout change (every
+/// product misses it) from products that are not the game searched
+#[derive(Debug, Default)]
+pub struct ProductsCheck {
+    products: usize,
+    incomplete: usize,
+    missing: Option<&'static str>,
+}
+
+impl ProductsCheck {
+    pub fn complete(&mut self) {
+        self.products += 1;
+    }
+
+    pub fn missing(&mut self, selector: &'static str) {
+        log::trace!("product without {}", selector);
+        self.products += 1;
+        self.incomplete += 1;
+        self.missing = Some(selector);
+    }
+
+    /// An error if every product of the page missed an element
+    pub fn check(&self, website: &'static str) -> Result<(), ScrapeError> {
+        match self.missing {
+            Some(selector) if self.incomplete == self.products => {
+                Err(ScrapeError::selector_miss(website, selector))
+            }
+            _ => Ok(()),
+        }
+    }
+}
+
 #[cfg(test)]
 mod tests {
-    use crate::website::helper::are_names_similar;
+    use crate::error::ScrapeError;
+    use crate::website::helper::{are_names_similar, expect_results_list, ProductsCheck};
+
+    #[test]
+    fn test_products_check() {
+        let mut check = ProductsCheck::default();
+        assert!(check.check("ludifolie").is_ok());
+
+        check.missing(".price");
+        check.missing(".price");
+        assert!(matches!(
+            check.check("ludifolie"),
+            Err(ScrapeError::SelectorMiss { selector, .. }) if selector == ".price"
+        ));
+
+        check.complete();
+        assert!(check.check("ludifolie").is_ok());
+    }
+
+    #[test]
+    fn test_expect_results_list() {
+        let document =
+            scraper::Html::parse_document("<html><body><div id=\"products\"></div></body></html>");
+        assert!(expect_results_list("agorajeux", &document, "#products").is_ok());
+        assert!(expect_results_list("agorajeux", &document, ".product_list").is_err());
+    }
 
     struct Test<'a> {
         name1: &'a str,
@@ -140,7 +214,7 @@ mod tests {
             },
         ];
         for test in tests.into_iter() {
-            assert_eq!(are_names_similar(&test.name1, &test.name2), test.result);
+            assert_eq!(are_names_similar(test.name1, test.name2), test.result);
         }
     }
 }
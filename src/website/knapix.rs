@@ -1,14 +1,15 @@
-use std::error;
-
-use scraper::Selector;
+use scraper::{Html, Selector};
 
 use crate::{
+    error::ScrapeError,
     game::{Game, Reference},
     httpclient,
-    website::helper::clean_name,
+    website::helper::{clean_name, ProductsCheck},
 };
 
-pub async fn get_knapix_prices(game: &mut Game) -> Result<(), Box<dyn error::Error>> {
+/// Add the prices of the shops compared by knapix to the references of a game, returning
+/// the number of offers listed by knapix
+pub async fn get_knapix_prices(game: &mut Game) -> Result<usize, ScrapeError> {
     let name = clean_name(&game.okkazeo_announce.name).replace(' ', "+");
     let search = format!(
         "https://www.knapix.com/comparateur.php?nom_jeu={}&checkbox-exact=on&affiner=",
@@ -18,45 +19,94 @@ pub async fn get_knapix_prices(game: &mut Game) -> Result<(), Box<dyn error::Err
     log::debug!("searching knapix {}", search);
     let (document, _) = httpclient::get_doc(search).await?;
 
+    let offers = parse_knapix_document(&document)?;
+    let nb_offers = offers.len();
+    for reference in offers {
+        match reference.name.as_str() {
+            "agorajeux" | "philibert" | "ultrajeux" => {
+                game.references.insert(reference.name.clone(), reference);
+            }
+            _ => {}
+        }
+    }
+
+    Ok(nb_offers)
+}
+
+/// The offers of every shop of a knapix comparison
+fn parse_knapix_document(document: &Html) -> Result<Vec<Reference>, ScrapeError> {
+    let img_css = "img[alt]";
+    let price_css = ".prix";
     // choper <tr data-href="/r/127347999"> pou rla redirection vers le site
-    let row_selector = Selector::parse("tr[data-href]")?;
-    let img_selector = Selector::parse("img[alt]")?;
-    let price_selector = Selector::parse(".prix")?;
+    let row_selector = Selector::parse("tr[data-href]").unwrap();
+    let img_selector = Selector::parse(img_css).unwrap();
+    let price_selector = Selector::parse(price_css).unwrap();
 
+    let mut offers = Vec::new();
+    let mut check = ProductsCheck::default();
     for row in document.select(&row_selector) {
         let url = format!(
             "{}{}",
             "https://www.knapix.com",
             row.value().attr("data-href").unwrap_or_default()
         );
-        let img_element = row.select(&img_selector).next();
-        if let Some(img) = img_element {
-            let alt_value = img.value().attr("alt").unwrap_or_default().to_lowercase();
-
-            let price_element = row.select(&price_selector).next();
-            if let Some(price) = price_element {
-                let price_text = price.text().collect::<String>();
-                let price = price_text
-                    .trim()
-                    .replace(" €", "")
-                    .replace(',', ".")
-                    .parse::<f32>()?;
-                match alt_value.as_str() {
-                    "agorajeux" | "philibert" | "ultrajeux" => {
-                        game.references.insert(
-                            String::from(alt_value.as_str()),
-                            Reference {
-                                name: String::from(alt_value.as_str()),
-                                price,
-                                url,
-                            },
-                        );
-                    }
-                    _ => {}
-                }
-            }
-        }
+        let Some(img) = row.select(&img_selector).next() else {
+            check.missing(img_css);
+            continue;
+        };
+        let alt_value = img.value().attr("alt").unwrap_or_default().to_lowercase();
+
+        let Some(price) = row.select(&price_selector).next() else {
+            check.missing(price_css);
+            continue;
+        };
+        check.complete();
+        let price_text = price.text().collect::<String>();
+        let price = price_text
+            .trim()
+            .replace(" €", "")
+            .replace(',', ".")
+            .parse::<f32>()
+            .map_err(|_| ScrapeError::parse("knapix", format!("invalid price {:?}", price_text)))?;
+
+        offers.push(Reference {
+            name: alt_value,
+            price,
+            url,
+        });
     }
+    check.check("knapix")?;
+
+    Ok(offers)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::parse_knapix_document;
+    use crate::error::ScrapeError;
 
-    Ok(())
+    #[test]
+    fn test_parsing() {
+        let doc = std::fs::read_to_string(
+            "tests/fixtures/www.knapix.com/comparateur.php_nom_jeu_Runebound_checkbox-exact_on_affiner.body",
+        )
+        .expect("Should have been able to read the file");
+
+        let offers = parse_knapix_document(&scraper::Html::parse_document(&doc)).unwrap();
+        assert_eq!(offers.len(), 2);
+        assert_eq!(offers[0].name, "agorajeux");
+        assert_eq!(offers[0].price, 39.9);
+        assert_eq!(offers[0].url, "https://www.knapix.com/r/127347999");
+
+        let document = scraper::Html::parse_document(&doc.replace("class=\"prix\"", ""));
+        assert!(matches!(
+            parse_knapix_document(&document),
+            Err(ScrapeError::SelectorMiss { selector, .. }) if selector == ".prix"
+        ));
+        assert!(
+            parse_knapix_document(&scraper::Html::parse_document("<html></html>"))
+                .unwrap()
+                .is_empty()
+        );
+    }
 }
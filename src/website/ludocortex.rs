@@ -1,65 +1,24 @@
-use scraper::Selector;
+use scraper::{Html, Selector};
 
 use crate::{
+    error::ScrapeError,
     httpclient,
-    website::helper::{are_names_similar, clean_name},
+    website::helper::{are_names_similar, clean_name, expect_results_list, ProductsCheck},
 };
 
 pub async fn get_ludocortex_price_and_url_by_barcode(
     barcode: u64,
-) -> Result<Option<(f32, String)>, anyhow::Error> {
+) -> Result<Option<(f32, String)>, ScrapeError> {
     let search = format!("https://www.ludocortex.fr/jolisearch?s={}", barcode);
     log::debug!("search on ludocortex by barcode: {}", barcode);
     let (document, _) = httpclient::get_doc(&search).await?;
 
-    // Sélecteur pour l'article de produit
-    let product_selector = Selector::parse(".product-miniature").unwrap();
-
-    for product in document.select(&product_selector) {
-        let href = product
-            .select(&Selector::parse("a.product-thumbnail").unwrap())
-            .next()
-            .and_then(|link| link.value().attr("href"));
-
-        let title = product
-            .select(&Selector::parse(".product-title").unwrap())
-            .next()
-            .map(|title| title.inner_html());
-
-        let regular_price = product
-            .select(&Selector::parse(".regular-price").unwrap())
-            .next()
-            .map(|price| price.inner_html());
-
-        if regular_price.is_none() {
-            return Ok(None);
-        }
-
-        let regular_price = regular_price
-            .unwrap()
-            .trim()
-            .replace("&nbsp;€", "")
-            .replace(',', ".")
-            .parse::<f32>();
-
-        if href.is_none() || title.is_none() || regular_price.is_err() {
-            LUDOCORTEX_STAT.with_label_values(&["fail"]).inc();
-            return Ok(None);
-        }
-
-        if href.unwrap().contains(&barcode.to_string()) {
-            LUDOCORTEX_STAT.with_label_values(&["success"]).inc();
-            return Ok(Some((regular_price.unwrap(), href.unwrap().to_string())));
-        }
-    }
-
-    LUDOCORTEX_STAT.with_label_values(&["fail"]).inc();
-    Ok(None)
+    parse_ludocortex_document(&document, |_, href| href.contains(&barcode.to_string()))
 }
 
 pub async fn get_ludocortex_price_and_url_by_name(
     name: &str,
-) -> Result<Option<(f32, String)>, anyhow::Error> {
+) -> Result<Option<(f32, String)>, ScrapeError> {
     let search = format!(
         "https://www.ludocortex.fr/jolisearch?s={}",
         clean_name(name)
@@ -68,46 +27,72 @@ pub async fn get_ludocortex_price_and_url_by_name(
 
     let (document, _) = httpclient::get_doc(&search).await?;
 
+    parse_ludocortex_document(&document, |title, _| are_names_similar(title, name))
+}
+
+/// The price and url of the first product whose title and href match
+fn parse_ludocortex_document(
+    document: &Html,
+    is_match: impl Fn(&str, &str) -> bool,
+) -> Result<Option<(f32, String)>, ScrapeError> {
+    let href_css = "a.product-thumbnail";
+    let title_css = ".product-title";
+    let regular_price_css = ".regular-price";
     // Sélecteur pour l'article de produit
     let product_selector = Selector::parse(".product-miniature").unwrap();
+    let href_selector = Selector::parse(href_css).unwrap();
+    let title_selector = Selector::parse(title_css).unwrap();
+    let regular_price_selector = Selector::parse(regular_price_css).unwrap();
+
+    expect_results_list("ludocortex", document, "#products, .products")
+        .inspect_err(|_| LUDOCORTEX_STAT.with_label_values(&["broken"]).inc())?;
 
+    let mut check = ProductsCheck::default();
     for product in document.select(&product_selector) {
-        let href = product
-            .select(&Selector::parse("a.product-thumbnail").unwrap())
+        let Some(href) = product
+            .select(&href_selector)
             .next()
-            .and_then(|link| link.value().attr("href"));
-
-        let title = product
-            .select(&Selector::parse(".product-title").unwrap())
+            .and_then(|link| link.value().attr("href"))
+        else {
+            check.missing(href_css);
+            continue;
+        };
+        let Some(title) = product
+            .select(&title_selector)
             .next()
-            .map(|title| title.inner_html());
-
-        let regular_price = product
-            .select(&Selector::parse(".regular-price").unwrap())
+            .map(|title| title.inner_html())
+        else {
+            check.missing(title_css);
+            continue;
+        };
+
+        // the first product without a regular price ends the results
+        let Some(regular_price) = product
+            .select(&regular_price_selector)
             .next()
-            .map(|price| price.inner_html());
-
-        if regular_price.is_none() {
-            return Ok(None);
-        }
-
-        let regular_price = regular_price
-            .unwrap()
+            .map(|price| price.inner_html())
+        else {
+            break;
+        };
+        let Ok(regular_price) = regular_price
             .trim()
             .replace("&nbsp;€", "")
             .replace(',', ".")
-            .parse::<f32>();
-
-        if href.is_none() || title.is_none() || regular_price.is_err() {
-            LUDOCORTEX_STAT.with_label_values(&["success"]).inc();
-            return Ok(None);
-        }
-
-        if are_names_similar(&(title.unwrap()), name) {
+            .parse::<f32>()
+        else {
+            check.missing(regular_price_css);
+            continue;
+        };
+        check.complete();
+
+        if is_match(&title, href) {
             LUDOCORTEX_STAT.with_label_values(&["success"]).inc();
-            return Ok(Some((regular_price.unwrap(), href.unwrap().to_string())));
+            return Ok(Some((regular_price, href.to_string())));
         }
     }
+    check
+        .check("ludocortex")
+        .inspect_err(|_| LUDOCORTEX_STAT.with_label_values(&["broken"]).inc())?;
 
     LUDOCORTEX_STAT.with_label_values(&["fail"]).inc();
     Ok(None)
@@ -116,9 +101,9 @@ pub async fn get_ludocortex_price_and_url_by_name(
 pub async fn get_ludocortex_price_and_url(
     name: &str,
     barcode: Option<u64>,
-) -> Result<Option<(f32, String)>, anyhow::Error> {
-    if barcode.is_some() {
-        if let Some((a, b)) = get_ludocortex_price_and_url_by_barcode(barcode.unwrap()).await? {
+) -> Result<Option<(f32, String)>, ScrapeError> {
+    if let Some(barcode) = barcode {
+        if let Some((a, b)) = get_ludocortex_price_and_url_by_barcode(barcode).await? {
             return Ok(Some((a, b)));
         }
     }
@@ -130,7 +115,7 @@ use prometheus::{register_int_counter_vec, IntCounterVec};
 lazy_static! {
     static ref LUDOCORTEX_STAT: IntCounterVec = register_int_counter_vec!(
         "ludocortex_stat",
-        "Stat about parsing/fetch success/fail for this website",
+        "Stat about parsing/fetch success/fail/broken for this website",
         &["result"]
     )
     .unwrap();
@@ -1,14 +1,16 @@
 use std::{
     collections::HashMap,
-    error::{self},
     fs::File,
+    future::Future,
     io::{Cursor, Write},
     path::Path,
+    sync::Mutex,
+    time::{Duration, Instant},
 };
 
 use chrono::{DateTime, Local, NaiveDate, NaiveDateTime, NaiveTime, Utc};
 use feed_rs::{
-    model::Feed,
+    model::{Entry, Feed},
     parser::{self},
 };
 use hyper::StatusCode;
@@ -16,11 +18,12 @@ use image::io::Reader as ImageReader;
 use regex::Regex;
 use scraper::{Html, Selector};
 
-use crate::{game::Seller, httpclient};
+use crate::{error::ScrapeError, game::Seller, httpclient};
 
-pub async fn game_still_available(id: u32) -> bool {
+pub async fn game_still_available(id: u32) -> Result<bool, ScrapeError> {
     log::debug!("checking if game with id {} is still available", id);
-    let (_, code) = get_okkazeo_announce_page(id).await;
+    let search = format!("https://www.okkazeo.com/annonces/view/{}", id);
+    let (_, code) = httpclient::get_doc_uncached(search).await?;
 
     log::trace!(
         "game {} still available, is redirection: {} : code http {}",
@@ -28,7 +31,7 @@ pub async fn game_still_available(id: u32) -> bool {
         code.is_redirection(),
         code
     );
-    !code.is_redirection()
+    Ok(!code.is_redirection())
 }
 
 pub fn okkazeo_is_pro_seller(document: &Html) -> bool {
@@ -91,14 +94,14 @@ pub fn get_okkazeo_seller(document: &Html) -> Option<Seller> {
         .collect::<String>()
         .trim()
         .parse::<u32>()
-        .unwrap();
+        .ok()?;
 
     let id = href_attr
         .split('/')
         .collect::<Vec<&str>>()
         .last()?
         .parse::<u32>()
-        .unwrap();
+        .ok()?;
 
     Some(Seller {
         id,
@@ -108,10 +111,80 @@ pub fn get_okkazeo_seller(document: &Html) -> Option<Seller> {
             href_attr.to_string().replace("viewProfil", "stock")
         ),
         nb_announces: nb_annonces_text,
-        is_pro: false,
+        ..Default::default()
     })
 }
 
+/// How long a seller rating is reused before its profile page is fetched again
+const SELLER_RATING_TTL: Duration = Duration::from_secs(3600);
+
+/// Rating of a seller and its number of feedbacks, none if it has no rating yet
+type SellerRating = Option<(f32, u32)>;
+
+/// Ratings of the sellers already fetched, so a crawl over many announces of the same
+/// seller fetches its profile page only once
+#[derive(Default)]
+pub struct SellerRatings {
+    entries: Mutex<HashMap<u32, (Instant, SellerRating)>>,
+}
+
+impl SellerRatings {
+    pub fn get(&self, seller_id: u32) -> Option<SellerRating> {
+        let entries = self.entries.lock().unwrap();
+        entries
+            .get(&seller_id)
+            .filter(|(expires, _)| Instant::now() < *expires)
+            .map(|(_, rating)| *rating)
+    }
+
+    pub fn insert(&self, seller_id: u32, rating: SellerRating, ttl: Duration) {
+        let now = Instant::now();
+        let mut entries = self.entries.lock().unwrap();
+        entries.retain(|_, (expires, _)| now < *expires);
+        entries.insert(seller_id, (now + ttl, rating));
+    }
+}
+
+/// Fetch the okkazeo rating of a seller and its number of feedbacks from its profile page
+pub async fn get_okkazeo_seller_rating(seller: &Seller) -> Result<SellerRating, ScrapeError> {
+    if let Some(rating) = SELLER_RATINGS.get(seller.id) {
+        log::trace!("seller {} rating found in cache", seller.id);
+        return Ok(rating);
+    }
+
+    let profile_url = seller.url.replace("stock", "viewProfil");
+    log::debug!("getting seller profile from okkazeo : {}", profile_url);
+    let (document, _) = httpclient::get_doc(&profile_url).await?;
+
+    let rating = parse_okkazeo_seller_rating(&document);
+    SELLER_RATINGS.insert(seller.id, rating, SELLER_RATING_TTL);
+    Ok(rating)
+}
+
+fn parse_okkazeo_seller_rating(document: &Html) -> SellerRating {
+    let rating_selector = Selector::parse(".note, .rating, .evaluation").unwrap();
+
+    for element in document.select(&rating_selector) {
+        let text = element.text().collect::<Vec<_>>().join(" ");
+        let rating = SELLER_RATING_RE
+            .captures(&text)
+            .and_then(|c| c.get(1))
+            .and_then(|r| r.as_str().replace(',', ".").parse::<f32>().ok());
+
+        if let Some(rating) = rating {
+            let nb_ratings = SELLER_FEEDBACK_RE
+                .captures(&text)
+                .and_then(|c| c.get(1))
+                .and_then(|n| n.as_str().parse::<u32>().ok())
+                .unwrap_or_default();
+            log::trace!("seller rating : {}, feedbacks : {}", rating, nb_ratings);
+            return Some((rating, nb_ratings));
+        }
+    }
+
+    None
+}
+
 pub fn get_okkazeo_barcode(document: &Html) -> Option<u64> {
     let barcode_selector = Selector::parse("i.fa-barcode").unwrap();
     let barcode = if let Some(barcode) = document.select(&barcode_selector).next() {
@@ -138,52 +211,58 @@ pub fn get_okkazeo_city(document: &Html) -> Option<String> {
     None
 }
 
-pub fn get_okkazeo_announce_price(
-    document: &Html,
-) -> Result<f32, Box<dyn error::Error + Send + Sync>> {
+pub fn get_okkazeo_announce_price(document: &Html) -> Result<f32, ScrapeError> {
     let price_selector = Selector::parse(".desc_jeu .prix").unwrap();
 
     if let Some(price_element) = document.select(&price_selector).next() {
         let price = price_element.text().collect::<Vec<_>>().join("");
 
-        return Ok(price.replace('€', ".").parse::<f32>()?);
+        return price
+            .replace('€', ".")
+            .parse::<f32>()
+            .map_err(|_| ScrapeError::parse("okkazeo", format!("invalid price {:?}", price)));
     };
 
-    Err("error get_okkazeo_price, no entry in select".into())
+    Err(ScrapeError::selector_miss("okkazeo", ".desc_jeu .prix"))
 }
 
-pub fn get_okkazeo_announce_name(
-    document: &Html,
-) -> Result<String, Box<dyn error::Error + Send + Sync>> {
+pub fn get_okkazeo_announce_name(document: &Html) -> Result<String, ScrapeError> {
     let name_selector = Selector::parse("div.large-12.cell h1").unwrap();
 
     if let Some(name_element) = document.select(&name_selector).next() {
         return Ok(name_element.text().collect::<Vec<_>>().join(""));
     };
 
-    Err("error get_okkazeo_name, no entry in select".into())
+    Err(ScrapeError::selector_miss(
+        "okkazeo",
+        "div.large-12.cell h1",
+    ))
 }
 
-pub fn get_okkazeo_announce_extension(
-    document: &Html,
-) -> Result<String, Box<dyn error::Error + Send + Sync>> {
+pub fn get_okkazeo_announce_extension(document: &Html) -> Result<String, ScrapeError> {
     let extension_selector = Selector::parse("div.large-12.cell b").unwrap();
 
     if let Some(extension_element) = document.select(&extension_selector).next() {
         return Ok(extension_element.text().collect::<Vec<_>>().join(""));
     };
 
-    Err("error get_okkazeo_extension, no entry in select".into())
+    Err(ScrapeError::selector_miss("okkazeo", "div.large-12.cell b"))
 }
 
 pub fn get_okkazeo_announce_modification_date(
     document: &Html,
-) -> Result<DateTime<Utc>, Box<dyn error::Error + Send + Sync>> {
+) -> Result<DateTime<Utc>, ScrapeError> {
     let re = Regex::new(r"Modifiée le (\d{2}/\d{2}/\d{2})").unwrap();
 
     if let Some(captures) = re.captures(&document.html()) {
         if let Some(date) = captures.get(1) {
-            let naive_date: NaiveDate = NaiveDate::parse_from_str(date.as_str(), "%d/%m/%y")?;
+            let naive_date: NaiveDate = NaiveDate::parse_from_str(date.as_str(), "%d/%m/%y")
+                .map_err(|err| {
+                    ScrapeError::parse(
+                        "okkazeo",
+                        format!("invalid modification date {} : {}", date.as_str(), err),
+                    )
+                })?;
 
             // this is a trick as okkazeo announces dont have time, only date, so in order to try to have
             // them with the same order as the website, I add the current time (reversed as we are going through pages
@@ -200,49 +279,68 @@ pub fn get_okkazeo_announce_modification_date(
                 )
                 .unwrap(),
             );
-            let datetime_utc = DateTime::<Utc>::from_utc(naive_datetime, Utc);
+            let datetime_utc = DateTime::<Utc>::from_naive_utc_and_offset(naive_datetime, Utc);
             return Ok(datetime_utc);
         }
     }
 
-    Err("error get_okkazeo_modification_date, no entry in select".into())
+    Err(ScrapeError::selector_miss("okkazeo", re.as_str()))
 }
 
-pub fn get_okkazeo_announce_image(
-    document: &Html,
-) -> Result<String, Box<dyn error::Error + Send + Sync>> {
-    let image_selector = Selector::parse("div.image-wrapper.image img").unwrap();
+pub fn get_okkazeo_announce_image(document: &Html) -> Result<String, ScrapeError> {
+    let image_selector = Selector::parse("div.image-wrapper.image img[src]").unwrap();
 
     if let Some(image_element) = document.select(&image_selector).next() {
-        return Ok(image_element.value().attr("src").unwrap().to_string());
+        return Ok(image_element
+            .value()
+            .attr("src")
+            .unwrap_or_default()
+            .to_string());
     };
 
-    Err("error get_okkazeo_image, no entry in select".into())
+    Err(ScrapeError::selector_miss(
+        "okkazeo",
+        "div.image-wrapper.image img[src]",
+    ))
 }
 
-pub async fn get_okkazeo_announce_page(id: u32) -> (Html, StatusCode) {
+/// The page of an announce, okkazeo redirecting to the announces list once it is sold
+pub async fn get_okkazeo_announce_page(id: u32) -> Result<Html, ScrapeError> {
     let search = format!("https://www.okkazeo.com/annonces/view/{}", id);
     log::debug!("getting announce page from okkazeo : {}", id);
 
-    httpclient::get_doc(search).await.unwrap()
+    let (document, code) = httpclient::get_doc(&search).await?;
+    if code.is_redirection() || code == StatusCode::NOT_FOUND || code == StatusCode::GONE {
+        return Err(ScrapeError::NotFound { url: search });
+    }
+    if !code.is_success() {
+        return Err(ScrapeError::Status {
+            url: search,
+            status: code,
+        });
+    }
+    Ok(document)
 }
 
-pub async fn download_okkazeo_game_image(
-    url: &str,
-) -> Result<String, Box<dyn std::error::Error + Sync + Send>> {
+pub async fn download_okkazeo_game_image(url: &str) -> Result<String, ScrapeError> {
     log::debug!("getting image from {}", url);
-    let response = httpclient::get(url).await?;
+    let response = httpclient::get_uncached(url).await?;
     let image_bytes = response.bytes().await?;
 
+    let invalid_image = |err: image::ImageError| {
+        ScrapeError::parse("okkazeo", format!("invalid image {} : {}", url, err))
+    };
     let image_reader = ImageReader::new(std::io::Cursor::new(image_bytes)).with_guessed_format()?;
 
-    let image = image_reader.decode()?;
+    let image = image_reader.decode().map_err(invalid_image)?;
 
     let mut bytes: Vec<u8> = Vec::new();
-    image.write_to(
-        &mut Cursor::new(&mut bytes),
-        image::ImageOutputFormat::Jpeg(60),
-    )?;
+    image
+        .write_to(
+            &mut Cursor::new(&mut bytes),
+            image::ImageOutputFormat::Jpeg(60),
+        )
+        .map_err(invalid_image)?;
 
     let re = Regex::new(r"/([^/]+)\.(jpg|png)$").unwrap();
     let mut name: &str = "unknown";
@@ -259,21 +357,173 @@ pub async fn download_okkazeo_game_image(
     let mut output_file = File::create(&output_path)?;
     output_file.write_all(&bytes)?;
 
-    Ok(output_path.to_str().unwrap().to_string())
+    Ok(output_path.to_string_lossy().to_string())
+}
+
+/// number of entries of a page of the atom feed
+pub const ATOM_FEED_PAGE_SIZE: u32 = 50;
+
+pub async fn get_atom_feed() -> Result<Feed, ScrapeError> {
+    get_atom_feed_page(0).await
+}
+
+/// A page of the atom feed, starting at the entry `offset` from the most recent
+pub async fn get_atom_feed_page(offset: u32) -> Result<Feed, ScrapeError> {
+    log::debug!("getting atom feed from {}", offset);
+    let content = httpclient::get(format!(
+        "https://www.okkazeo.com/annonces/atom/{}/{}",
+        offset, ATOM_FEED_PAGE_SIZE
+    ))
+    .await?
+    .bytes()
+    .await?;
+    parser::parse(content.as_ref())
+        .map_err(|err| ScrapeError::parse("okkazeo", format!("invalid atom feed : {}", err)))
+}
+
+/// The most recent entry of the feed handled by the backend, kept across restarts to
+/// detect the entries missed in between
+#[derive(Debug, Clone, PartialEq)]
+pub struct FeedState {
+    pub last_id: u32,
+    pub last_date: DateTime<Utc>,
+}
+
+impl FeedState {
+    /// The most recently updated of the entries
+    pub fn latest(entries: &[Entry]) -> Option<Self> {
+        entries
+            .iter()
+            .filter_map(|entry| Some((entry.id.parse::<u32>().ok()?, entry.updated?)))
+            .max_by_key(|(_, updated)| *updated)
+            .map(|(last_id, last_date)| FeedState { last_id, last_date })
+    }
+
+    /// Whether entries may have been published between this one and the given page:
+    /// none of its entries is this one or older
+    pub fn has_gap(&self, entries: &[Entry]) -> bool {
+        let last_id = self.last_id.to_string();
+        !entries.iter().any(|entry| {
+            entry.id == last_id || entry.updated.is_some_and(|date| date <= self.last_date)
+        })
+    }
+}
+
+/// The entries of the feed since the last seen one
+#[derive(Debug, Default)]
+pub struct FeedEntries {
+    pub entries: Vec<Entry>,
+    /// number of older pages fetched to reach the last seen entry
+    pub catch_up_pages: u32,
+    /// false when an older page could not be fetched, the entries missing some
+    pub complete: bool,
+    /// true when the gap is larger than the pages allowed, the entries between the last
+    /// seen one and the oldest fetched being left to the crawler
+    pub truncated: bool,
 }
 
-pub async fn get_atom_feed() -> Result<Feed, anyhow::Error> {
-    log::debug!("getting atom feed");
-    let content = httpclient::get("https://www.okkazeo.com/annonces/atom/0/50")
-        .await?
-        .bytes()
-        .await?;
-    Ok(parser::parse(content.as_ref())?)
+/// Fetch the atom feed and, when its first page does not reach the last seen entry, the
+/// older pages until one does, `max_pages` at most. An entry appearing in two pages (the
+/// feed moving while paging) is only kept once
+pub async fn get_atom_feed_since(
+    last_seen: Option<&FeedState>,
+    max_pages: u32,
+) -> Result<FeedEntries, ScrapeError> {
+    let entries = get_atom_feed().await?.entries;
+    Ok(
+        catch_up_feed(entries, last_seen, max_pages, |offset| async move {
+            get_atom_feed_page(offset).await.map(|feed| feed.entries)
+        })
+        .await,
+    )
 }
 
-pub async fn get_games_from_page(
-    page: u32,
-) -> Result<Vec<u32>, Box<dyn error::Error + Send + Sync>> {
+/// Complete the first page of the feed with the older pages given by `get_page` (from
+/// their offset) until the last seen entry is reached
+async fn catch_up_feed<F, Fut>(
+    mut entries: Vec<Entry>,
+    last_seen: Option<&FeedState>,
+    max_pages: u32,
+    get_page: F,
+) -> FeedEntries
+where
+    F: Fn(u32) -> Fut,
+    Fut: Future<Output = Result<Vec<Entry>, ScrapeError>>,
+{
+    let mut res = FeedEntries {
+        complete: true,
+        ..Default::default()
+    };
+    let Some(last_seen) = last_seen.filter(|last_seen| last_seen.has_gap(&entries)) else {
+        res.entries = entries;
+        return res;
+    };
+    log::warn!(
+        "atom feed does not reach the last seen entry {} of {}, fetching older pages",
+        last_seen.last_id,
+        last_seen.last_date
+    );
+
+    let mut page = entries.clone();
+    while last_seen.has_gap(&page) && page.len() as u32 == ATOM_FEED_PAGE_SIZE {
+        if res.catch_up_pages >= max_pages {
+            log::error!(
+                "atom feed gap larger than {} pages, the entries updated between {} and {} are left to the crawler",
+                max_pages,
+                last_seen.last_date,
+                entries
+                    .iter()
+                    .filter_map(|entry| entry.updated)
+                    .min()
+                    .map_or("now".to_string(), |date| date.to_string())
+            );
+            res.truncated = true;
+            break;
+        }
+        res.catch_up_pages += 1;
+        page = match get_page(res.catch_up_pages * ATOM_FEED_PAGE_SIZE).await {
+            Ok(page) => page,
+            Err(e) => {
+                log::error!("cannot get older atom feed page : {}", e);
+                res.complete = false;
+                break;
+            }
+        };
+        for entry in &page {
+            if !entries.iter().any(|known| known.id == entry.id) {
+                entries.push(entry.clone());
+            }
+        }
+    }
+
+    res.entries = entries;
+    res
+}
+
+/// The price of an announce of the atom feed, at the end of its summary (`... 25€`)
+pub fn get_atom_entry_price(entry: &Entry) -> Result<f32, ScrapeError> {
+    let summary = entry
+        .summary
+        .as_ref()
+        .ok_or_else(|| ScrapeError::selector_miss("okkazeo", "entry summary"))?;
+    let price = summary
+        .content
+        .rsplit('>')
+        .next()
+        .unwrap_or_default()
+        .split('€')
+        .next()
+        .unwrap_or_default();
+
+    price.trim().parse::<f32>().map_err(|_| {
+        ScrapeError::parse(
+            "okkazeo",
+            format!("invalid price {:?} in entry {}", price, entry.id),
+        )
+    })
+}
+
+pub async fn get_games_from_page(page: u32) -> Result<Vec<u32>, ScrapeError> {
     let search = format!("https:///www.okkazeo.com/jeux/arrivages?page={}", page);
     log::debug!("getting okkazeo page : {}", &search);
     let (document, _) = httpclient::get_doc(&search).await?;
@@ -290,7 +540,10 @@ pub async fn get_games_from_page(
                     .collect::<Vec<&str>>()
                     .last()
                     .unwrap()
-                    .parse::<u32>()?;
+                    .parse::<u32>()
+                    .map_err(|_| {
+                        ScrapeError::parse("okkazeo", format!("invalid announce link {}", href))
+                    })?;
                 links.push(ids);
             }
         }
@@ -298,3 +551,208 @@ pub async fn get_games_from_page(
 
     Ok(links)
 }
+
+use lazy_static::lazy_static;
+lazy_static! {
+    static ref SELLER_RATING_RE: Regex = Regex::new(r"(\d+(?:[.,]\d+)?)\s*/\s*5").unwrap();
+    static ref SELLER_FEEDBACK_RE: Regex = Regex::new(r"(\d+)\s+(?:avis|évaluations?)").unwrap();
+    static ref SELLER_RATINGS: SellerRatings = SellerRatings::default();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        catch_up_feed, get_atom_entry_price, parse_okkazeo_seller_rating, FeedState, SellerRatings,
+        ATOM_FEED_PAGE_SIZE,
+    };
+    use crate::error::ScrapeError;
+    use chrono::{TimeZone, Utc};
+    use feed_rs::model::Entry;
+    use std::{fs, time::Duration};
+
+    #[test]
+    fn test_atom_entry_price() {
+        let feed = feed_rs::parser::parse(
+            r#"<?xml version="1.0" encoding="utf-8"?>
+            <feed xmlns="http://www.w3.org/2005/Atom">
+                <title>okkazeo</title>
+                <entry>
+                    <id>1234567</id>
+                    <title>Runebound</title>
+                    <summary type="html">&lt;img src="runebound.jpg"&gt;25.50€</summary>
+                </entry>
+                <entry>
+                    <id>7654321</id>
+                    <title>Catan</title>
+                    <summary type="html">&lt;img src="catan.jpg"&gt;à débattre</summary>
+                </entry>
+                <entry>
+                    <id>1111111</id>
+                    <title>Azul</title>
+                </entry>
+            </feed>"#
+                .as_bytes(),
+        )
+        .unwrap();
+
+        assert_eq!(get_atom_entry_price(&feed.entries[0]).unwrap(), 25.5);
+        assert!(matches!(
+            get_atom_entry_price(&feed.entries[1]),
+            Err(ScrapeError::Parse { .. })
+        ));
+        assert!(matches!(
+            get_atom_entry_price(&feed.entries[2]),
+            Err(ScrapeError::SelectorMiss { .. })
+        ));
+    }
+
+    #[test]
+    fn test_feed_state() {
+        let feed = feed_rs::parser::parse(
+            r#"<?xml version="1.0" encoding="utf-8"?>
+            <feed xmlns="http://www.w3.org/2005/Atom">
+                <title>okkazeo</title>
+                <entry>
+                    <id>1234567</id>
+                    <title>Runebound</title>
+                    <updated>2026-10-14T10:00:00Z</updated>
+                </entry>
+                <entry>
+                    <id>1234566</id>
+                    <title>Catan</title>
+                    <updated>2026-10-14T11:00:00Z</updated>
+                </entry>
+                <entry>
+                    <id>1234565</id>
+                    <title>Azul</title>
+                    <updated>2026-10-14T09:00:00Z</updated>
+                </entry>
+            </feed>"#
+                .as_bytes(),
+        )
+        .unwrap();
+
+        let latest = FeedState::latest(&feed.entries).unwrap();
+        assert_eq!(latest.last_id, 1234566);
+        assert_eq!(latest.last_date.to_rfc3339(), "2026-10-14T11:00:00+00:00");
+        assert!(FeedState::latest(&[]).is_none());
+
+        // the last seen entry is in the page, or older than some of its entries
+        assert!(!latest.has_gap(&feed.entries));
+        let older = FeedState {
+            last_id: 1,
+            last_date: feed.entries[0].updated.unwrap(),
+        };
+        assert!(!older.has_gap(&feed.entries));
+        let older = FeedState {
+            last_id: 1234565,
+            last_date: feed.entries[2].updated.unwrap() - chrono::Duration::hours(1),
+        };
+        assert!(!older.has_gap(&feed.entries));
+
+        let gap = FeedState {
+            last_id: 1,
+            last_date: feed.entries[2].updated.unwrap() - chrono::Duration::hours(1),
+        };
+        assert!(gap.has_gap(&feed.entries));
+    }
+
+    /// A full page of the feed, its entries updated a minute apart from `first` backwards
+    fn feed_page(page: u32) -> Vec<Entry> {
+        let first = Utc.with_ymd_and_hms(2026, 10, 14, 12, 0, 0).unwrap();
+        (0..ATOM_FEED_PAGE_SIZE)
+            .map(|i| {
+                let n = page * ATOM_FEED_PAGE_SIZE + i;
+                Entry {
+                    id: (1_000_000 - n).to_string(),
+                    updated: Some(first - chrono::Duration::minutes(n as i64)),
+                    ..Default::default()
+                }
+            })
+            .collect()
+    }
+
+    async fn get_feed_page(offset: u32) -> Result<Vec<Entry>, ScrapeError> {
+        Ok(feed_page(offset / ATOM_FEED_PAGE_SIZE))
+    }
+
+    #[tokio::test]
+    async fn test_catch_up_feed() {
+        // the last seen entry is in the third page
+        let last_seen = FeedState {
+            last_id: 1_000_000 - 120,
+            last_date: feed_page(2)[20].updated.unwrap(),
+        };
+        let feed = catch_up_feed(feed_page(0), Some(&last_seen), 5, get_feed_page).await;
+        assert!(feed.complete);
+        assert!(!feed.truncated);
+        assert_eq!(feed.catch_up_pages, 2);
+        assert_eq!(feed.entries.len(), 3 * ATOM_FEED_PAGE_SIZE as usize);
+
+        // no gap, the first page is enough
+        let last_seen = FeedState::latest(&feed_page(0)).unwrap();
+        let feed = catch_up_feed(feed_page(0), Some(&last_seen), 5, get_feed_page).await;
+        assert_eq!(feed.catch_up_pages, 0);
+        assert_eq!(feed.entries.len(), ATOM_FEED_PAGE_SIZE as usize);
+    }
+
+    #[tokio::test]
+    async fn test_catch_up_feed_gap_larger_than_max_pages() {
+        let last_seen = FeedState {
+            last_id: 1,
+            last_date: Utc.with_ymd_and_hms(2026, 10, 1, 0, 0, 0).unwrap(),
+        };
+        let feed = catch_up_feed(feed_page(0), Some(&last_seen), 2, get_feed_page).await;
+        // the pages fetched are complete, the state can move past the skipped entries
+        assert!(feed.complete);
+        assert!(feed.truncated);
+        assert_eq!(feed.catch_up_pages, 2);
+        assert_eq!(feed.entries.len(), 3 * ATOM_FEED_PAGE_SIZE as usize);
+        assert_eq!(
+            FeedState::latest(&feed.entries),
+            FeedState::latest(&feed_page(0))
+        );
+    }
+
+    #[tokio::test]
+    async fn test_catch_up_feed_failed_page() {
+        let last_seen = FeedState {
+            last_id: 1,
+            last_date: Utc.with_ymd_and_hms(2026, 10, 1, 0, 0, 0).unwrap(),
+        };
+        let feed = catch_up_feed(feed_page(0), Some(&last_seen), 5, |_| async {
+            Err(ScrapeError::parse("okkazeo", "invalid atom feed"))
+        })
+        .await;
+        assert!(!feed.complete);
+        assert!(!feed.truncated);
+        assert_eq!(feed.entries.len(), ATOM_FEED_PAGE_SIZE as usize);
+    }
+
+    #[test]
+    fn test_parse_seller_rating() {
+        let tests = vec![
+            ("tests/okkazeo/profile1.html", Some((4.9, 127))),
+            ("tests/okkazeo/profile2.html", None),
+        ];
+        for (file, expected) in tests.into_iter() {
+            let doc = fs::read_to_string(file).expect("Should have been able to read the file");
+            let document = scraper::Html::parse_document(&doc);
+            assert_eq!(parse_okkazeo_seller_rating(&document), expected);
+        }
+    }
+
+    #[test]
+    fn test_seller_ratings_cache() {
+        let cache = SellerRatings::default();
+        assert_eq!(cache.get(4242), None);
+
+        cache.insert(4242, Some((4.9, 127)), Duration::from_secs(60));
+        cache.insert(1337, None, Duration::from_secs(60));
+        cache.insert(7, Some((3.0, 2)), Duration::ZERO);
+
+        assert_eq!(cache.get(4242), Some(Some((4.9, 127))));
+        assert_eq!(cache.get(1337), Some(None));
+        assert_eq!(cache.get(7), None);
+    }
+}
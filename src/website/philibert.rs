@@ -1,13 +1,14 @@
-use scraper::Selector;
+use scraper::{Html, Selector};
 
 use crate::{
+    error::ScrapeError,
     httpclient,
-    website::helper::{are_names_similar, clean_name},
+    website::helper::{are_names_similar, clean_name, expect_results_list, ProductsCheck},
 };
 
 pub async fn get_philibert_price_and_url_by_barcode(
     barcode: u64,
-) -> Result<Option<(f32, String)>, anyhow::Error> {
+) -> Result<Option<(f32, String)>, ScrapeError> {
     let search = format!(
         "https://www.philibertnet.com/fr/recherche?search_query={}&submit_search=",
         barcode
@@ -15,44 +16,17 @@ pub async fn get_philibert_price_and_url_by_barcode(
     log::debug!("search on philibert by barcode: {}", &barcode);
     let (document, _) = httpclient::get_doc(&search).await?;
 
-    let product_list_selector = Selector::parse(".product_list.grid .ajax_block_product").unwrap();
-    let price_selector = Selector::parse(".price").unwrap();
-    let title_selector = Selector::parse("p.s_title_block a").unwrap();
-
-    for product in document.select(&product_list_selector) {
-        let price_element = product.select(&price_selector).next();
-        if let Some(price) = price_element {
-            let price_text = price.text().collect::<String>();
-            let price_text = price_text
-                .trim()
-                .replace(" €", "")
-                .replace(',', ".")
-                .parse::<f32>()
-                .unwrap_or(0.0);
-
-            let title_element = product.select(&title_selector).next();
-            if let Some(title) = title_element {
-                let href_attr = title.value().attr("href").unwrap_or_default();
-
-                if href_attr
-                    .split('?')
-                    .next()
-                    .unwrap()
-                    .contains(&barcode.to_string())
-                {
-                    PHILIBERT_STAT.with_label_values(&["success"]).inc();
-                    return Ok(Some((price_text, href_attr.to_string())));
-                }
-            }
-        }
-    }
-    PHILIBERT_STAT.with_label_values(&["fail"]).inc();
-    Ok(None)
+    parse_philibert_document(&document, |_, href| {
+        href.split('?')
+            .next()
+            .unwrap()
+            .contains(&barcode.to_string())
+    })
 }
 
 pub async fn get_philibert_price_and_url_by_name(
     name: &str,
-) -> Result<Option<(f32, String)>, anyhow::Error> {
+) -> Result<Option<(f32, String)>, ScrapeError> {
     let search = format!(
         "https://www.philibertnet.com/fr/recherche?search_query={}&submit_search=",
         clean_name(name)
@@ -60,34 +34,56 @@ pub async fn get_philibert_price_and_url_by_name(
     log::debug!("search on philibert by name: {}", &name);
     let (document, _) = httpclient::get_doc(&search).await?;
 
+    parse_philibert_document(&document, |title, _| are_names_similar(title, name))
+}
+
+/// The price and url of the first product whose title and href match
+fn parse_philibert_document(
+    document: &Html,
+    is_match: impl Fn(&str, &str) -> bool,
+) -> Result<Option<(f32, String)>, ScrapeError> {
+    let price_css = ".price";
+    let title_css = "p.s_title_block a";
     let product_list_selector = Selector::parse(".product_list.grid .ajax_block_product").unwrap();
-    let price_selector = Selector::parse(".price").unwrap();
-    let title_selector = Selector::parse("p.s_title_block a").unwrap();
+    let price_selector = Selector::parse(price_css).unwrap();
+    let title_selector = Selector::parse(title_css).unwrap();
+
+    // the search page without results has no product list, only its main column
+    expect_results_list("philibert", document, "#center_column, .product_list")
+        .inspect_err(|_| PHILIBERT_STAT.with_label_values(&["broken"]).inc())?;
 
+    let mut check = ProductsCheck::default();
     for product in document.select(&product_list_selector) {
-        let price_element = product.select(&price_selector).next();
-        if let Some(price) = price_element {
-            let price_text = price.text().collect::<String>();
-            let price_text = price_text
-                .trim()
-                .replace(" €", "")
-                .replace(',', ".")
-                .parse::<f32>()
-                .unwrap_or(0.0);
+        let Some(price) = product.select(&price_selector).next() else {
+            check.missing(price_css);
+            continue;
+        };
+        let price_text = price.text().collect::<String>();
+        let price_text = price_text
+            .trim()
+            .replace(" €", "")
+            .replace(',', ".")
+            .parse::<f32>()
+            .unwrap_or(0.0);
 
-            let title_element = product.select(&title_selector).next();
-            if let Some(title) = title_element {
-                let title_text = title.text().collect::<String>();
-                let title_text = title_text.trim();
-                let href_attr = title.value().attr("href").unwrap_or_default();
+        let Some(title) = product.select(&title_selector).next() else {
+            check.missing(title_css);
+            continue;
+        };
+        check.complete();
+        let title_text = title.text().collect::<String>();
+        let title_text = title_text.trim();
+        let href_attr = title.value().attr("href").unwrap_or_default();
 
-                if are_names_similar(title_text, name) {
-                    PHILIBERT_STAT.with_label_values(&["success"]).inc();
-                    return Ok(Some((price_text, href_attr.to_string())));
-                }
-            }
+        if is_match(title_text, href_attr) {
+            PHILIBERT_STAT.with_label_values(&["success"]).inc();
+            return Ok(Some((price_text, href_attr.to_string())));
         }
     }
+    check
+        .check("philibert")
+        .inspect_err(|_| PHILIBERT_STAT.with_label_values(&["broken"]).inc())?;
+
     PHILIBERT_STAT.with_label_values(&["fail"]).inc();
     Ok(None)
 }
@@ -95,9 +91,9 @@ pub async fn get_philibert_price_and_url_by_name(
 pub async fn get_philibert_price_and_url(
     name: &str,
     barcode: Option<u64>,
-) -> Result<Option<(f32, String)>, anyhow::Error> {
-    if barcode.is_some() {
-        if let Some((a, b)) = get_philibert_price_and_url_by_barcode(barcode.unwrap()).await? {
+) -> Result<Option<(f32, String)>, ScrapeError> {
+    if let Some(barcode) = barcode {
+        if let Some((a, b)) = get_philibert_price_and_url_by_barcode(barcode).await? {
             return Ok(Some((a, b)));
         }
     }
@@ -109,7 +105,7 @@ use prometheus::{register_int_counter_vec, IntCounterVec};
 lazy_static! {
     static ref PHILIBERT_STAT: IntCounterVec = register_int_counter_vec!(
         "philibert_stat",
-        "Stat about parsing/fetch success/fail for this website",
+        "Stat about parsing/fetch success/fail/broken for this website",
         &["result"]
     )
     .unwrap();
@@ -1,10 +1,11 @@
 use regex::Regex;
 
+use crate::error::ScrapeError;
 use crate::website::helper::clean_name;
 
 pub async fn get_ultrajeux_price_and_url_by_barcode(
     barcode: u64,
-) -> Result<Option<(f32, String)>, anyhow::Error> {
+) -> Result<Option<(f32, String)>, ScrapeError> {
     let search = format!(
         "https://www.ultrajeux.com/search3.php?text={}&submit=Ok",
         barcode
@@ -15,7 +16,7 @@ pub async fn get_ultrajeux_price_and_url_by_barcode(
     let content_str: &str = &String::from_utf8_lossy(&content);
     for capture in re.captures_iter(content_str) {
         if let Some(value) = capture.get(1) {
-            let number: f32 = value.as_str().replace(',', ".").parse().unwrap();
+            let number = parse_ultrajeux_price(value.as_str())?;
             ULTRAJEUX_STAT.with_label_values(&["success"]).inc();
             return Ok(Some((number, search)));
         }
@@ -26,7 +27,7 @@ pub async fn get_ultrajeux_price_and_url_by_barcode(
 
 pub async fn get_ultrajeux_price_and_url_by_name(
     name: &str,
-) -> Result<Option<(f32, String)>, anyhow::Error> {
+) -> Result<Option<(f32, String)>, ScrapeError> {
     let search = format!(
         "https://www.ultrajeux.com/search3.php?text={}&submit=Ok",
         clean_name(name)
@@ -37,7 +38,7 @@ pub async fn get_ultrajeux_price_and_url_by_name(
     let content_str: &str = &String::from_utf8_lossy(&content);
     for capture in re.captures_iter(content_str) {
         if let Some(value) = capture.get(1) {
-            let number: f32 = value.as_str().replace(',', ".").parse().unwrap();
+            let number = parse_ultrajeux_price(value.as_str())?;
             ULTRAJEUX_STAT.with_label_values(&["success"]).inc();
             return Ok(Some((number, search)));
         }
@@ -46,12 +47,19 @@ pub async fn get_ultrajeux_price_and_url_by_name(
     Ok(None)
 }
 
+fn parse_ultrajeux_price(price: &str) -> Result<f32, ScrapeError> {
+    price
+        .replace(',', ".")
+        .parse::<f32>()
+        .map_err(|_| ScrapeError::parse("ultrajeux", format!("invalid price {:?}", price)))
+}
+
 pub async fn get_ultrajeux_price_and_url(
     name: &str,
     barcode: Option<u64>,
-) -> Result<Option<(f32, String)>, anyhow::Error> {
-    if barcode.is_some() {
-        if let Some((a, b)) = get_ultrajeux_price_and_url_by_barcode(barcode.unwrap()).await? {
+) -> Result<Option<(f32, String)>, ScrapeError> {
+    if let Some(barcode) = barcode {
+        if let Some((a, b)) = get_ultrajeux_price_and_url_by_barcode(barcode).await? {
             return Ok(Some((a, b)));
         }
     }
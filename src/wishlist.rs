@@ -0,0 +1,323 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use unidecode::unidecode;
+
+use crate::game::{Game, Games};
+use crate::website::bgg::bgg_id_from_url;
+use crate::website::helper::{are_names_similar, clean_name};
+
+/// words flagging an announce as not french, okkazeo announces are french otherwise
+static ENGLISH_TOKENS: [&str; 3] = ["vo", "anglais", "english"];
+/// language codes only count between brackets, "en" alone being a common french word
+static ENGLISH_MARKERS: [&str; 4] = ["(en)", "[en]", "(uk)", "(us)"];
+
+/// A wanted game, matched against every incoming announce
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct WishlistItem {
+    pub id: u32,
+    pub name: String,
+    pub barcode: Option<u64>,
+    pub bgg_id: Option<u32>,
+    pub max_price: Option<f32>,
+    /// "fr" or "en"
+    pub language: Option<String>,
+    /// word that must appear in the announce name, e.g. "deluxe"
+    pub edition: Option<String>,
+    /// bgg wishlist priority, from 1 (must have) to 5 (don't buy this)
+    pub priority: Option<u32>,
+    pub want_to_buy: bool,
+    pub creation_date: DateTime<Utc>,
+}
+
+// this is ugly, but otherwise the Form from axum doesnt work properly
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct WishlistForm {
+    pub name_form: String,
+    pub barcode_form: Option<String>,
+    pub bgg_id_form: Option<String>,
+    pub max_price_form: Option<String>,
+    pub language_form: Option<String>,
+    pub edition_form: Option<String>,
+}
+
+impl WishlistForm {
+    pub fn to_item(&self) -> Option<WishlistItem> {
+        let name = self.name_form.trim();
+        if name.is_empty() {
+            return None;
+        }
+        let non_empty = |field: &Option<String>| {
+            field
+                .as_deref()
+                .map(str::trim)
+                .filter(|v| !v.is_empty())
+                .map(str::to_string)
+        };
+
+        Some(WishlistItem {
+            name: name.to_string(),
+            barcode: non_empty(&self.barcode_form).and_then(|v| v.parse::<u64>().ok()),
+            bgg_id: non_empty(&self.bgg_id_form).and_then(|v| v.parse::<u32>().ok()),
+            max_price: non_empty(&self.max_price_form)
+                .and_then(|v| v.replace(',', ".").parse::<f32>().ok()),
+            language: non_empty(&self.language_form),
+            edition: non_empty(&self.edition_form),
+            ..Default::default()
+        })
+    }
+}
+
+/// A wishlist item with the announces currently matching it
+#[derive(Debug, Clone, Serialize)]
+pub struct WishlistEntry {
+    pub item: WishlistItem,
+    pub offers: Games,
+    /// cheapest new price found in the shops for the matched announces
+    pub cheapest_reference: Option<f32>,
+}
+
+fn words(name: &str) -> Vec<String> {
+    clean_name(name)
+        .split_whitespace()
+        .map(|word| {
+            unidecode(word)
+                .to_lowercase()
+                .trim_matches(|c: char| !c.is_alphanumeric())
+                .to_string()
+        })
+        .filter(|word| !word.is_empty())
+        .collect()
+}
+
+/// The name without its bracketed language markers
+fn without_english_markers(name: &str) -> String {
+    ENGLISH_MARKERS
+        .iter()
+        .fold(name.to_lowercase(), |name, marker| {
+            name.replace(marker, " ")
+        })
+}
+
+fn announce_language(name: &str) -> &'static str {
+    let lowercase_name = name.to_lowercase();
+    if ENGLISH_MARKERS
+        .iter()
+        .any(|marker| lowercase_name.contains(marker))
+        || words(name)
+            .iter()
+            .any(|word| ENGLISH_TOKENS.contains(&word.as_str()))
+    {
+        "en"
+    } else {
+        "fr"
+    }
+}
+
+impl WishlistItem {
+    fn is_same_game(&self, game: &Game) -> bool {
+        let announce = &game.okkazeo_announce;
+        if let (Some(barcode), Some(announce_barcode)) = (self.barcode, announce.barcode) {
+            if barcode == announce_barcode {
+                return true;
+            }
+        }
+        if let Some(bgg_id) = self.bgg_id {
+            if game
+                .review
+                .reviews
+                .get("bgg")
+                .and_then(|bgg| bgg_id_from_url(&bgg.url))
+                == Some(bgg_id)
+            {
+                return true;
+            }
+        }
+
+        // the edition and language words are part of the announce name
+        let mut name = self.name.clone();
+        if let Some(edition) = &self.edition {
+            name = format!("{} {}", name, edition);
+        }
+        let announce_name = words(&without_english_markers(&announce.name))
+            .into_iter()
+            .filter(|word| !ENGLISH_TOKENS.contains(&word.as_str()))
+            .collect::<Vec<String>>()
+            .join(" ");
+        are_names_similar(&name, &announce_name)
+    }
+
+    /// Check if an announce is the wanted game, within the price and language/edition constraints
+    pub fn matches(&self, game: &Game) -> bool {
+        let announce = &game.okkazeo_announce;
+        if let Some(max_price) = self.max_price {
+            if announce.price > max_price {
+                return false;
+            }
+        }
+        if let Some(language) = &self.language {
+            if announce_language(&announce.name) != language {
+                return false;
+            }
+        }
+        if let Some(edition) = &self.edition {
+            let announce_words = words(&announce.name);
+            if !words(edition)
+                .iter()
+                .all(|word| announce_words.contains(word))
+            {
+                return false;
+            }
+        }
+
+        self.is_same_game(game)
+    }
+}
+
+impl WishlistEntry {
+    pub fn new(item: WishlistItem, offers: Games) -> Self {
+        let cheapest_reference = offers
+            .games
+            .iter()
+            .filter_map(|game| game.cheapest_reference())
+            .map(|reference| reference.price)
+            .min_by(|a, b| a.total_cmp(b));
+
+        WishlistEntry {
+            item,
+            offers,
+            cheapest_reference,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::game::{Game, Games, Reference, Reviewer};
+
+    use super::{announce_language, WishlistEntry, WishlistForm, WishlistItem};
+
+    fn game(name: &str, price: f32, barcode: Option<u64>) -> Game {
+        let mut game = Game::default();
+        game.okkazeo_announce.name = name.to_string();
+        game.okkazeo_announce.price = price;
+        game.okkazeo_announce.barcode = barcode;
+        game
+    }
+
+    fn item(name: &str) -> WishlistItem {
+        WishlistItem {
+            name: name.to_string(),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_match_name() {
+        let skaal = item("Skaal");
+        assert!(skaal.matches(&game("Skaal", 20.0, None)));
+        assert!(skaal.matches(&game("SKAAL - VF", 20.0, None)));
+        assert!(!skaal.matches(&game("Skaal : Extension Rois", 20.0, None)));
+        assert!(!item("Runebound").matches(&game("Skaal", 20.0, None)));
+    }
+
+    #[test]
+    fn test_match_barcode_and_bgg_id() {
+        let mut wanted = item("Runebound 3eme edition");
+        wanted.barcode = Some(8435407612058);
+        assert!(wanted.matches(&game("Runebound", 30.0, Some(8435407612058))));
+        assert!(!wanted.matches(&game("Runebound", 30.0, Some(1))));
+
+        wanted.barcode = None;
+        wanted.bgg_id = Some(9829);
+        let mut runebound = game("Runebound", 30.0, None);
+        runebound.review.reviews.insert(
+            "bgg".to_string(),
+            Reviewer {
+                name: "bgg".to_string(),
+                url: "https://boardgamegeek.com/boardgame/9829/runebound".to_string(),
+                note: 6.22,
+                number: 1577,
+            },
+        );
+        assert!(wanted.matches(&runebound));
+    }
+
+    #[test]
+    fn test_match_constraints() {
+        let mut skaal = item("Skaal");
+        skaal.max_price = Some(25.0);
+        assert!(skaal.matches(&game("Skaal", 25.0, None)));
+        assert!(!skaal.matches(&game("Skaal", 25.5, None)));
+
+        skaal.language = Some("fr".to_string());
+        assert!(skaal.matches(&game("Skaal VF", 20.0, None)));
+        assert!(!skaal.matches(&game("Skaal VO", 20.0, None)));
+        skaal.language = Some("en".to_string());
+        assert!(skaal.matches(&game("Skaal (anglais)", 20.0, None)));
+        assert!(!skaal.matches(&game("Skaal", 20.0, None)));
+
+        assert!(skaal.matches(&game("Skaal (EN)", 20.0, None)));
+        assert!(skaal.matches(&game("Skaal [en]", 20.0, None)));
+
+        skaal.language = None;
+        skaal.edition = Some("Deluxe".to_string());
+        assert!(skaal.matches(&game("Skaal Deluxe", 20.0, None)));
+        assert!(!skaal.matches(&game("Skaal", 20.0, None)));
+    }
+
+    #[test]
+    fn test_french_en() {
+        assert_eq!(announce_language("Skaal en bon état"), "fr");
+        assert_eq!(announce_language("Carcassonne en boîte"), "fr");
+        assert_eq!(announce_language("Pandemic (US)"), "en");
+
+        let mut voyages = item("Voyages en Terre du Milieu");
+        voyages.language = Some("fr".to_string());
+        assert!(voyages.matches(&game("Voyages en Terre du Milieu", 50.0, None)));
+        assert!(!voyages.matches(&game("Voyages en Terre du Milieu (EN)", 50.0, None)));
+    }
+
+    #[test]
+    fn test_form() {
+        let form = WishlistForm {
+            name_form: " Skaal ".to_string(),
+            barcode_form: Some(String::new()),
+            bgg_id_form: Some("12".to_string()),
+            max_price_form: Some("19,5".to_string()),
+            language_form: Some(String::new()),
+            edition_form: None,
+        };
+        let item = form.to_item().unwrap();
+        assert_eq!(item.name, "Skaal");
+        assert_eq!(item.barcode, None);
+        assert_eq!(item.bgg_id, Some(12));
+        assert_eq!(item.max_price, Some(19.5));
+        assert_eq!(item.language, None);
+
+        assert!(WishlistForm::default().to_item().is_none());
+    }
+
+    #[test]
+    fn test_cheapest_reference() {
+        let mut offer = game("Skaal", 20.0, None);
+        for (name, price) in [("philibert", 35.0), ("ludocortex", 32.5), ("knapix", 0.0)] {
+            offer.references.insert(
+                name.to_string(),
+                Reference {
+                    name: name.to_string(),
+                    price,
+                    url: String::new(),
+                },
+            );
+        }
+        let mut offers = Games::new();
+        offers.games.push(Box::new(offer));
+
+        let entry = WishlistEntry::new(item("Skaal"), offers);
+        assert_eq!(entry.cheapest_reference, Some(32.5));
+        assert_eq!(
+            WishlistEntry::new(item("Skaal"), Games::new()).cheapest_reference,
+            None
+        );
+    }
+}
@@ -0,0 +1,154 @@
+use boardgame_finder::error::ScrapeError;
+use boardgame_finder::game::{get_game_infos, Deal, Game, OkkazeoAnnounce, Reference};
+use boardgame_finder::website::okkazeo::game_still_available;
+use chrono::NaiveDate;
+
+/// Serve the responses recorded in tests/fixtures instead of querying the websites
+fn replay_fixtures() {
+    std::env::set_var("HTTP_FIXTURES", "replay");
+    std::env::set_var("HTTP_FIXTURES_DIR", "tests/fixtures");
+}
+
+#[tokio::test]
+async fn test_get_game_infos() {
+    replay_fixtures();
+
+    let game = get_game_infos(None, 1234567).await.unwrap();
+
+    let announce = &game.okkazeo_announce;
+    assert_eq!(announce.name, "Runebound");
+    assert_eq!(announce.price, 25.0);
+    assert_eq!(announce.extension, "Jeu");
+    assert_eq!(announce.barcode, Some(3558380012345));
+    assert_eq!(announce.city.as_deref(), Some("Lyon (69003)"));
+    assert_eq!(
+        announce.last_modification_date.date_naive(),
+        NaiveDate::from_ymd_opt(2026, 10, 14).unwrap()
+    );
+    assert_eq!(announce.image, "img/runebound.jpg");
+    assert_eq!(announce.shipping.len(), 3);
+    assert_eq!(announce.shipping["hand_delivery"], 0.0);
+    assert_eq!(announce.shipping["Colissimo"], 7.5);
+    assert_eq!(announce.shipping["Mondial Relay"], 4.9);
+
+    assert_eq!(announce.seller.id, 4242);
+    assert_eq!(announce.seller.name, "meeplelover");
+    assert_eq!(announce.seller.nb_announces, 12);
+    assert!(!announce.seller.is_pro);
+    assert_eq!(announce.seller.rating, 4.9);
+    assert_eq!(announce.seller.nb_ratings, 127);
+
+    let mut references: Vec<(&str, f32)> = game
+        .references
+        .values()
+        .map(|reference| (reference.name.as_str(), reference.price))
+        .collect();
+    references.sort_by(|a, b| a.0.cmp(b.0));
+    assert_eq!(
+        references,
+        vec![
+            ("agorajeux", 39.9),
+            ("ludifolie", 42.5),
+            ("ludocortex", 45.0),
+            ("philibert", 44.9),
+        ]
+    );
+    assert_eq!(
+        game.references["agorajeux"].url,
+        "https://www.knapix.com/r/127347999"
+    );
+
+    let bgg = &game.review.reviews["bgg"];
+    assert_eq!(bgg.note, 6.22);
+    assert_eq!(bgg.number, 1577);
+    assert_eq!(
+        bgg.url,
+        "https://boardgamegeek.com/boardgame/9829/runebound"
+    );
+    assert_eq!(game.review.average_note, 6.22);
+
+    assert_eq!(game.deal.deal_price, -15);
+    assert_eq!(game.deal.deal_percentage, -37);
+}
+
+#[tokio::test]
+async fn test_refresh_references() {
+    replay_fixtures();
+
+    let reference = |name: &str, price: f32| {
+        (
+            name.to_string(),
+            Reference {
+                name: name.to_string(),
+                price,
+                url: format!("https://{}/runebound", name),
+            },
+        )
+    };
+    // only the shops searched by barcode are recorded for this name, the others failing
+    let mut game = Game {
+        okkazeo_announce: OkkazeoAnnounce {
+            id: 1234567,
+            name: "Runebound 2".to_string(),
+            price: 25.0,
+            barcode: Some(3558380012345),
+            ..Default::default()
+        },
+        references: [
+            reference("philibert", 50.0),
+            reference("ludocortex", 60.0),
+            reference("agorajeux", 39.9),
+            reference("ludifolie", 30.0),
+            reference("ultrajeux", 35.0),
+        ]
+        .into_iter()
+        .collect(),
+        deal: Deal {
+            deal_price: -99,
+            deal_percentage: -99,
+        },
+        ..Default::default()
+    };
+
+    game.refresh_references().await;
+
+    let mut references: Vec<(&str, f32)> = game
+        .references
+        .values()
+        .map(|reference| (reference.name.as_str(), reference.price))
+        .collect();
+    references.sort_by(|a, b| a.0.cmp(b.0));
+    assert_eq!(
+        references,
+        vec![
+            ("agorajeux", 39.9),
+            ("ludifolie", 30.0),
+            ("ludocortex", 45.0),
+            ("philibert", 44.9),
+            ("ultrajeux", 35.0),
+        ]
+    );
+    assert_eq!(game.deal.deal_price, -5);
+    assert_eq!(game.deal.deal_percentage, -17);
+}
+
+#[tokio::test]
+async fn test_get_game_infos_sold() {
+    replay_fixtures();
+
+    assert!(matches!(
+        get_game_infos(None, 7654321).await,
+        Err(ScrapeError::NotFound { .. })
+    ));
+}
+
+#[tokio::test]
+async fn test_game_still_available() {
+    replay_fixtures();
+
+    assert!(game_still_available(1234567).await.unwrap());
+    // okkazeo redirects to the announces list once an announce is sold
+    assert!(!game_still_available(7654321).await.unwrap());
+    // not recorded
+    assert!(game_still_available(1).await.is_err());
+}
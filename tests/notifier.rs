@@ -0,0 +1,363 @@
+use std::collections::HashMap;
+use std::error::Error;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use async_trait::async_trait;
+use boardgame_finder::notifier::push::{Gotify, Ntfy};
+use boardgame_finder::notifier::smtp::{Smtp, SmtpConfig, SmtpSecurity};
+use boardgame_finder::notifier::webhook::{Discord, Slack, Webhook};
+use boardgame_finder::notifier::{Dispatcher, Notification, Notifier, SentNotifications};
+use tokio::io::{AsyncBufReadExt, AsyncReadExt, AsyncWriteExt, BufReader};
+use tokio::net::TcpListener;
+use tokio::sync::mpsc;
+
+/// A request received by the http stand-in
+struct HttpRequest {
+    request_line: String,
+    headers: Vec<(String, String)>,
+    body: String,
+}
+
+impl HttpRequest {
+    fn header(&self, name: &str) -> Option<&str> {
+        self.headers
+            .iter()
+            .find(|(k, _)| k.eq_ignore_ascii_case(name))
+            .map(|(_, v)| v.as_str())
+    }
+
+    fn json(&self) -> serde_json::Value {
+        serde_json::from_str(&self.body).unwrap()
+    }
+}
+
+/// Start an http server answering with the given status codes, one per connection,
+/// the last one being repeated. Returns its base url and the received requests
+async fn http_stand_in(statuses: Vec<u16>) -> (String, mpsc::UnboundedReceiver<HttpRequest>) {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    let (tx, rx) = mpsc::unbounded_channel();
+
+    tokio::spawn(async move {
+        let mut statuses = statuses.into_iter();
+        let mut status = 200;
+        loop {
+            let (stream, _) = listener.accept().await.unwrap();
+            status = statuses.next().unwrap_or(status);
+
+            let mut stream = BufReader::new(stream);
+            let mut request_line = String::new();
+            stream.read_line(&mut request_line).await.unwrap();
+
+            let mut headers = Vec::new();
+            loop {
+                let mut line = String::new();
+                stream.read_line(&mut line).await.unwrap();
+                let line = line.trim_end();
+                if line.is_empty() {
+                    break;
+                }
+                let (k, v) = line.split_once(':').unwrap();
+                headers.push((k.trim().to_string(), v.trim().to_string()));
+            }
+
+            let len = headers
+                .iter()
+                .find(|(k, _)| k.eq_ignore_ascii_case("content-length"))
+                .map(|(_, v)| v.parse::<usize>().unwrap())
+                .unwrap_or(0);
+            let mut body = vec![0; len];
+            stream.read_exact(&mut body).await.unwrap();
+
+            let response = format!(
+                "HTTP/1.1 {} Stand-in\r\nContent-Length: 0\r\nConnection: close\r\n\r\n",
+                status
+            );
+            stream.write_all(response.as_bytes()).await.unwrap();
+            stream.shutdown().await.unwrap();
+
+            let _ = tx.send(HttpRequest {
+                request_line: request_line.trim_end().to_string(),
+                headers,
+                body: String::from_utf8(body).unwrap(),
+            });
+        }
+    });
+
+    (format!("http://{}", addr), rx)
+}
+
+/// Start a minimal smtp server accepting every mail, returns its port and the received mails
+async fn smtp_stand_in() -> (u16, mpsc::UnboundedReceiver<String>) {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let port = listener.local_addr().unwrap().port();
+    let (tx, rx) = mpsc::unbounded_channel();
+
+    tokio::spawn(async move {
+        loop {
+            let (stream, _) = listener.accept().await.unwrap();
+            let mut stream = BufReader::new(stream);
+            stream
+                .write_all(b"220 localhost stand-in\r\n")
+                .await
+                .unwrap();
+
+            let mut data = String::new();
+            let mut in_data = false;
+            loop {
+                let mut line = String::new();
+                if stream.read_line(&mut line).await.unwrap() == 0 {
+                    break;
+                }
+
+                if in_data {
+                    if line == ".\r\n" {
+                        in_data = false;
+                        let _ = tx.send(std::mem::take(&mut data));
+                        stream.write_all(b"250 queued\r\n").await.unwrap();
+                    } else {
+                        data.push_str(&line);
+                    }
+                    continue;
+                }
+
+                let command = line.to_ascii_uppercase();
+                let reply: &[u8] = if command.starts_with("EHLO") || command.starts_with("HELO") {
+                    b"250 localhost\r\n"
+                } else if command.starts_with("DATA") {
+                    in_data = true;
+                    b"354 go ahead\r\n"
+                } else if command.starts_with("QUIT") {
+                    stream.write_all(b"221 bye\r\n").await.unwrap();
+                    break;
+                } else {
+                    b"250 ok\r\n"
+                };
+                stream.write_all(reply).await.unwrap();
+            }
+        }
+    });
+
+    (port, rx)
+}
+
+/// In memory record of the notifications sent, standing in for the notification_sent table
+#[derive(Default)]
+struct SentLog(Mutex<HashMap<(String, i32), f32>>);
+
+#[async_trait]
+impl SentNotifications for SentLog {
+    async fn is_sent(
+        &self,
+        channel: &str,
+        id: i32,
+        price: f32,
+    ) -> Result<bool, Box<dyn Error + Send + Sync>> {
+        let sent = self.0.lock().unwrap();
+        Ok(sent
+            .get(&(channel.to_string(), id))
+            .is_some_and(|sent_price| *sent_price <= price))
+    }
+
+    async fn set_sent(
+        &self,
+        channel: &str,
+        id: i32,
+        price: f32,
+    ) -> Result<(), Box<dyn Error + Send + Sync>> {
+        self.0
+            .lock()
+            .unwrap()
+            .insert((channel.to_string(), id), price);
+        Ok(())
+    }
+}
+
+fn notification() -> Notification {
+    Notification {
+        id: 42,
+        name: "Skaal".to_string(),
+        price: 20.0,
+        deal_percentage: -33,
+        seller: "bob".to_string(),
+        city: Some("Paris".to_string()),
+        url: "https://www.okkazeo.com/annonces/view/42".to_string(),
+        permalink: None,
+        reason: "Recherche \"pas cher\"".to_string(),
+    }
+}
+
+#[tokio::test]
+async fn test_webhook() {
+    let (url, mut rx) = http_stand_in(vec![200]).await;
+    Webhook::new(&format!("{}/hook", url))
+        .send(&notification())
+        .await
+        .unwrap();
+
+    let request = rx.recv().await.unwrap();
+    assert_eq!(request.request_line, "POST /hook HTTP/1.1");
+    assert_eq!(request.header("content-type"), Some("application/json"));
+    let body = request.json();
+    assert_eq!(body["id"], 42);
+    assert_eq!(body["name"], "Skaal");
+    assert_eq!(body["deal_percentage"], -33);
+}
+
+#[tokio::test]
+async fn test_discord() {
+    let (url, mut rx) = http_stand_in(vec![204]).await;
+    Discord::new(&url).send(&notification()).await.unwrap();
+
+    let body = rx.recv().await.unwrap().json();
+    assert_eq!(body["embeds"][0]["title"], "Skaal - 20.00€ (-33%)");
+    assert_eq!(
+        body["embeds"][0]["description"],
+        "Recherche \"pas cher\" : vendu par bob (Paris)"
+    );
+    assert_eq!(
+        body["embeds"][0]["url"],
+        "https://www.okkazeo.com/annonces/view/42"
+    );
+}
+
+#[tokio::test]
+async fn test_slack() {
+    let (url, mut rx) = http_stand_in(vec![200]).await;
+    Slack::new(&url).send(&notification()).await.unwrap();
+
+    let body = rx.recv().await.unwrap().json();
+    assert!(body["text"]
+        .as_str()
+        .unwrap()
+        .starts_with("*<https://www.okkazeo.com/annonces/view/42|Skaal - 20.00€ (-33%)>*"));
+}
+
+#[tokio::test]
+async fn test_ntfy() {
+    let (url, mut rx) = http_stand_in(vec![200]).await;
+    Ntfy::new(&url, "deals", Some("tk_secret".to_string()))
+        .send(&notification())
+        .await
+        .unwrap();
+
+    let request = rx.recv().await.unwrap();
+    assert_eq!(request.request_line, "POST / HTTP/1.1");
+    assert_eq!(request.header("authorization"), Some("Bearer tk_secret"));
+    let body = request.json();
+    assert_eq!(body["topic"], "deals");
+    assert_eq!(body["click"], "https://www.okkazeo.com/annonces/view/42");
+}
+
+#[tokio::test]
+async fn test_gotify() {
+    let (url, mut rx) = http_stand_in(vec![200]).await;
+    Gotify::new(&format!("{}/", url), "app_token")
+        .send(&notification())
+        .await
+        .unwrap();
+
+    let request = rx.recv().await.unwrap();
+    assert_eq!(request.request_line, "POST /message HTTP/1.1");
+    assert_eq!(request.header("x-gotify-key"), Some("app_token"));
+    assert_eq!(request.json()["title"], "Skaal - 20.00€ (-33%)");
+}
+
+#[tokio::test]
+async fn test_http_error() {
+    let (url, _rx) = http_stand_in(vec![500]).await;
+    assert!(Webhook::new(&url).send(&notification()).await.is_err());
+}
+
+#[tokio::test]
+async fn test_smtp() {
+    let (port, mut rx) = smtp_stand_in().await;
+    let smtp = Smtp::new(SmtpConfig {
+        host: "127.0.0.1".to_string(),
+        port,
+        security: SmtpSecurity::None,
+        credentials: None,
+        from: "aubonmeeple@localhost".to_string(),
+        to: "me@localhost".to_string(),
+    })
+    .unwrap();
+    smtp.send(&notification()).await.unwrap();
+
+    let mail = rx.recv().await.unwrap();
+    assert!(mail.contains("To: me@localhost"));
+    assert!(mail.contains("Subject: "));
+    assert!(mail.contains("https://www.okkazeo.com/annonces/view/42"));
+}
+
+#[tokio::test]
+async fn test_retry() {
+    let (url, mut rx) = http_stand_in(vec![503, 500, 200]).await;
+    let webhook = Webhook::new(&url);
+    let dispatcher = Dispatcher::new(
+        vec![],
+        Arc::new(SentLog::default()),
+        3,
+        Duration::from_millis(10),
+    );
+
+    dispatcher
+        .send_with_retry(&webhook, &notification())
+        .await
+        .unwrap();
+    for _ in 0..3 {
+        rx.recv().await.unwrap();
+    }
+}
+
+#[tokio::test]
+async fn test_retry_exhausted() {
+    let (url, mut rx) = http_stand_in(vec![500]).await;
+    let webhook = Webhook::new(&url);
+    let dispatcher = Dispatcher::new(
+        vec![],
+        Arc::new(SentLog::default()),
+        2,
+        Duration::from_millis(10),
+    );
+
+    assert!(dispatcher
+        .send_with_retry(&webhook, &notification())
+        .await
+        .is_err());
+    for _ in 0..2 {
+        rx.recv().await.unwrap();
+    }
+    assert!(rx.try_recv().is_err());
+}
+
+#[tokio::test]
+async fn test_notify_once_per_price() {
+    let (url, mut rx) = http_stand_in(vec![200]).await;
+    let sent = Arc::new(SentLog::default());
+    let dispatcher = Dispatcher::new(
+        vec![Box::new(Webhook::new(&url))],
+        sent.clone(),
+        3,
+        Duration::from_millis(10),
+    );
+
+    // the second one comes while the first is being delivered
+    dispatcher.notify(&notification());
+    dispatcher.notify(&notification());
+    rx.recv().await.unwrap();
+    while !sent.is_sent("webhook", 42, 20.0).await.unwrap() {
+        tokio::time::sleep(Duration::from_millis(10)).await;
+    }
+
+    // the third one comes once the first is recorded
+    dispatcher.notify(&notification());
+    tokio::time::sleep(Duration::from_millis(100)).await;
+    assert!(rx.try_recv().is_err());
+
+    // a lower price is notified again
+    let mut repriced = notification();
+    repriced.price = 15.0;
+    dispatcher.notify(&repriced);
+    assert_eq!(rx.recv().await.unwrap().json()["price"], 15.0);
+}
@@ -0,0 +1,323 @@
+use boardgame_finder::frontlib::server::State;
+use boardgame_finder::frontlib::{Filters, Pagination, Sort};
+use boardgame_finder::game::{Game, Reference, Reviewer};
+use boardgame_finder::storage::sqlite::SqliteStorage;
+use boardgame_finder::storage::Storage;
+use boardgame_finder::website::okkazeo::FeedState;
+use chrono::{TimeZone, Utc};
+
+fn game(id: u32, name: &str, price: f32, city: &str, day: u32) -> Game {
+    let mut game = Game::default();
+    game.okkazeo_announce.id = id;
+    game.okkazeo_announce.name = name.to_string();
+    game.okkazeo_announce.price = price;
+    game.okkazeo_announce.url = format!("https://www.okkazeo.com/annonces/view/{}", id);
+    game.okkazeo_announce.extension = "Jeu".to_string();
+    game.okkazeo_announce.city = Some(city.to_string());
+    game.okkazeo_announce.last_modification_date =
+        Utc.with_ymd_and_hms(2024, 1, day, 12, 0, 0).unwrap();
+    game.okkazeo_announce.seller.id = 1;
+    game.okkazeo_announce.seller.name = "bob".to_string();
+    game.okkazeo_announce.seller.rating = 4.5;
+    game.okkazeo_announce
+        .shipping
+        .insert("hand_delivery".to_string(), 0.0);
+    game.references.insert(
+        "philibert".to_string(),
+        Reference {
+            name: "philibert".to_string(),
+            price: 40.0,
+            url: "https://www.philibert.net".to_string(),
+        },
+    );
+    game.get_deal_advantage();
+    game
+}
+
+/// Catane (cheap, reviewed, shipped), Azul and Skaal (by a pro seller)
+async fn storage() -> SqliteStorage {
+    let storage = SqliteStorage::open(":memory:").unwrap();
+
+    let mut catane = game(1, "Les Colons de Catane", 20.0, "Paris (75011)", 3);
+    catane
+        .okkazeo_announce
+        .shipping
+        .insert("colissimo".to_string(), 8.0);
+    catane.review.reviews.insert(
+        "bgg".to_string(),
+        Reviewer {
+            name: "bgg".to_string(),
+            url: "https://boardgamegeek.com/boardgame/13/catan".to_string(),
+            note: 7.1,
+            number: 100,
+        },
+    );
+    storage.upsert_game(&catane).await.unwrap();
+    storage
+        .upsert_game(&game(2, "Azul", 30.0, "Lyon (69003)", 2))
+        .await
+        .unwrap();
+    let mut skaal = game(3, "Skaal", 35.0, "Nantes (44000)", 1);
+    skaal.okkazeo_announce.seller.id = 2;
+    skaal.okkazeo_announce.seller.name = "Ludothèque".to_string();
+    skaal.okkazeo_announce.seller.is_pro = true;
+    skaal.okkazeo_announce.seller.rating = 3.0;
+    storage.upsert_game(&skaal).await.unwrap();
+
+    storage
+}
+
+fn state(filters: Filters, sort: &str) -> State {
+    State {
+        pagination: Pagination::default(),
+        filters,
+        sort: Sort {
+            sort: sort.to_string(),
+        },
+    }
+}
+
+async fn listed_ids(storage: &SqliteStorage, filters: Filters) -> Vec<u32> {
+    let count = storage.count_games(&filters).await.unwrap();
+    let games = storage
+        .select_games(&state(filters, "updated"))
+        .await
+        .unwrap();
+    assert_eq!(games.games.len() as i64, count);
+
+    games
+        .games
+        .iter()
+        .map(|game| game.okkazeo_announce.id)
+        .collect()
+}
+
+#[tokio::test]
+async fn test_select_game() {
+    let storage = storage().await;
+
+    let catane = storage.select_game(1).await.unwrap().unwrap();
+    assert_eq!(catane.okkazeo_announce.name, "Les Colons de Catane");
+    assert_eq!(catane.okkazeo_announce.price, 20.0);
+    assert_eq!(
+        catane.okkazeo_announce.city.as_deref(),
+        Some("Paris (75011)")
+    );
+    assert_eq!(
+        catane.okkazeo_announce.last_modification_date,
+        Utc.with_ymd_and_hms(2024, 1, 3, 12, 0, 0).unwrap()
+    );
+    assert_eq!(catane.okkazeo_announce.seller.name, "bob");
+    assert_eq!(catane.okkazeo_announce.shipping.len(), 2);
+    assert_eq!(catane.references["philibert"].price, 40.0);
+    assert_eq!(catane.review.reviews["bgg"].number, 100);
+    assert_eq!(catane.deal.deal_percentage, -50);
+
+    assert!(storage.select_game(42).await.unwrap().is_none());
+}
+
+#[tokio::test]
+async fn test_update_game() {
+    let storage = storage().await;
+
+    let mut azul = storage.select_game(2).await.unwrap().unwrap();
+    azul.okkazeo_announce.price = 25.0;
+    azul.references.clear();
+    azul.okkazeo_announce.shipping.clear();
+    storage.upsert_game(&azul).await.unwrap();
+
+    let azul = storage.select_game(2).await.unwrap().unwrap();
+    assert_eq!(azul.okkazeo_announce.price, 25.0);
+    assert!(azul.references.is_empty());
+    assert!(azul.okkazeo_announce.shipping.is_empty());
+}
+
+#[tokio::test]
+async fn test_update_keeps_seller_rating() {
+    let storage = storage().await;
+
+    // the seller profile could not be fetched this time
+    let mut azul = storage.select_game(2).await.unwrap().unwrap();
+    azul.okkazeo_announce.seller.rating = 0.0;
+    azul.okkazeo_announce.seller.nb_ratings = 0;
+    storage.upsert_game(&azul).await.unwrap();
+
+    let mut azul = storage.select_game(2).await.unwrap().unwrap();
+    assert_eq!(azul.okkazeo_announce.seller.rating, 4.5);
+
+    azul.okkazeo_announce.seller.rating = 4.0;
+    storage.upsert_game(&azul).await.unwrap();
+    let azul = storage.select_game(2).await.unwrap().unwrap();
+    assert_eq!(azul.okkazeo_announce.seller.rating, 4.0);
+}
+
+#[tokio::test]
+async fn test_delete_game() {
+    let storage = storage().await;
+
+    storage.delete_game(1).await.unwrap();
+    assert!(storage.select_game(1).await.unwrap().is_none());
+    assert_eq!(listed_ids(&storage, Filters::default()).await, vec![2, 3]);
+}
+
+#[tokio::test]
+async fn test_filters() {
+    let storage = storage().await;
+
+    assert_eq!(
+        listed_ids(&storage, Filters::default()).await,
+        vec![1, 2, 3]
+    );
+    let cases = [
+        (
+            Filters {
+                name: Some("catane".to_string()),
+                ..Default::default()
+            },
+            vec![1],
+        ),
+        (
+            Filters {
+                city: Some("69".to_string()),
+                ..Default::default()
+            },
+            vec![2],
+        ),
+        (
+            Filters {
+                vendor: Some("ludotheque".to_string()),
+                ..Default::default()
+            },
+            vec![3],
+        ),
+        (
+            Filters {
+                pro: Some(true),
+                ..Default::default()
+            },
+            vec![1, 2],
+        ),
+        (
+            Filters {
+                delivery: Some(true),
+                ..Default::default()
+            },
+            vec![1],
+        ),
+        (
+            Filters {
+                note: Some(7.0),
+                ..Default::default()
+            },
+            vec![1],
+        ),
+        (
+            Filters {
+                seller_rating: Some(4.0),
+                ..Default::default()
+            },
+            vec![1, 2],
+        ),
+        (
+            Filters {
+                min_price: Some(25),
+                max_price: Some(32),
+                ..Default::default()
+            },
+            vec![2],
+        ),
+        (
+            Filters {
+                date: Some("2024-01-02".to_string()),
+                ..Default::default()
+            },
+            vec![1, 2],
+        ),
+        (
+            Filters {
+                type_game: false,
+                ..Default::default()
+            },
+            vec![],
+        ),
+    ];
+    for (filters, ids) in cases {
+        assert_eq!(
+            listed_ids(&storage, filters.clone()).await,
+            ids,
+            "{:?}",
+            filters
+        );
+    }
+}
+
+#[tokio::test]
+async fn test_sort_and_pagination() {
+    let storage = storage().await;
+
+    let mut state = state(Filters::default(), "price");
+    state.pagination.per_page = 2;
+    let ids: Vec<u32> = storage
+        .select_games(&state)
+        .await
+        .unwrap()
+        .games
+        .iter()
+        .map(|game| game.okkazeo_announce.id)
+        .collect();
+    assert_eq!(ids, vec![1, 2]);
+
+    state.pagination.page = 1;
+    let games = storage.select_games(&state).await.unwrap();
+    assert_eq!(games.games.len(), 1);
+    assert_eq!(games.games[0].okkazeo_announce.id, 3);
+}
+
+#[tokio::test]
+async fn test_name_suggestions() {
+    let storage = storage().await;
+
+    assert_eq!(
+        storage.select_name_suggestions("cat", 10).await.unwrap(),
+        vec!["Les Colons de Catane"]
+    );
+    assert_eq!(
+        storage.select_name_suggestions("a", 10).await.unwrap(),
+        vec!["Azul", "Les Colons de Catane", "Skaal"]
+    );
+    assert!(storage
+        .select_name_suggestions("zzz", 10)
+        .await
+        .unwrap()
+        .is_empty());
+}
+
+#[tokio::test]
+async fn test_feed_state() {
+    let storage = storage().await;
+    assert!(storage
+        .select_feed_state("okkazeo")
+        .await
+        .unwrap()
+        .is_none());
+
+    let state = FeedState {
+        last_id: 1234567,
+        last_date: Utc.with_ymd_and_hms(2026, 10, 14, 10, 0, 0).unwrap(),
+    };
+    storage.update_feed_state("okkazeo", &state).await.unwrap();
+    assert_eq!(
+        storage.select_feed_state("okkazeo").await.unwrap(),
+        Some(state.clone())
+    );
+
+    let state = FeedState {
+        last_id: 1234570,
+        last_date: state.last_date + chrono::Duration::minutes(5),
+    };
+    storage.update_feed_state("okkazeo", &state).await.unwrap();
+    assert_eq!(
+        storage.select_feed_state("okkazeo").await.unwrap(),
+        Some(state)
+    );
+}